@@ -0,0 +1,675 @@
+use crate::pcm::Pcm;
+use crate::ring_buffer::RingBuffer;
+use seed::prelude::{js_sys, Closure, JsCast, JsValue};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AnalyserNode, AudioBuffer, AudioContext, AudioProcessingEvent, AudioWorkletNode, AudioWorkletNodeOptions,
+    ChannelSplitterNode, GainNode, MediaStream, MediaStreamAudioDestinationNode, MediaStreamAudioSourceNode,
+    MediaStreamTrack,
+    OscillatorNode, ScriptProcessorNode,
+};
+
+// Web Audio graph used to measure the input level:
+// `MediaStream` -> `MediaStreamAudioSourceNode` -> `AnalyserNode`.
+// For stereo input there's a second branch measuring each channel on its own:
+// `MediaStreamAudioSourceNode` -> `ChannelSplitterNode` -> one `AnalyserNode` per channel.
+// The analysers aren't connected to the destination, so we don't play the mic back to the user.
+pub struct LevelMeter {
+    context: AudioContext,
+    // Kept only to make sure the nodes live as long as the graph.
+    _source: MediaStreamAudioSourceNode,
+    _splitter: Option<ChannelSplitterNode>,
+    analyser: AnalyserNode,
+    channel_analysers: Vec<AnalyserNode>,
+    samples: Vec<f32>,
+    frequencies: Vec<u8>,
+}
+
+impl LevelMeter {
+    pub fn new(stream: &MediaStream, fft_size: u32, channel_count: u32) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let source = context.create_media_stream_source(stream)?;
+        let analyser = context.create_analyser()?;
+        source.connect_with_audio_node(&analyser)?;
+        let (splitter, channel_analysers) = if channel_count >= 2 {
+            let splitter = context.create_channel_splitter_with_number_of_outputs(2)?;
+            source.connect_with_audio_node(&splitter)?;
+            let channel_analysers = (0..2)
+                .map(|output| {
+                    let analyser = context.create_analyser()?;
+                    splitter.connect_with_audio_node_and_output(&analyser, output)?;
+                    Ok(analyser)
+                })
+                .collect::<Result<Vec<_>, JsValue>>()?;
+            (Some(splitter), channel_analysers)
+        } else {
+            (None, Vec::new())
+        };
+        let mut level_meter = Self {
+            context,
+            _source: source,
+            _splitter: splitter,
+            analyser,
+            channel_analysers,
+            samples: Vec::new(),
+            frequencies: Vec::new(),
+        };
+        level_meter.set_fft_size(fft_size);
+        Ok(level_meter)
+    }
+
+    // `fft_size` has to be a power of two between 32 and 32768.
+    pub fn set_fft_size(&mut self, fft_size: u32) {
+        self.analyser.set_fft_size(fft_size);
+        for analyser in &self.channel_analysers {
+            analyser.set_fft_size(fft_size);
+        }
+        self.samples = vec![0.; self.analyser.fft_size() as usize];
+        self.frequencies = vec![0; self.analyser.frequency_bin_count() as usize];
+    }
+
+    // Magnitudes (0 - 255) of the frequency bins, from 0 Hz up to half the sample rate.
+    pub fn frequency_data(&mut self) -> &[u8] {
+        self.analyser.get_byte_frequency_data(&mut self.frequencies);
+        &self.frequencies
+    }
+
+    // Peak amplitude (0.0 - 1.0) of the latest analyser window.
+    pub fn level(&mut self) -> f32 {
+        peak(&self.analyser, &mut self.samples)
+    }
+
+    // Peak amplitude of each channel - `[L, R]` for stereo input, just the overall level for mono.
+    pub fn channel_levels(&mut self) -> Vec<f32> {
+        if self.channel_analysers.is_empty() {
+            return vec![self.level()];
+        }
+        let samples = &mut self.samples;
+        self.channel_analysers.iter().map(|analyser| peak(analyser, samples)).collect()
+    }
+}
+
+// All analysers share the FFT size, so they can share the sample buffer as well.
+fn peak(analyser: &AnalyserNode, samples: &mut [f32]) -> f32 {
+    analyser.get_float_time_domain_data(samples);
+    samples.iter().fold(0., |peak, sample| sample.abs().max(peak))
+}
+
+impl Drop for LevelMeter {
+    fn drop(&mut self) {
+        // `close` releases the audio hardware; the returned promise isn't interesting for us.
+        let _ = self.context.close();
+    }
+}
+
+// Processor for `WorkletMeter`. It sees every frame and reports the peak and RMS of
+// each ~20 ms window, so short transients the `Tick`-sampled analyser misses still show up.
+const PEAK_METER_PROCESSOR: &str = r#"
+class PeakMeter extends AudioWorkletProcessor {
+    constructor() {
+        super();
+        this.peak = 0;
+        this.squares = 0;
+        this.samples = 0;
+        this.frames = 0;
+    }
+
+    process(inputs) {
+        const channels = inputs[0];
+        for (const channel of channels) {
+            for (const sample of channel) {
+                this.peak = Math.max(this.peak, Math.abs(sample));
+                this.squares += sample * sample;
+            }
+            this.samples += channel.length;
+        }
+        this.frames += channels.length > 0 ? channels[0].length : 128;
+        if (this.frames >= sampleRate / 50) {
+            const rms = this.samples > 0 ? Math.sqrt(this.squares / this.samples) : 0;
+            this.port.postMessage({ peak: this.peak, rms });
+            this.peak = this.squares = this.samples = this.frames = 0;
+        }
+        return true;
+    }
+}
+registerProcessor("peak-meter", PeakMeter);
+"#;
+
+// Sample-accurate alternative to `LevelMeter::level`:
+// `MediaStream` -> `MediaStreamAudioSourceNode` -> `AudioWorkletNode` ("peak-meter").
+// The node has no outputs, so the browser processes it without it being connected to the destination.
+pub struct WorkletMeter {
+    context: AudioContext,
+    _source: MediaStreamAudioSourceNode,
+    node: AudioWorkletNode,
+    _on_message: Closure<dyn Fn(JsValue)>,
+    // `(peak, rms)` reported since the last `take_levels`.
+    levels: Rc<Cell<Option<(f32, f32)>>>,
+}
+
+impl WorkletMeter {
+    // Fails where `AudioWorklet` isn't available, e.g. in insecure contexts.
+    pub async fn new(stream: &MediaStream) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let result = async {
+            // The module is inlined through a blob URL so there's no extra file to deploy.
+            let mut options = web_sys::BlobPropertyBag::new();
+            options.type_("application/javascript");
+            let blob = web_sys::Blob::new_with_str_sequence_and_options(
+                &js_sys::Array::of1(&PEAK_METER_PROCESSOR.into()),
+                &options,
+            )?;
+            let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+            let loaded = match context.audio_worklet()?.add_module(&url) {
+                Ok(promise) => JsFuture::from(promise).await.map(drop),
+                Err(error) => Err(error),
+            };
+            web_sys::Url::revoke_object_url(&url)?;
+            loaded?;
+
+            let source = context.create_media_stream_source(stream)?;
+            let mut node_options = AudioWorkletNodeOptions::new();
+            node_options.number_of_outputs(0);
+            let node = AudioWorkletNode::new_with_options(&context, "peak-meter", &node_options)?;
+            let levels = Rc::new(Cell::new(None::<(f32, f32)>));
+            let reported = Rc::clone(&levels);
+            let on_message = Closure::wrap(Box::new(move |event: JsValue| {
+                let data = js_sys::Reflect::get(&event, &"data".into()).unwrap_or(JsValue::UNDEFINED);
+                let get = |key: &str| {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let value = js_sys::Reflect::get(&data, &key.into()).ok()?.as_f64()? as f32;
+                    Some(value)
+                };
+                if let (Some(peak), Some(rms)) = (get("peak"), get("rms")) {
+                    // Several windows may arrive between two ticks; keep the loudest.
+                    let (held_peak, held_rms) = reported.get().unwrap_or_default();
+                    reported.set(Some((peak.max(held_peak), rms.max(held_rms))));
+                }
+            }) as Box<dyn Fn(JsValue)>);
+            node.port()?.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            source.connect_with_audio_node(&node)?;
+            Ok(Self {
+                context: context.clone(),
+                _source: source,
+                node,
+                _on_message: on_message,
+                levels,
+            })
+        }.await;
+        if result.is_err() {
+            let _ = context.close();
+        }
+        result
+    }
+
+    // `(peak, rms)` over everything processed since the previous call, `None` if nothing was.
+    pub fn take_levels(&self) -> Option<(f32, f32)> {
+        self.levels.take()
+    }
+}
+
+impl Drop for WorkletMeter {
+    fn drop(&mut self) {
+        if let Ok(port) = self.node.port() {
+            port.set_onmessage(None);
+        }
+        let _ = self.node.disconnect();
+        let _ = self.context.close();
+    }
+}
+
+// Name a user-provided worklet module has to register its processor under, see `ProcessingGraph`.
+pub const CUSTOM_PROCESSOR_NAME: &str = "recorder-processor";
+
+// Routes the mic through a user-provided `AudioWorkletProcessor` before it's recorded:
+// `MediaStream` -> `MediaStreamAudioSourceNode` -> `AudioWorkletNode` -> `MediaStreamAudioDestinationNode`.
+// The recorder then records `output` - the processed audio plus the input's video tracks, if any.
+//
+// Protocol for the worklet module (stable):
+// - It calls `registerProcessor("recorder-processor", ...)`.
+// - The node has one input and one output with as many channels as the mic;
+//   whatever the processor writes to its output is what gets recorded.
+// - The app posts `{ type: "start" }` on its port when a take starts and `{ type: "stop" }`
+//   when it stops or is cancelled. Between takes the processor keeps running (unrecorded).
+// - The processor may post `{ type: "log", message: "..." }`; the message is logged at info level.
+//   Anything else it posts is ignored.
+pub struct ProcessingGraph {
+    context: AudioContext,
+    // `id` of the stream the graph reads.
+    input_id: String,
+    _source: MediaStreamAudioSourceNode,
+    node: AudioWorkletNode,
+    _destination: MediaStreamAudioDestinationNode,
+    _on_message: Closure<dyn Fn(JsValue)>,
+    output: MediaStream,
+}
+
+impl ProcessingGraph {
+    pub async fn new(stream: &MediaStream, module_url: &str, channel_count: u32) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let result = async {
+            JsFuture::from(context.audio_worklet()?.add_module(module_url)?).await?;
+            let source = context.create_media_stream_source(stream)?;
+            let mut options = AudioWorkletNodeOptions::new();
+            options
+                .number_of_inputs(1)
+                .number_of_outputs(1)
+                .output_channel_count(&js_sys::Array::of1(&channel_count.into()));
+            let node = AudioWorkletNode::new_with_options(&context, CUSTOM_PROCESSOR_NAME, &options)?;
+            let on_message = Closure::wrap(Box::new(|event: JsValue| {
+                let data = js_sys::Reflect::get(&event, &"data".into()).unwrap_or(JsValue::UNDEFINED);
+                let field = |key: &str| js_sys::Reflect::get(&data, &key.into()).ok().and_then(|value| value.as_string());
+                if field("type").as_deref() == Some("log") {
+                    log_info!("Worklet:", field("message").unwrap_or_default());
+                }
+            }) as Box<dyn Fn(JsValue)>);
+            node.port()?.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            let destination = context.create_media_stream_destination()?;
+            source.connect_with_audio_node(&node)?;
+            node.connect_with_audio_node(&destination)?;
+            let output = destination.stream();
+            for track in stream.get_video_tracks().iter() {
+                output.add_track(&track.unchecked_into::<MediaStreamTrack>());
+            }
+            Ok(Self {
+                context: context.clone(),
+                input_id: stream.id(),
+                _source: source,
+                node,
+                _destination: destination,
+                _on_message: on_message,
+                output,
+            })
+        }.await;
+        if result.is_err() {
+            let _ = context.close();
+        }
+        result
+    }
+
+    pub fn reads(&self, stream: &MediaStream) -> bool {
+        self.input_id == stream.id()
+    }
+
+    pub const fn output(&self) -> &MediaStream {
+        &self.output
+    }
+
+    // Posts `{ type: message_type }` to the processor.
+    pub fn post(&self, message_type: &str) {
+        let message = js_sys::Object::new();
+        let posted = js_sys::Reflect::set(&message, &"type".into(), &message_type.into())
+            .and_then(|_| self.node.port()?.post_message(&message));
+        if let Err(error) = posted {
+            log_error!("Error posting to the worklet:", error);
+        }
+    }
+}
+
+impl Drop for ProcessingGraph {
+    fn drop(&mut self) {
+        if let Ok(port) = self.node.port() {
+            port.set_onmessage(None);
+        }
+        let _ = self.node.disconnect();
+        let _ = self.context.close();
+    }
+}
+
+// Gains `suggested_gain` picks from; beyond them the input is better fixed at the source.
+pub const MIN_GAIN: f32 = 0.1;
+pub const MAX_GAIN: f32 = 10.;
+
+// Gain that brings the measured `peak` (0.0 - 1.0) to `target_peak`.
+// Silence (or no measurement) gives no hint, so it keeps the input as is.
+pub fn suggested_gain(peak: f32, target_peak: f32) -> f32 {
+    if peak.is_nan() || peak <= 0. {
+        return 1.;
+    }
+    (target_peak / peak).max(MIN_GAIN).min(MAX_GAIN)
+}
+
+// Applies a fixed gain to the mic before it's recorded:
+// `MediaStream` -> `MediaStreamAudioSourceNode` -> `GainNode` -> `MediaStreamAudioDestinationNode`.
+pub struct GainStage {
+    context: AudioContext,
+    _source: MediaStreamAudioSourceNode,
+    _gain: GainNode,
+    output: MediaStream,
+}
+
+impl GainStage {
+    pub fn new(stream: &MediaStream, gain: f32) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let source = context.create_media_stream_source(stream)?;
+        let gain_node = context.create_gain()?;
+        gain_node.gain().set_value(gain);
+        let destination = context.create_media_stream_destination()?;
+        source.connect_with_audio_node(&gain_node)?;
+        gain_node.connect_with_audio_node(&destination)?;
+        let output = destination.stream();
+        for track in stream.get_video_tracks().iter() {
+            output.add_track(&track.unchecked_into::<MediaStreamTrack>());
+        }
+        Ok(Self {
+            context,
+            _source: source,
+            _gain: gain_node,
+            output,
+        })
+    }
+
+    pub const fn output(&self) -> &MediaStream {
+        &self.output
+    }
+}
+
+impl Drop for GainStage {
+    fn drop(&mut self) {
+        let _ = self.context.close();
+    }
+}
+
+// Mixes the audio tracks of a stream (e.g. two mics acquired one by one) into one track,
+// each with its own gain: track -> `MediaStreamAudioSourceNode` -> `GainNode` -> `MediaStreamAudioDestinationNode`.
+// A track that ends (e.g. an unplugged mic) just falls silent; the mix goes on with the others.
+pub struct Mixer {
+    context: AudioContext,
+    tracks: Vec<MediaStreamTrack>,
+    _sources: Vec<MediaStreamAudioSourceNode>,
+    gains: Vec<GainNode>,
+    output: MediaStream,
+    _on_ended: Vec<Closure<dyn Fn(JsValue)>>,
+}
+
+impl Mixer {
+    // `gains` are per audio track, missing ones are 1.0. `on_ended` gets the index of a track that has ended.
+    pub fn new(stream: &MediaStream, gains: &[f32], on_ended: Rc<dyn Fn(usize)>) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let destination = context.create_media_stream_destination()?;
+        let tracks = stream.get_audio_tracks().iter()
+            .map(|track| track.unchecked_into::<MediaStreamTrack>())
+            .collect::<Vec<_>>();
+        let (mut sources, mut gain_nodes, mut on_ended_callbacks) = (Vec::new(), Vec::new(), Vec::new());
+        for (index, track) in tracks.iter().enumerate() {
+            let source = context.create_media_stream_source(&MediaStream::new_with_tracks(&js_sys::Array::of1(track))?)?;
+            let gain = context.create_gain()?;
+            gain.gain().set_value(gains.get(index).copied().unwrap_or(1.));
+            source.connect_with_audio_node(&gain)?;
+            gain.connect_with_audio_node(&destination)?;
+            let on_ended = Rc::clone(&on_ended);
+            let on_ended = Closure::wrap(Box::new(move |_: JsValue| on_ended(index)) as Box<dyn Fn(JsValue)>);
+            track.set_onended(Some(on_ended.as_ref().unchecked_ref()));
+            sources.push(source);
+            gain_nodes.push(gain);
+            on_ended_callbacks.push(on_ended);
+        }
+        let output = destination.stream();
+        for track in stream.get_video_tracks().iter() {
+            output.add_track(&track.unchecked_into::<MediaStreamTrack>());
+        }
+        Ok(Self {
+            context,
+            tracks,
+            _sources: sources,
+            gains: gain_nodes,
+            output,
+            _on_ended: on_ended_callbacks,
+        })
+    }
+
+    pub const fn output(&self) -> &MediaStream {
+        &self.output
+    }
+
+    // Applies right away, also while recording.
+    pub fn set_gain(&self, index: usize, gain: f32) {
+        if let Some(node) = self.gains.get(index) {
+            node.gain().set_value(gain);
+        }
+    }
+}
+
+impl Drop for Mixer {
+    fn drop(&mut self) {
+        for track in &self.tracks {
+            track.set_onended(None);
+        }
+        let _ = self.context.close();
+    }
+}
+
+// A sine wave played straight to the speakers, e.g. to check the output path
+// independently of the mic. The oscillator stops on its own after `duration_s`;
+// drop the `Tone` afterwards to close its context.
+pub struct Tone {
+    context: AudioContext,
+    oscillator: OscillatorNode,
+}
+
+impl Tone {
+    pub fn play(frequency: f32, duration_s: f64) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        oscillator.frequency().set_value(frequency);
+        oscillator.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        oscillator.stop_with_when(context.current_time() + duration_s)?;
+        Ok(Self { context, oscillator })
+    }
+}
+
+impl Drop for Tone {
+    fn drop(&mut self) {
+        let _ = self.oscillator.disconnect();
+        let _ = self.context.close();
+    }
+}
+
+// Chrome throttles timers in background tabs hard, unless the tab is playing audio.
+// This plays an inaudible oscillator (through a zero gain) so the page counts as audible
+// and the ticks keep coming. The price is a running audio device, i.e. a bit of power.
+pub struct KeepAlive {
+    context: AudioContext,
+    oscillator: OscillatorNode,
+    _gain: GainNode,
+}
+
+impl KeepAlive {
+    pub fn start() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.);
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        Ok(Self { context, oscillator, _gain: gain })
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        let _ = self.oscillator.stop();
+        let _ = self.oscillator.disconnect();
+        let _ = self.context.close();
+    }
+}
+
+// Hands the raw samples of a stream, a block per channel at a time, to `on_samples`:
+// `MediaStream` -> `MediaStreamAudioSourceNode` -> `ScriptProcessorNode`.
+// `ScriptProcessorNode` is deprecated, but unlike `AudioWorklet` it needs no separate module
+// and it's still supported everywhere. It only runs while connected to the destination;
+// its output buffers are never written to, so it's silent.
+struct Tap {
+    context: AudioContext,
+    _source: MediaStreamAudioSourceNode,
+    processor: ScriptProcessorNode,
+    _on_audio_process: Closure<dyn Fn(JsValue)>,
+}
+
+const TAP_BUFFER_SIZE: u32 = 4096;
+
+impl Tap {
+    fn new(stream: &MediaStream, channel_count: u32, on_samples: impl Fn(Vec<Vec<f32>>) + 'static) -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let source = context.create_media_stream_source(stream)?;
+        let processor = context
+            .create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+                TAP_BUFFER_SIZE,
+                channel_count,
+                1,
+            )?;
+        let on_audio_process = Closure::wrap(Box::new(move |event: JsValue| {
+            let buffer = match event.unchecked_into::<AudioProcessingEvent>().input_buffer() {
+                Ok(buffer) => buffer,
+                Err(error) => return log_error!("Error reading tapped samples:", error),
+            };
+            on_samples((0..channel_count).filter_map(|index| buffer.get_channel_data(index).ok()).collect());
+        }) as Box<dyn Fn(JsValue)>);
+        processor.set_onaudioprocess(Some(on_audio_process.as_ref().unchecked_ref()));
+        source.connect_with_audio_node(&processor)?;
+        processor.connect_with_audio_node(&context.destination())?;
+        Ok(Self {
+            context,
+            _source: source,
+            processor,
+            _on_audio_process: on_audio_process,
+        })
+    }
+}
+
+impl Drop for Tap {
+    fn drop(&mut self) {
+        self.processor.set_onaudioprocess(None);
+        let _ = self.processor.disconnect();
+        let _ = self.context.close();
+    }
+}
+
+// Copies the raw samples of a stream while it's being recorded, for lossless takes.
+pub struct PcmTap {
+    tap: Tap,
+    channels: Rc<RefCell<Vec<Vec<f32>>>>,
+    paused: Rc<Cell<bool>>,
+}
+
+impl PcmTap {
+    pub fn new(stream: &MediaStream, channel_count: u32) -> Result<Self, JsValue> {
+        let channels = Rc::new(RefCell::new(vec![Vec::new(); channel_count as usize]));
+        let paused = Rc::new(Cell::new(false));
+        let (captured, is_paused) = (Rc::clone(&channels), Rc::clone(&paused));
+        let tap = Tap::new(stream, channel_count, move |block| {
+            if is_paused.get() {
+                return;
+            }
+            for (channel, samples) in captured.borrow_mut().iter_mut().zip(block) {
+                channel.extend(samples);
+            }
+        })?;
+        Ok(Self { tap, channels, paused })
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.set(paused);
+    }
+
+    pub fn finish(self) -> Pcm {
+        Pcm {
+            sample_rate: self.tap.context.sample_rate(),
+            channels: self.channels.replace(Vec::new()),
+        }
+    }
+}
+
+// Keeps the last `seconds` of a stream, whether it's recorded or not, for an instant replay.
+pub struct ReplayTap {
+    tap: Tap,
+    buffer: Rc<RefCell<RingBuffer>>,
+}
+
+impl ReplayTap {
+    pub fn new(stream: &MediaStream, channel_count: u32, seconds: u32) -> Result<Self, JsValue> {
+        // The buffer is sized at the context's rate, which is only known once it's there.
+        let buffer = Rc::new(RefCell::new(RingBuffer::new(0, 0)));
+        let filled = Rc::clone(&buffer);
+        let tap = Tap::new(stream, channel_count, move |block| filled.borrow_mut().push(&block))?;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let capacity = (f64::from(tap.context.sample_rate()) * f64::from(seconds)) as usize;
+        buffer.replace(RingBuffer::new(channel_count as usize, capacity));
+        Ok(Self { tap, buffer })
+    }
+
+    pub fn buffered_s(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let frames = self.buffer.borrow().len() as f64;
+        frames / f64::from(self.tap.context.sample_rate())
+    }
+
+    pub fn latest(&self, seconds: u32) -> Pcm {
+        let sample_rate = self.tap.context.sample_rate();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frames = (f64::from(sample_rate) * f64::from(seconds)) as usize;
+        Pcm { sample_rate, channels: self.buffer.borrow().latest(frames) }
+    }
+}
+
+// Decodes a whole recording (in any format the browser can play) into PCM.
+// The browser hands out the channels separately already, see `pcm::interleave` for the other layout.
+pub async fn decode(bytes: &[u8]) -> Result<Pcm, JsValue> {
+    let context = AudioContext::new()?;
+    // `decodeAudioData` detaches the buffer it gets, so it has to be a copy.
+    let array_buffer = js_sys::Uint8Array::from(bytes).buffer();
+    let result = async {
+        let buffer = JsFuture::from(context.decode_audio_data(&array_buffer)?)
+            .await?
+            .unchecked_into::<AudioBuffer>();
+        let channels = (0..buffer.number_of_channels())
+            .map(|channel| buffer.get_channel_data(channel))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pcm { sample_rate: buffer.sample_rate(), channels })
+    }.await;
+    let _ = context.close();
+    result
+}
+
+// Reduces the analyser's frequency bins to `band_count` bands by averaging neighbouring bins.
+pub fn frequency_bands(bins: &[u8], band_count: usize) -> Vec<u8> {
+    if bins.is_empty() || band_count == 0 {
+        return Vec::new();
+    }
+    let band_count = band_count.min(bins.len());
+    (0..band_count)
+        .map(|band| {
+            let bins = &bins[band * bins.len() / band_count..(band + 1) * bins.len() / band_count];
+            #[allow(clippy::cast_possible_truncation)]
+            let average = (bins.iter().map(|bin| u32::from(*bin)).sum::<u32>() / bins.len() as u32) as u8;
+            average
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brings_the_peak_to_the_target() {
+        assert!((suggested_gain(0.25, 0.5) - 2.).abs() < f32::EPSILON);
+        assert!((suggested_gain(1., 0.5) - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn keeps_the_gain_within_bounds() {
+        assert!((suggested_gain(0.001, 0.9) - MAX_GAIN).abs() < f32::EPSILON);
+        assert!((suggested_gain(100., 0.9) - MIN_GAIN).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn silence_keeps_the_input() {
+        assert!((suggested_gain(0., 0.9) - 1.).abs() < f32::EPSILON);
+        assert!((suggested_gain(f32::NAN, 0.9) - 1.).abs() < f32::EPSILON);
+    }
+}
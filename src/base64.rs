@@ -0,0 +1,51 @@
+// Standard base64 (RFC 4648) with padding, e.g. for `data:` URLs.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0_u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        // A chunk of n bytes makes n + 1 characters, padded to 4.
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(char::from(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize]));
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+pub fn data_url(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4648, section 10.
+    #[test]
+    fn matches_the_rfc_test_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, output) in vectors.iter() {
+            assert_eq!(encode(input.as_bytes()), *output, "{}", input);
+        }
+    }
+
+    #[test]
+    fn builds_a_data_url() {
+        assert_eq!(data_url("audio/wav", b"foobar"), "data:audio/wav;base64,Zm9vYmFy");
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{constraints::{Feature, CHANNEL_COUNTS}, CaptureMode, ChunkStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The recorder settings worth sharing between browsers, exported and imported as JSON.
+// The field names are camelCase like the rest of the JS-facing API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppConfig {
+    pub audio_bits_per_second: u32,
+    pub timeslice_ms: i32,
+    pub mime_type: String,
+    // `None` leaves it to the browser, see `AudioConstraints`.
+    pub sample_rate: Option<u32>,
+    pub channel_count: Option<u32>,
+    // By constraint name, e.g. `"echoCancellation": false`; missing ones are left to the browser.
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+    pub pipeline: PipelineConfig,
+    // See `file_name::stem`.
+    pub file_name_template: String,
+}
+
+// How a take is captured and turned into bytes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineConfig {
+    // `CaptureMode::as_str`
+    pub capture_mode: String,
+    // `ChunkStrategy::as_str`
+    pub chunk_strategy: String,
+    // The processing worklet, empty for none.
+    #[serde(default)]
+    pub worklet_url: String,
+}
+
+impl AppConfig {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|error| format!("The settings couldn't be exported: {}", error))
+    }
+
+    // Numbers out of their `limits` are clamped when applied, like typed-in ones;
+    // anything that can't be applied at all is an error.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let config = serde_json::from_str::<Self>(json).map_err(|error| format!("Not a valid config: {}", error))?;
+        if config.mime_type.trim().is_empty() {
+            return Err("The MIME type is empty".to_owned());
+        }
+        if config.sample_rate == Some(0) {
+            return Err("The sample rate is 0".to_owned());
+        }
+        if let Some(channel_count) = config.channel_count.filter(|count| !CHANNEL_COUNTS.contains(count)) {
+            return Err(format!("Unsupported channel count {}", channel_count));
+        }
+        if let Some(name) = config.features.keys().find(|name| feature(name).is_none()) {
+            return Err(format!("Unknown constraint {}", name));
+        }
+        if CaptureMode::parse(&config.pipeline.capture_mode).is_none() {
+            return Err(format!("Unknown capture mode {}", config.pipeline.capture_mode));
+        }
+        if ChunkStrategy::parse(&config.pipeline.chunk_strategy).is_none() {
+            return Err(format!("Unknown chunk strategy {}", config.pipeline.chunk_strategy));
+        }
+        Ok(config)
+    }
+
+    pub fn features(&self) -> impl Iterator<Item = (Feature, bool)> + '_ {
+        self.features.iter().filter_map(|(name, enabled)| Some((feature(name)?, *enabled)))
+    }
+}
+
+fn feature(name: &str) -> Option<Feature> {
+    Feature::ALL.iter().copied().find(|feature| feature.as_str() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AppConfig {
+        AppConfig {
+            audio_bits_per_second: 96_000,
+            timeslice_ms: 500,
+            mime_type: "audio/webm;codecs=opus".to_owned(),
+            sample_rate: Some(48000),
+            channel_count: None,
+            features: vec![("echoCancellation".to_owned(), false), ("autoGainControl".to_owned(), true)]
+                .into_iter()
+                .collect(),
+            pipeline: PipelineConfig {
+                capture_mode: "both".to_owned(),
+                chunk_strategy: "batched".to_owned(),
+                worklet_url: "https://example.com/processor.js".to_owned(),
+            },
+            file_name_template: "{date} interview {n}".to_owned(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let config = config();
+        assert_eq!(AppConfig::from_json(&config.to_json().unwrap()), Ok(config));
+    }
+
+    #[test]
+    fn rejects_what_cannot_be_applied() {
+        let mut unknown_feature = config();
+        unknown_feature.features.insert("loudness".to_owned(), true);
+        assert!(AppConfig::from_json(&unknown_feature.to_json().unwrap()).is_err());
+
+        let mut unknown_mode = config();
+        unknown_mode.pipeline.capture_mode = "raw".to_owned();
+        assert!(AppConfig::from_json(&unknown_mode.to_json().unwrap()).is_err());
+
+        let mut channels = config();
+        channels.channel_count = Some(6);
+        assert!(AppConfig::from_json(&channels.to_json().unwrap()).is_err());
+
+        assert!(AppConfig::from_json("{}").is_err());
+    }
+}
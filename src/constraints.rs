@@ -0,0 +1,129 @@
+use seed::prelude::{js_sys, JsCast, JsValue};
+use web_sys::{MediaStreamTrack, MediaTrackConstraints};
+
+// Offered when the browser can't tell what the mic supports (no `getCapabilities`, e.g. Firefox).
+pub const SAMPLE_RATES: [u32; 4] = [8000, 16000, 44100, 48000];
+pub const CHANNEL_COUNTS: [u32; 2] = [1, 2];
+
+// The browser's own processing of the mic signal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    EchoCancellation,
+    NoiseSuppression,
+    AutoGainControl,
+}
+
+impl Feature {
+    pub const ALL: [Self; 3] = [Self::EchoCancellation, Self::NoiseSuppression, Self::AutoGainControl];
+
+    // Also the name of the constraint and of the capability.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::EchoCancellation => "echoCancellation",
+            Self::NoiseSuppression => "noiseSuppression",
+            Self::AutoGainControl => "autoGainControl",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::EchoCancellation => "Echo cancellation",
+            Self::NoiseSuppression => "Noise suppression",
+            Self::AutoGainControl => "Auto gain",
+        }
+    }
+}
+
+// What's asked of the mic; `None` leaves it to the browser. The values are ideal rather than exact,
+// so a mic that can't do them is still used as it is.
+#[derive(Default)]
+pub struct AudioConstraints {
+    pub sample_rate: Option<u32>,
+    pub channel_count: Option<u32>,
+    features: Vec<(Feature, bool)>,
+}
+
+impl AudioConstraints {
+    pub fn feature(&self, feature: Feature) -> Option<bool> {
+        self.features.iter().find(|(f, _)| *f == feature).map(|(_, enabled)| *enabled)
+    }
+
+    pub fn set_feature(&mut self, feature: Feature, enabled: Option<bool>) {
+        self.features.retain(|(f, _)| *f != feature);
+        if let Some(enabled) = enabled {
+            self.features.push((feature, enabled));
+        }
+    }
+
+    pub fn apply(&self, constraints: &MediaTrackConstraints) {
+        // `web_sys` has no setter for `sampleRate`, so they all go through `Reflect` alike.
+        let set = |key: &str, value: JsValue| {
+            let _ = js_sys::Reflect::set(constraints, &key.into(), &value);
+        };
+        if let Some(sample_rate) = self.sample_rate {
+            set("sampleRate", sample_rate.into());
+        }
+        if let Some(channel_count) = self.channel_count {
+            set("channelCount", channel_count.into());
+        }
+        for (feature, enabled) in &self.features {
+            set(feature.as_str(), (*enabled).into());
+        }
+    }
+}
+
+// What the mic of a stream reports it can do, from `MediaStreamTrack.getCapabilities`.
+pub struct Capabilities {
+    // `(min, max)`
+    pub sample_rate: Option<(f64, f64)>,
+    pub channel_count: Option<(f64, f64)>,
+    // The values each feature can take, e.g. `[true, false]`.
+    pub features: Vec<(Feature, Vec<bool>)>,
+}
+
+// `None` where the browser doesn't have `getCapabilities`; `web_sys` doesn't bind it yet.
+pub fn read(track: &MediaStreamTrack) -> Option<Capabilities> {
+    let capabilities = js_sys::Reflect::get(track, &"getCapabilities".into()).ok()?
+        .dyn_into::<js_sys::Function>().ok()?
+        .call0(track).ok()?;
+    let range = |key: &str| {
+        let range = get(&capabilities, key);
+        Some((get(&range, "min").as_f64()?, get(&range, "max").as_f64()?))
+    };
+    Some(Capabilities {
+        sample_rate: range("sampleRate"),
+        channel_count: range("channelCount"),
+        features: Feature::ALL.iter()
+            .filter_map(|feature| {
+                let values = get(&capabilities, feature.as_str()).dyn_into::<js_sys::Array>().ok()?;
+                Some((*feature, values.iter().filter_map(|value| value.as_bool()).collect()))
+            })
+            .collect(),
+    })
+}
+
+// The presets the mic can do; all of them without capabilities.
+pub fn sample_rates(capabilities: Option<&Capabilities>) -> Vec<u32> {
+    within(&SAMPLE_RATES, capabilities.and_then(|capabilities| capabilities.sample_rate))
+}
+
+pub fn channel_counts(capabilities: Option<&Capabilities>) -> Vec<u32> {
+    within(&CHANNEL_COUNTS, capabilities.and_then(|capabilities| capabilities.channel_count))
+}
+
+pub fn feature_values(capabilities: Option<&Capabilities>, feature: Feature) -> Vec<bool> {
+    capabilities
+        .and_then(|capabilities| capabilities.features.iter().find(|(f, _)| *f == feature))
+        .map_or_else(|| vec![true, false], |(_, values)| values.clone())
+}
+
+fn within(presets: &[u32], range: Option<(f64, f64)>) -> Vec<u32> {
+    presets.iter()
+        .copied()
+        .filter(|preset| range.map_or(true, |(min, max)| (min..=max).contains(&f64::from(*preset))))
+        .collect()
+}
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(object, &key.into()).unwrap_or(JsValue::UNDEFINED)
+}
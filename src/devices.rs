@@ -0,0 +1,124 @@
+use seed::prelude::{js_sys, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{MediaDeviceInfo, MediaDeviceKind};
+
+// An input device from `enumerateDevices`. Labels are empty until the user
+// has granted the media permission, so we enumerate again after acquiring a stream.
+pub struct Device {
+    pub id: String,
+    // Devices of the same physical product (e.g. the mic and camera of a webcam) share a group.
+    pub group_id: String,
+    pub kind: MediaDeviceKind,
+    pub label: String,
+}
+
+// Chrome on Windows lists the system's default and default communications devices once more,
+// under these ids, next to the real devices (with the same group as the device they stand for).
+// Which mic is which can differ, so it matters which of the two gets picked.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DefaultRole {
+    Default,
+    Communications,
+}
+
+impl DefaultRole {
+    pub const ALL: [Self; 2] = [Self::Default, Self::Communications];
+
+    // Also the `deviceId` of the device that stands for it.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Communications => "communications",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|role| role.as_str() == value)
+    }
+}
+
+// Which camera of a phone (or tablet) to prefer, the `facingMode` of the video track.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FacingMode {
+    User,
+    Environment,
+}
+
+impl FacingMode {
+    pub const ALL: [Self; 2] = [Self::User, Self::Environment];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Environment => "environment",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.as_str() == value)
+    }
+
+    pub const fn flipped(self) -> Self {
+        match self {
+            Self::User => Self::Environment,
+            Self::Environment => Self::User,
+        }
+    }
+}
+
+impl Default for FacingMode {
+    fn default() -> Self {
+        Self::User
+    }
+}
+
+impl Device {
+    pub fn default_role(&self) -> Option<DefaultRole> {
+        DefaultRole::parse(&self.id)
+    }
+}
+
+// The mic that stands for `role`, if the browser lists one.
+pub fn default_mic(devices: &[Device], role: DefaultRole) -> Option<&Device> {
+    devices.iter().find(|device| device.kind == MediaDeviceKind::Audioinput && device.default_role() == Some(role))
+}
+
+pub async fn enumerate() -> Result<Vec<Device>, JsValue> {
+    let media_devices = seed::window().navigator().media_devices()?;
+    let devices = JsFuture::from(media_devices.enumerate_devices()?)
+        .await?
+        .unchecked_into::<js_sys::Array>();
+    Ok(devices.iter()
+        .map(|device| device.unchecked_into::<MediaDeviceInfo>())
+        .filter(|info| info.kind() != MediaDeviceKind::Audiooutput)
+        .map(|info| Device {
+            id: info.device_id(),
+            group_id: info.group_id(),
+            kind: info.kind(),
+            label: info.label(),
+        })
+        .collect())
+}
+
+// Devices grouped by `group_id`, groups in the order they were enumerated.
+// The default devices are left out - they'd only repeat the device they stand for.
+pub fn groups(devices: &[Device]) -> Vec<(&str, Vec<&Device>)> {
+    let mut groups = Vec::<(&str, Vec<&Device>)>::new();
+    for device in devices.iter().filter(|device| device.default_role().is_none()) {
+        match groups.iter_mut().find(|(group_id, _)| *group_id == device.group_id) {
+            Some((_, group)) => group.push(device),
+            None => groups.push((&device.group_id, vec![device])),
+        }
+    }
+    groups
+}
+
+pub fn camera_count(devices: &[Device]) -> usize {
+    devices.iter()
+        .filter(|device| device.kind == MediaDeviceKind::Videoinput && device.default_role().is_none())
+        .count()
+}
+
+pub fn group_has_kind(devices: &[Device], group_id: &str, kind: MediaDeviceKind) -> bool {
+    devices.iter().any(|device| device.group_id == group_id && device.kind == kind)
+}
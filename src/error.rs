@@ -0,0 +1,91 @@
+use seed::prelude::{js_sys, JsValue};
+
+// Why the mic (or the camera) couldn't be opened, classified by the `DOMException` name
+// of the rejected `getUserMedia` promise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecorderError {
+    // `NotAllowedError`: the user (or a policy) refused the permission.
+    PermissionDenied,
+    // `NotReadableError`: the device exists and is allowed but another application holds it,
+    // common on Windows. It usually goes away once the other app releases it, hence the retry.
+    DeviceBusy,
+    // `NotFoundError` / `OverconstrainedError`: nothing matches the device or the constraints.
+    NotFound,
+    // `AbortError`: the request was dropped midway, e.g. by a navigation or a quick cancel.
+    // Not a failure - nothing to tell the user, see `is_benign`.
+    Aborted,
+    Other(String),
+}
+
+impl RecorderError {
+    pub fn classify(error: &JsValue) -> Self {
+        let field = |key: &str| js_sys::Reflect::get(error, &key.into()).ok().and_then(|value| value.as_string());
+        let name = field("name").unwrap_or_default();
+        let message = field("message").unwrap_or_else(|| format!("{:?}", error));
+        Self::from_name(&name, message)
+    }
+
+    pub fn from_name(name: &str, message: String) -> Self {
+        match name {
+            "NotAllowedError" | "SecurityError" => Self::PermissionDenied,
+            "NotReadableError" | "TrackStartError" => Self::DeviceBusy,
+            "NotFoundError" | "OverconstrainedError" => Self::NotFound,
+            "AbortError" => Self::Aborted,
+            _ => Self::Other(message),
+        }
+    }
+
+    // The app just goes back to idle, without an error banner.
+    pub fn is_benign(&self) -> bool {
+        *self == Self::Aborted
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::PermissionDenied => "Access to the microphone was denied".to_owned(),
+            Self::DeviceBusy => "Another application is using the microphone".to_owned(),
+            Self::NotFound => "No matching microphone was found".to_owned(),
+            Self::Aborted => "Opening the microphone was cancelled".to_owned(),
+            Self::Other(message) => format!("The microphone couldn't be opened: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_every_known_name() {
+        let cases = [
+            ("NotAllowedError", RecorderError::PermissionDenied),
+            ("SecurityError", RecorderError::PermissionDenied),
+            ("NotReadableError", RecorderError::DeviceBusy),
+            ("TrackStartError", RecorderError::DeviceBusy),
+            ("NotFoundError", RecorderError::NotFound),
+            ("OverconstrainedError", RecorderError::NotFound),
+            ("AbortError", RecorderError::Aborted),
+        ];
+        for (name, error) in cases.iter() {
+            assert_eq!(&RecorderError::from_name(name, String::new()), error, "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_held_device_is_busy_not_denied() {
+        let error = RecorderError::from_name("NotReadableError", "Could not start audio source".to_owned());
+        assert_eq!(error, RecorderError::DeviceBusy);
+        assert!(!error.is_benign());
+        assert_eq!(error.message(), "Another application is using the microphone");
+    }
+
+    #[test]
+    fn keeps_the_message_of_unknown_names() {
+        assert_eq!(
+            RecorderError::from_name("TypeError", "bad constraints".to_owned()),
+            RecorderError::Other("bad constraints".to_owned()),
+        );
+        assert!(!RecorderError::from_name("", String::new()).is_benign());
+        assert!(RecorderError::from_name("AbortError", String::new()).is_benign());
+    }
+}
@@ -0,0 +1,59 @@
+// Names of the downloaded and saved files, from a template the user can change.
+// `{n}` is the 1-based number of the recording, `{date}` the day of the download (`YYYY-MM-DD`).
+// The extension is added by the callers.
+pub const DEFAULT_TEMPLATE: &str = "recording-{n}";
+
+// Characters no file system takes are replaced; an empty name falls back to the default template.
+pub fn stem(template: &str, number: usize, date: &str) -> String {
+    let template = if template.trim().is_empty() { DEFAULT_TEMPLATE } else { template.trim() };
+    template
+        .replace("{n}", &number.to_string())
+        .replace("{date}", date)
+        .chars()
+        .map(|c| if "/\\:*?\"<>|".contains(c) || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+// Names that repeat (a template without `{n}`) get ` (2)`, ` (3)`, ... before the extension,
+// so the files of a ZIP don't overwrite each other.
+pub fn unique(names: Vec<String>) -> Vec<String> {
+    let mut seen = Vec::<String>::with_capacity(names.len());
+    for name in names {
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => name.split_at(dot),
+            _ => (name.as_str(), ""),
+        };
+        let mut candidate = name.clone();
+        let mut count = 1;
+        while seen.contains(&candidate) {
+            count += 1;
+            candidate = format!("{} ({}){}", stem, count, extension);
+        }
+        seen.push(candidate);
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_placeholders() {
+        assert_eq!(stem(DEFAULT_TEMPLATE, 3, "2024-05-01"), "recording-3");
+        assert_eq!(stem("{date} take {n}", 12, "2024-05-01"), "2024-05-01 take 12");
+        assert_eq!(stem("interview", 1, "2024-05-01"), "interview");
+    }
+
+    #[test]
+    fn falls_back_and_replaces_invalid_characters() {
+        assert_eq!(stem("  ", 2, "2024-05-01"), "recording-2");
+        assert_eq!(stem("a/b:{n}", 2, "2024-05-01"), "a_b_2");
+    }
+
+    #[test]
+    fn numbers_repeated_names() {
+        let names = vec!["take.webm".to_owned(), "take.webm".to_owned(), "other.ogg".to_owned(), "take.webm".to_owned()];
+        assert_eq!(unique(names), ["take.webm", "take (2).webm", "other.ogg", "take (3).webm"]);
+    }
+}
@@ -0,0 +1,88 @@
+use seed::prelude::{js_sys, JsCast, JsValue};
+use std::collections::BTreeMap;
+use wasm_bindgen_futures::JsFuture;
+
+// Streams a take into a user-chosen file with the File System Access API,
+// so long recordings don't have to be held in memory.
+// `web_sys` has no bindings for that API yet, hence all the `Reflect`.
+pub struct FileSink {
+    pub name: String,
+    // A `FileSystemWritableFileStream`.
+    writable: JsValue,
+    // Chunks are read asynchronously and may finish out of order; they wait here until
+    // all the ones before them have been written.
+    waiting: BTreeMap<usize, Vec<u8>>,
+    next_index: usize,
+}
+
+pub fn is_supported() -> bool {
+    js_sys::Reflect::has(&seed::window(), &"showSaveFilePicker".into()).unwrap_or(false)
+}
+
+// Shows the save picker. `Ok(None)` means the user cancelled it.
+// Has to be called while handling a user gesture, otherwise the browser refuses to show it.
+pub async fn open(suggested_name: &str) -> Result<Option<FileSink>, JsValue> {
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &"suggestedName".into(), &suggested_name.into())?;
+    let handle = match JsFuture::from(call(&seed::window(), "showSaveFilePicker", &options)?).await {
+        Ok(handle) => handle,
+        Err(error) if error_name(&error).as_deref() == Some("AbortError") => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let writable = JsFuture::from(call(&handle, "createWritable", &JsValue::UNDEFINED)?).await?;
+    let name = js_sys::Reflect::get(&handle, &"name".into())?
+        .as_string()
+        .unwrap_or_else(|| suggested_name.to_owned());
+    Ok(Some(FileSink {
+        name,
+        writable,
+        waiting: BTreeMap::new(),
+        next_index: 0,
+    }))
+}
+
+impl FileSink {
+    // Queues the chunk `index` of the take; the stream itself queues the writes in order.
+    pub fn write_chunk(&mut self, index: usize, bytes: Vec<u8>) -> Result<(), JsValue> {
+        self.waiting.insert(index, bytes);
+        while let Some(bytes) = self.waiting.remove(&self.next_index) {
+            let data = js_sys::Uint8Array::from(bytes.as_slice());
+            spawn_logged(call(&self.writable, "write", &data)?, "Error writing to file:");
+            self.next_index += 1;
+        }
+        Ok(())
+    }
+
+    // Resolves once all the queued writes are on disk.
+    pub async fn close(self) -> Result<(), JsValue> {
+        JsFuture::from(call(&self.writable, "close", &JsValue::UNDEFINED)?).await?;
+        Ok(())
+    }
+
+    // Discards everything written so far; the file keeps its previous content.
+    pub fn abort(self) {
+        match call(&self.writable, "abort", &JsValue::UNDEFINED) {
+            Ok(promise) => spawn_logged(promise, "Error aborting file write:"),
+            Err(error) => log_error!("Error aborting file write:", error),
+        }
+    }
+}
+
+fn call(target: &JsValue, method: &str, arg: &JsValue) -> Result<js_sys::Promise, JsValue> {
+    js_sys::Reflect::get(target, &method.into())?
+        .unchecked_into::<js_sys::Function>()
+        .call1(target, arg)?
+        .dyn_into::<js_sys::Promise>()
+}
+
+fn spawn_logged(promise: js_sys::Promise, message: &'static str) {
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(error) = JsFuture::from(promise).await {
+            log_error!(message, error);
+        }
+    });
+}
+
+fn error_name(error: &JsValue) -> Option<String> {
+    js_sys::Reflect::get(error, &"name".into()).ok()?.as_string()
+}
@@ -0,0 +1,351 @@
+use crate::pcm::Pcm;
+use crate::wav::{quantize_i16, to_i24, BitDepth};
+
+// Encodes PCM as 16- or 24-bit FLAC: a `STREAMINFO` block, an optional `VORBIS_COMMENT` block and
+// fixed-size frames. Each subframe is the smallest of constant, verbatim and the fixed predictors
+// of order 0 - 4 with a single Rice partition - no LPC, so it compresses less than the reference
+// encoder, but it's valid FLAC that every decoder reads. The MD5 of the samples is left unset (all zeros),
+// which the format allows.
+const BLOCK_SIZE: usize = 4096;
+const MAX_FIXED_ORDER: usize = 4;
+// The frame header has 3 bits for the channel count, and the independent channel assignments go up to 8.
+const MAX_CHANNELS: usize = 8;
+// `STREAMINFO` has 20 bits for the rate in Hz.
+const MAX_SAMPLE_RATE: f32 = 655_350.;
+// 15 (`0b1111`) is the escape code of the 4-bit Rice parameter.
+const MAX_RICE_PARAMETER: u32 = 14;
+const VENDOR: &str = "mediarecorder";
+
+// `comments` are `KEY=value` pairs for the `VORBIS_COMMENT` block, e.g. `REPLAYGAIN_TRACK_GAIN=-3.20 dB`.
+// FLAC has no float samples, and no more than 8 channels.
+pub fn encode(pcm: &Pcm, bit_depth: BitDepth, comments: &[String]) -> Result<Vec<u8>, String> {
+    let quantize: fn(f32) -> i32 = match bit_depth {
+        BitDepth::Int16 => |sample| i32::from(quantize_i16(sample)),
+        BitDepth::Int24 => to_i24,
+        BitDepth::Float32 => return Err("FLAC can't hold 32-bit float samples".to_owned()),
+    };
+    let bits = u32::from(bit_depth.bits());
+    if pcm.channels.is_empty() || pcm.channels.len() > MAX_CHANNELS {
+        return Err(format!("FLAC holds 1 - {} channels, not {}", MAX_CHANNELS, pcm.channels.len()));
+    }
+    if !(1. ..=MAX_SAMPLE_RATE).contains(&pcm.sample_rate) {
+        return Err(format!("FLAC can't hold a sample rate of {} Hz", pcm.sample_rate));
+    }
+    let samples = pcm.channels.iter()
+        .map(|channel| channel.iter().map(|sample| quantize(*sample)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let frames = pcm.frames();
+
+    let mut bytes = b"fLaC".to_vec();
+    let has_comments = !comments.is_empty();
+    metadata_block(&mut bytes, 0, !has_comments, &stream_info(pcm, bits, frames));
+    if has_comments {
+        metadata_block(&mut bytes, 4, true, &vorbis_comment(comments));
+    }
+    for (number, start) in (0..frames).step_by(BLOCK_SIZE).enumerate() {
+        let end = (start + BLOCK_SIZE).min(frames);
+        let block = samples.iter().map(|channel| &channel[start..end]).collect::<Vec<_>>();
+        bytes.extend(frame(number, bits, &block));
+    }
+    Ok(bytes)
+}
+
+fn metadata_block(bytes: &mut Vec<u8>, block_type: u8, last: bool, data: &[u8]) {
+    let last_flag = if last { 0x80 } else { 0 };
+    bytes.push(last_flag | block_type);
+    #[allow(clippy::cast_possible_truncation)]
+    let len = data.len() as u32;
+    bytes.extend_from_slice(&len.to_be_bytes()[1..]);
+    bytes.extend_from_slice(data);
+}
+
+fn stream_info(pcm: &Pcm, bits: u32, frames: usize) -> Vec<u8> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let sample_rate = pcm.sample_rate as u64;
+    #[allow(clippy::cast_possible_truncation)]
+    let channel_count = pcm.channels.len() as u64;
+    let mut writer = BitWriter::default();
+    // Min and max block size; the last block may be shorter, which the minimum doesn't count.
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(BLOCK_SIZE as u64, 16);
+    // Min and max frame size, 0 is unknown.
+    writer.write(0, 24);
+    writer.write(0, 24);
+    writer.write(sample_rate, 20);
+    writer.write(channel_count.saturating_sub(1), 3);
+    writer.write(u64::from(bits - 1), 5);
+    writer.write(frames as u64, 36);
+    // MD5 of the samples, unset.
+    writer.write(0, 64);
+    writer.write(0, 64);
+    writer.finish()
+}
+
+// The lengths are little-endian here, unlike anywhere else in FLAC - it's the Vorbis format.
+fn vorbis_comment(comments: &[String]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let string = |data: &mut Vec<u8>, text: &str| {
+        #[allow(clippy::cast_possible_truncation)]
+        let len = text.len() as u32;
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(text.as_bytes());
+    };
+    string(&mut data, VENDOR);
+    #[allow(clippy::cast_possible_truncation)]
+    let count = comments.len() as u32;
+    data.extend_from_slice(&count.to_le_bytes());
+    for comment in comments {
+        string(&mut data, comment);
+    }
+    data
+}
+
+fn frame(number: usize, bits: u32, channels: &[&[i32]]) -> Vec<u8> {
+    let block_size = channels.first().map_or(0, |channel| channel.len());
+    let mut writer = BitWriter::default();
+    // Sync code, reserved bit, fixed block size.
+    writer.write(0b1111_1111_1111_1000, 16);
+    // Block size as a 16-bit value at the end of the header; sample rate from `STREAMINFO`.
+    writer.write(0b0111, 4);
+    writer.write(0b0000, 4);
+    // Independent channels, the sample size (`100` is 16 bits, `110` 24 bits), reserved bit.
+    writer.write(channels.len().saturating_sub(1) as u64, 4);
+    writer.write(if bits == 24 { 0b110 } else { 0b100 }, 3);
+    writer.write(0, 1);
+    for byte in utf8_number(number as u64) {
+        writer.write(u64::from(byte), 8);
+    }
+    writer.write(block_size.saturating_sub(1) as u64, 16);
+    let header = writer.bytes.clone();
+    writer.write(u64::from(crc8(&header)), 8);
+
+    for channel in channels {
+        subframe(&mut writer, bits, channel);
+    }
+    let mut bytes = writer.finish();
+    let crc = crc16(&bytes);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+    bytes
+}
+
+fn subframe(writer: &mut BitWriter, bits: u32, samples: &[i32]) {
+    let verbatim_bits = samples.len() as u64 * u64::from(bits);
+    if samples.windows(2).all(|pair| pair[0] == pair[1]) {
+        // Zero padding bit, type `000000`, no wasted bits.
+        writer.write(0, 8);
+        writer.write_signed(samples.first().copied().unwrap_or_default(), bits);
+        return;
+    }
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len()))
+        .map(|order| {
+            let residual = fixed_residual(samples, order);
+            let parameter = rice_parameter(&residual);
+            let size = order as u64 * u64::from(bits) + 10 + rice_bits(&residual, parameter);
+            (order, residual, parameter, size)
+        })
+        .min_by_key(|(_, _, _, size)| *size);
+    match best {
+        Some((order, residual, parameter, size)) if size < verbatim_bits => {
+            // Type `001xxx` with the order in `xxx`.
+            writer.write(0, 1);
+            writer.write(0b1000 | order as u64, 6);
+            writer.write(0, 1);
+            for sample in &samples[..order] {
+                writer.write_signed(*sample, bits);
+            }
+            // Rice coding with 4-bit parameters, a single partition.
+            writer.write(0b00, 2);
+            writer.write(0, 4);
+            writer.write(u64::from(parameter), 4);
+            for value in residual {
+                writer.write_rice(value, parameter);
+            }
+        }
+        _ => {
+            writer.write(0, 1);
+            writer.write(0b000001, 6);
+            writer.write(0, 1);
+            for sample in samples {
+                writer.write_signed(*sample, bits);
+            }
+        }
+    }
+}
+
+// The residual of the fixed polynomial predictor of `order` after the `order` warm-up samples,
+// i.e. the `order`-th difference of the signal.
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let mut residual = samples.to_vec();
+    for _ in 0..order {
+        residual = residual.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    }
+    residual
+}
+
+fn zigzag(value: i32) -> u32 {
+    #[allow(clippy::cast_sign_loss)]
+    let folded = ((value << 1) ^ (value >> 31)) as u32;
+    folded
+}
+
+// The parameter close to the optimum for a geometric distribution of the given mean.
+fn rice_parameter(residual: &[i32]) -> u32 {
+    if residual.is_empty() {
+        return 0;
+    }
+    let sum = residual.iter().map(|value| u64::from(zigzag(*value))).sum::<u64>();
+    let mean = sum / residual.len() as u64;
+    (u64::BITS - mean.leading_zeros()).min(MAX_RICE_PARAMETER)
+}
+
+fn rice_bits(residual: &[i32], parameter: u32) -> u64 {
+    residual.iter().map(|value| u64::from(zigzag(*value) >> parameter) + 1 + u64::from(parameter)).sum()
+}
+
+// The frame number in the variable-length "UTF-8" coding FLAC uses (up to 36 bits).
+fn utf8_number(number: u64) -> Vec<u8> {
+    if number < 0x80 {
+        #[allow(clippy::cast_possible_truncation)]
+        return vec![number as u8];
+    }
+    let mut continuation = Vec::new();
+    let mut rest = number;
+    // Each continuation byte carries 6 bits; the first byte gets what's left after them.
+    while rest >= 1 << (6 - continuation.len()).max(1) {
+        #[allow(clippy::cast_possible_truncation)]
+        continuation.push(0x80 | (rest & 0x3f) as u8);
+        rest >>= 6;
+    }
+    let count = continuation.len();
+    #[allow(clippy::cast_possible_truncation)]
+    let lead = (0xff_u16 << (7 - count)) as u8 | rest as u8;
+    std::iter::once(lead).chain(continuation.into_iter().rev()).collect()
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 == 0 { crc << 1 } else { (crc << 1) ^ 0x07 })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 == 0 { crc << 1 } else { (crc << 1) ^ 0x8005 }
+        })
+    })
+}
+
+// MSB-first, as all of FLAC's bit fields are.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    filled: u32,
+}
+
+impl BitWriter {
+    // The lowest `bits` bits of `value`.
+    fn write(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            #[allow(clippy::cast_possible_truncation)]
+            let bit = ((value >> shift) & 1) as u8;
+            self.current = (self.current << 1) | bit;
+            self.filled += 1;
+            if self.filled == 8 {
+                self.bytes.push(self.current);
+                self.current = 0;
+                self.filled = 0;
+            }
+        }
+    }
+
+    // Two's complement in `bits` bits.
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        #[allow(clippy::cast_sign_loss)]
+        let value = value as u32;
+        self.write(u64::from(value), bits);
+    }
+
+    fn write_rice(&mut self, value: i32, parameter: u32) {
+        let folded = zigzag(value);
+        for _ in 0..folded >> parameter {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+        self.write(u64::from(folded), parameter);
+    }
+
+    // Pads the last byte with zeros.
+    fn finish(mut self) -> Vec<u8> {
+        if self.filled > 0 {
+            self.write(0, 8 - self.filled);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn silence(channels: usize, frames: usize) -> Pcm {
+        Pcm { sample_rate: 44100., channels: vec![vec![0.; frames]; channels] }
+    }
+
+    #[test]
+    fn starts_with_marker_and_stream_info() {
+        let flac = encode(&silence(1, 10), BitDepth::Int16, &[]).unwrap();
+        let mut expected = b"fLaC".to_vec();
+        // Last metadata block, type 0, 34 bytes.
+        expected.extend_from_slice(&[0x80, 0x00, 0x00, 0x22]);
+        // Block sizes 4096, unknown frame sizes.
+        expected.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        // 44100 Hz, 1 channel, 16 bits, 10 samples.
+        expected.extend_from_slice(&[0x0a, 0xc4, 0x40, 0xf0, 0x00, 0x00, 0x00, 0x0a]);
+        expected.extend_from_slice(&[0; 16]);
+        assert_eq!(&flac[..expected.len()], expected.as_slice());
+    }
+
+    #[test]
+    fn crcs_match_check_values() {
+        assert_eq!(crc8(b"123456789"), 0xf4);
+        assert_eq!(crc16(b"123456789"), 0xfee8);
+    }
+
+    #[test]
+    fn frame_header_has_crc8() {
+        let block = [0; 16];
+        let mono = frame(0, 16, &[&block]);
+        assert_eq!(&mono[..8], &[0xff, 0xf8, 0x70, 0x08, 0x00, 0x00, 0x0f, 0x37]);
+
+        let stereo = frame(0, 24, &[&block, &block]);
+        assert_eq!(&stereo[..8], &[0xff, 0xf8, 0x70, 0x1c, 0x00, 0x00, 0x0f, 0x08]);
+    }
+
+    #[test]
+    fn frame_ends_with_crc16() {
+        let samples = (0..100).map(|i| (i * 37 % 201) - 100).collect::<Vec<i32>>();
+        let frame = frame(3, 16, &[&samples]);
+        // The CRC of the frame including its (big-endian) CRC is 0.
+        assert_eq!(crc16(&frame), 0);
+        let (body, crc) = frame.split_at(frame.len() - 2);
+        assert_eq!(crc16(body).to_be_bytes(), [crc[0], crc[1]]);
+    }
+
+    #[test]
+    fn frame_numbers_use_utf8_coding() {
+        assert_eq!(utf8_number(0x7f), [0x7f]);
+        assert_eq!(utf8_number(0x80), [0xc2, 0x80]);
+        assert_eq!(utf8_number(0x7ff), [0xdf, 0xbf]);
+        assert_eq!(utf8_number(0x800), [0xe0, 0xa0, 0x80]);
+    }
+
+    #[test]
+    fn rejects_what_flac_cannot_hold() {
+        assert!(encode(&silence(9, 10), BitDepth::Int16, &[]).is_err());
+        assert!(encode(&silence(0, 0), BitDepth::Int16, &[]).is_err());
+        assert!(encode(&silence(2, 10), BitDepth::Float32, &[]).is_err());
+        assert!(encode(&silence(8, 10), BitDepth::Int24, &[]).is_ok());
+    }
+}
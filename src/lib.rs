@@ -1,52 +1,278 @@
 #![allow(clippy::wildcard_imports)]
 
-use gloo_file::{Blob, futures::read_as_bytes};
+#[macro_use]
+mod logging;
+mod audio;
+mod base64;
+mod config;
+mod constraints;
+mod devices;
+mod error;
+mod file_name;
+mod file_sink;
+mod flac;
+mod hash;
+mod limits;
+mod live_playback;
+mod live_stream;
+mod loudness;
+mod mime;
+mod pcm;
+mod progress;
+mod quality;
+mod ring_buffer;
+mod storage;
+#[cfg(feature = "test-input")]
+mod test_input;
+mod upload;
+mod wav;
+mod zip;
+mod voice_activity;
+
+use gloo_file::{Blob, futures::{read_as_bytes, read_as_text}};
 use seed::{prelude::*, *};
 use wasm_bindgen_futures::JsFuture;
 use wasm_bindgen::closure::Closure;
-use web_sys::{MediaStreamConstraints, MediaStream, MediaRecorder, MediaRecorderOptions, BlobEvent};
+use config::{AppConfig, PipelineConfig};
+use audio::{GainStage, KeepAlive, LevelMeter, Mixer, PcmTap, ProcessingGraph, ReplayTap, Tone, WorkletMeter};
+use constraints::{AudioConstraints, Capabilities, Feature};
+use devices::{DefaultRole, Device, FacingMode};
+use error::RecorderError;
+use file_sink::FileSink;
+use live_playback::LivePlayback;
+use live_stream::{Connection, LiveStream, Overflow, SocketEvent};
+use logging::LogLevel;
+use loudness::Loudness;
+use quality::Quality;
+use mime::{Codec, Container};
+use pcm::{FadeCurve, Pcm};
+use progress::Value;
+use std::cell::RefCell;
+use std::rc::Rc;
+use storage::StoredRecording;
+use upload::{MultipartUpload, UploadMethod};
+use voice_activity::VoiceActivityDetector;
+use wav::BitDepth;
+use web_sys::{MediaDeviceKind, MediaStreamConstraints, MediaTrackConstraints, MediaStream, MediaStreamTrack, MediaRecorder, MediaRecorderOptions, BlobEvent, HtmlAudioElement, HtmlCanvasElement, HtmlInputElement, CanvasRenderingContext2d, CustomEvent, CustomEventInit, RecordingState};
+
+// Name of the DOM event dispatched on `document` when a recording is finalized.
+// Can be changed at start with e.g. `?event=my-recording-done`.
+const DEFAULT_COMPLETE_EVENT: &str = "recording-complete";
+
+// Long recordings are uploaded in parts so no single request runs into a timeout.
+const DEFAULT_UPLOAD_ENDPOINT: &str = "/upload";
+const UPLOAD_PART_SIZE: usize = 1024 * 1024;
+const UPLOAD_MAX_ATTEMPTS: u32 = 3;
+
+const DEFAULT_AUDIO_BITS_PER_SECOND: u32 = 64_000;
+const DEFAULT_TIMESLICE_MS: i32 = 1000;
+
+// Canvas size; one column of the waveform per pixel.
+const WAVEFORM_WIDTH: u32 = 600;
+const WAVEFORM_HEIGHT: u32 = 80;
+
+const FFT_SIZES: [u32; 7] = [256, 512, 1024, 2048, 4096, 8192, 16384];
+const DEFAULT_FFT_SIZE: u32 = 2048;
+const DEFAULT_SPECTRUM_BANDS: usize = 32;
+
+fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
+    // The log level can be chosen at start with e.g. `?log=debug`.
+    let log_level = query_param(&url, "log")
+        .and_then(LogLevel::parse)
+        .unwrap_or_default();
+    logging::set_level(log_level);
+    progress::set_enabled(query_param(&url, "progress") == Some("json"));
+
+    // `pagehide` is the last reliable moment to release the object URLs before the app is torn down.
+    orders.stream(streams::window_event(Ev::PageHide, |_| Msg::ReleaseRecordings));
+    orders.perform_cmd(async { Msg::RecordingsLoaded(storage::load_all().await) });
+    request_storage_estimate(orders);
+    orders.perform_cmd(async { Msg::DevicesEnumerated(devices::enumerate().await) });
+    let mut model = Model {
+        log_level,
+        mime_type: mime::mime_type(Container::default(), Codec::default()),
+        supported_formats: probe_formats(),
+        mime_candidates: mime::CANDIDATES.iter()
+            .map(|mime_type| (*mime_type, MediaRecorder::is_type_supported(mime_type)))
+            .collect(),
+        complete_event: query_param(&url, "event").unwrap_or(DEFAULT_COMPLETE_EVENT).to_owned(),
+        // A custom processor can also be set up front with e.g. `?worklet=https://example.com/processor.js`.
+        worklet_url: query_param(&url, "worklet").unwrap_or_default().to_owned(),
+        // Phones can start with the rear camera with `?facing=environment`.
+        facing_mode: query_param(&url, "facing").and_then(FacingMode::parse).unwrap_or_default(),
+        stream_url: query_param(&url, "stream").unwrap_or_default().to_owned(),
+        #[cfg(feature = "test-input")]
+        test_input: query_param(&url, "input") == Some("test-tone"),
+        upload_endpoint: DEFAULT_UPLOAD_ENDPOINT.to_owned(),
+        audio_bits_per_second: DEFAULT_AUDIO_BITS_PER_SECOND,
+        timeslice_ms: DEFAULT_TIMESLICE_MS,
+        fft_size: DEFAULT_FFT_SIZE,
+        spectrum_bands: DEFAULT_SPECTRUM_BANDS,
+        input_gain: 1.,
+        countdown_beeps: true,
+        replay_s: DEFAULT_REPLAY_S,
+        file_name_template: file_name::DEFAULT_TEMPLATE.to_owned(),
+        ..Model::default()
+    };
+    // `?prewarm=gesture` acquires the stream on the first pointer press, which counts as a gesture.
+    if query_param(&url, "prewarm") == Some("gesture") {
+        model.prewarm_gesture = Some(orders.stream_with_handle(streams::window_event(Ev::PointerDown, |_| Msg::Prewarm)));
+    }
+    request_audio_stream(&mut model, orders);
+    model
+}
+
+fn query_param<'a>(url: &'a Url, key: &str) -> Option<&'a str> {
+    url.search().get(key)
+        .and_then(|values| values.first())
+        .map(String::as_str)
+}
+
+// Constraints for the next `get_audio_stream`. With a device group selected, both the mic
+// and the camera are taken from it - as long as the group has that kind of device at all,
+// otherwise the browser is free to pick any device of that kind.
+fn stream_constraints(model: &Model) -> MediaStreamConstraints {
+    let group_with = |kind| model.group_id.as_deref()
+        .filter(|group_id| devices::group_has_kind(&model.devices, group_id, kind));
+
+    let mut constraints = MediaStreamConstraints::new();
+    let audio = match model.default_mic {
+        Some(role) => track_constraints("deviceId", Some(role.as_str())),
+        None => track_constraints("groupId", group_with(MediaDeviceKind::Audioinput)),
+    };
+    model.audio_constraints.apply(&audio);
+    constraints.audio(&audio);
+    if model.record_video {
+        let mut video = track_constraints("groupId", group_with(MediaDeviceKind::Videoinput));
+        // Ideal rather than exact, so it's ignored where there's no such camera (or not in the group).
+        video.facing_mode(&model.facing_mode.as_str().into());
+        constraints.video(&video);
+    }
+    constraints
+}
+
+// One set of constraints per mic of `mix_inputs`; the camera (if any) comes with the first one.
+fn mix_constraints(model: &Model) -> Vec<MediaStreamConstraints> {
+    model.mix_inputs.iter().enumerate()
+        .map(|(index, input)| {
+            let mut constraints = if index == 0 { stream_constraints(model) } else { MediaStreamConstraints::new() };
+            let audio = track_constraints("deviceId", Some(&input.device_id));
+            model.audio_constraints.apply(&audio);
+            constraints.audio(&audio);
+            constraints
+        })
+        .collect()
+}
 
-fn init(_: Url, orders: &mut impl Orders<Msg>) -> Model {
-    orders.perform_cmd(get_audio_stream());
-    Model::default()
+// `{ [key]: { exact: value } }`, or `{}` - any device - without a value.
+fn track_constraints(key: &str, value: Option<&str>) -> MediaTrackConstraints {
+    let constraints = MediaTrackConstraints::new();
+    if let Some(value) = value {
+        // `web_sys` doesn't have setters for `groupId` and `deviceId`.
+        let exact = js_sys::Object::new();
+        js_sys::Reflect::set(&exact, &"exact".into(), &value.into()).unwrap();
+        js_sys::Reflect::set(&constraints, &key.into(), &exact).unwrap();
+    }
+    constraints
 }
 
 // This is essentially copied from the seed user_media example;
 // just getting a stream from the client's media source (in my
 // case, the microphone)
-async fn get_audio_stream() -> Msg {
-    let mut constraints = MediaStreamConstraints::new();
-    constraints.audio(&JsValue::from(true));
+async fn get_audio_stream(constraints: MediaStreamConstraints) -> Msg {
+    match request_stream(&constraints).await {
+        Ok(stream) => Msg::AudioStream(stream),
+        Err(error) => stream_failed("Error getting user media:", &error),
+    }
+}
 
-    let navigator = seed::window().navigator();
+async fn get_mix_stream(constraints: Vec<MediaStreamConstraints>) -> Msg {
+    match request_mix_stream(&constraints).await {
+        Ok(stream) => Msg::AudioStream(stream),
+        Err(error) => stream_failed("Error getting user media for the mix:", &error),
+    }
+}
 
-    let media_devices = navigator.media_devices()
-        .map_err(|v| {
-            let sopt = v.dyn_ref::<js_sys::JsString>();
-            log!("Error getting media devices: {:?}", sopt);
-            // return error msg
-        })
-        .unwrap();
+fn stream_failed(context: &str, error: &JsValue) -> Msg {
+    let classified = RecorderError::classify(error);
+    if classified.is_benign() {
+        log_info!("Getting user media aborted:", error);
+    } else {
+        log_error!(context, error);
+    }
+    Msg::StreamFailed(classified)
+}
 
-    // We need to request access to user's video or audio through constraints.
-    // Otherwise it fails (at least on Windows).
-    let stream_promise = media_devices.get_user_media_with_constraints(&constraints)
-        .map_err(|v| {
-            let sopt = v.dyn_ref::<js_sys::JsString>();
-            log!("Error getting user media: {:?}", sopt);
-            // return error msg
-        })
-        .unwrap();
+// The mics one by one, with all their tracks in one stream for `Mixer`.
+// If any of them fails, the ones acquired so far are released again.
+async fn request_mix_stream(constraints: &[MediaStreamConstraints]) -> Result<MediaStream, JsValue> {
+    let mixed = MediaStream::new()?;
+    for constraints in constraints {
+        match request_stream(constraints).await {
+            Ok(stream) => {
+                for track in stream.get_tracks().iter() {
+                    mixed.add_track(&track.unchecked_into::<MediaStreamTrack>());
+                }
+            }
+            Err(error) => {
+                stop_tracks(&mixed);
+                return Err(error);
+            }
+        }
+    }
+    Ok(mixed)
+}
 
-    let stream = JsFuture::from(stream_promise)
-        .await
-        .map(MediaStream::from)
-        .map_err(|e| {
-            log!("Error extracting audio stream: {}", e);
-        })
-        .unwrap();
+async fn get_tab_audio_stream() -> Option<Msg> {
+    match request_tab_audio().await {
+        Ok(stream) => Some(Msg::TabAudioStream(stream)),
+        // Closing the picker rejects with the same error as a denied permission.
+        Err(error) => match RecorderError::classify(&error) {
+            RecorderError::PermissionDenied | RecorderError::Aborted => {
+                log_info!("Tab sharing cancelled");
+                None
+            }
+            RecorderError::Other(message) => Some(Msg::Error(format!("Tab audio couldn't be captured: {}", message))),
+            error => Some(Msg::Error(format!("Tab audio couldn't be captured: {}", error.message()))),
+        },
+    }
+}
+
+fn tab_audio_supported() -> bool {
+    seed::window().navigator().media_devices()
+        .map_or(false, |media_devices| js_sys::Reflect::has(&media_devices, &"getDisplayMedia".into()).unwrap_or(false))
+}
+
+// `web_sys` has no `getDisplayMedia` binding yet.
+async fn request_tab_audio() -> Result<MediaStream, JsValue> {
+    let media_devices = seed::window().navigator().media_devices()?;
+    let get_display_media = js_sys::Reflect::get(&media_devices, &"getDisplayMedia".into())?
+        .dyn_into::<js_sys::Function>()?;
+    // Chrome shares a tab's audio only along with its video, so the video is requested too
+    // and dropped right away.
+    let constraints = js_sys::Object::new();
+    js_sys::Reflect::set(&constraints, &"video".into(), &true.into())?;
+    js_sys::Reflect::set(&constraints, &"audio".into(), &true.into())?;
+    let stream_promise = get_display_media.call1(&media_devices, &constraints)?.unchecked_into::<js_sys::Promise>();
+    let stream = JsFuture::from(stream_promise).await?.unchecked_into::<MediaStream>();
+    for track in stream.get_video_tracks().iter() {
+        let track = track.unchecked_into::<MediaStreamTrack>();
+        track.stop();
+        stream.remove_track(&track);
+    }
+    if stream.get_audio_tracks().length() == 0 {
+        stop_tracks(&stream);
+        return Err(js_sys::Error::new("nothing with audio was shared - pick a tab and tick \"Share tab audio\"").into());
+    }
+    Ok(stream)
+}
 
-    Msg::AudioStream(stream)
+async fn request_stream(constraints: &MediaStreamConstraints) -> Result<MediaStream, JsValue> {
+    let media_devices = seed::window().navigator().media_devices()?;
+    // We need to request access to user's video or audio through constraints.
+    // Otherwise it fails (at least on Windows).
+    let stream_promise = media_devices.get_user_media_with_constraints(constraints)?;
+    Ok(JsFuture::from(stream_promise).await?.into())
 }
 
 // I change the quickstart Model to a struct because, eventually,
@@ -56,113 +282,5080 @@ async fn get_audio_stream() -> Msg {
 // https://developer.mozilla.org/en-US/docs/Web/API/MediaRecorder#Example
 #[derive(Default)]
 struct Model {
+    stream: Option<MediaStream>,
     recorder: Option<MediaRecorder>,
     on_data_callback: Option<Closure<dyn Fn(JsValue)>>,
-    last_chunk: Vec<u8>,
+    on_stop_callback: Option<Closure<dyn Fn(JsValue)>>,
+    on_error_callback: Option<Closure<dyn Fn(JsValue)>>,
+    // Why the take in progress was broken off by the browser (an error, or all its tracks ending);
+    // its chunks go to `recovered` instead of the session list.
+    interrupted: Option<String>,
+    recovered: Option<RecoveredTake>,
+    // The take in progress continues `recovered`, see `Msg::ResumeFromError`.
+    resuming: bool,
+    // Recorded time before the take in progress started - `recovered`'s when resuming.
+    take_offset_ms: f64,
+    last_chunk_size: u64,
+    // Elapsed ms of the take when each chunk (by index) was received, see `set_chunk_callback`.
+    chunk_offsets: Vec<f64>,
+    // Bytes received in the take in progress; they count against the quota once the take is stored.
+    take_bytes: f64,
+    // See `storage::remaining_bytes`; `None` until estimated or where there's no estimate.
+    storage_remaining: Option<f64>,
+    chunk_strategy: ChunkStrategy,
+    // Chunks not read yet, see `ChunkStrategy::Batched`.
+    raw_blobs: Vec<web_sys::Blob>,
+    // Chunks of the current take, indexed by the order in which the recorder emitted them.
+    // Reads finish asynchronously, so a slot stays empty until its `BlobRead` arrives.
+    chunks: Vec<Vec<u8>>,
+    pending_reads: usize,
+    // Chunks of the take that couldn't be read, see `Msg::ChunkReadFailed`.
+    failed_chunks: usize,
+    // Set from `stop()` until the take is finalized; a new take can't start in the meantime
+    // because it would mix its chunks into the previous one.
+    stopping: bool,
+    // Set by the recorder's `onstop`; the take is finalized once all pending reads are done as well.
+    stopped: bool,
+    // Bumped with every new take and on cancel. Recorder callbacks and chunk reads carry the epoch
+    // they were started in, so late messages of a cancelled take are recognized and ignored.
+    epoch: u32,
+    recordings: Vec<Recording>,
+    next_recording_id: usize,
+    persistence: Persistence,
+    // Finished before the stored recordings were loaded, and added once they are - they can't
+    // get an id before that.
+    early_takes: Vec<EarlyTake>,
+    player: ElRef<HtmlAudioElement>,
+    // Recording the player was last loaded with.
+    player_id: Option<usize>,
+    // `pcm::waveform` of `pcm`, drawn with a playhead that follows the player; a click seeks.
+    waveform: Vec<f32>,
+    waveform_canvas: ElRef<HtmlCanvasElement>,
+    // Voice activation keeps the stream open and watches its level
+    // to start and stop recordings on its own.
+    voice_activation: bool,
+    // Instant replay: the mic is listened to all along and the last `replay_s` are kept, see `Msg::SaveReplay`.
+    replay: bool,
+    replay_s: u32,
+    replay_tap: Option<ReplayTap>,
+    // The stream is being acquired for the replay rather than for a take.
+    replay_acquiring: bool,
+    // Keeps the stream open after a take so the next one starts without re-acquiring the mic.
+    // The browser shows the mic as in use until it's released or a take is cancelled.
+    keep_stream: bool,
+    // `keep_stream` for takes stopped with `RecorderHandle.stop_keep_stream`, until `release`.
+    keep_stream_for_next: bool,
+    // Otherwise the mic stays open this long after a take for a quick re-record; 0 releases it at once.
+    mic_grace_s: u32,
+    mic_release_handle: Option<CmdHandle>,
+    // Armed by `stop_recorder` in case `onstop` never fires, see `FINALIZE_TIMEOUT_MS`.
+    finalize_timeout: Option<CmdHandle>,
+    prewarm: Prewarm,
+    prewarm_release_handle: Option<CmdHandle>,
+    // Waits for the first gesture with `?prewarm=gesture`.
+    prewarm_gesture: Option<StreamHandle>,
+    // `stream` is a shared tab's audio rather than the mic; it's never kept for the next take.
+    tab_audio: bool,
+    voice_detector: VoiceActivityDetector,
+    // The take in progress was started by the voice activation, so silence may stop it.
+    voice_take: bool,
+    level_meter: Option<LevelMeter>,
+    level: f32,
+    // Peak of each input channel, see `LevelMeter::channel_levels`.
+    channel_levels: Vec<f32>,
+    // Keeps the meters running during a take, not only while voice activation listens.
+    show_meters: bool,
+    // Opt-in: `level` comes from a `WorkletMeter` that sees every frame, once it has loaded.
+    accurate_meter: bool,
+    worklet_meter: Option<WorkletMeter>,
+    rms: f32,
+    show_spectrum: bool,
+    // Analyser window; the spectrum has `fft_size / 2` bins.
+    fft_size: u32,
+    // The bins are averaged into this many bars.
+    spectrum_bands: usize,
+    spectrum: Vec<u8>,
+    // Takes started by hand go straight to a file when supported, see `FileSink`.
+    save_to_file: bool,
+    file_sink: Option<FileSink>,
+    file_status: Option<String>,
+    // Takes last as long as the talk button is held; the mic stays open between presses.
+    push_to_talk: bool,
+    talk_held: bool,
+    // Opt-in: keeps the tab "audible" while recording, see `KeepAlive`.
+    keep_alive_in_background: bool,
+    keep_alive: Option<KeepAlive>,
+    // No meters, spectrum or waveform, and the timer ticks once a second, see `tick_ms`.
+    low_power: bool,
+    capture_mode: CaptureMode,
+    // The mode the current take was started with; `capture_mode` may change meanwhile.
+    take_capture_mode: CaptureMode,
+    pcm_tap: Option<PcmTap>,
+    // Opt-in: a take identical to one already in the list isn't added again.
+    dedup: bool,
+    dedup_notice: Option<String>,
+    tick_handle: Option<StreamHandle>,
+    log_level: LogLevel,
+    container: Container,
+    codec: Codec,
+    // Composed from `container` and `codec`, used for the next take.
+    mime_type: String,
+    // MIME type of the take in progress - the settings may change while recording.
+    take_mime_type: String,
+    // Container + codec combinations the browser can record.
+    supported_formats: Vec<(Container, Codec)>,
+    // `mime::CANDIDATES` with whether this browser can record them.
+    mime_candidates: Vec<(&'static str, bool)>,
+    complete_event: String,
+    test_tone: Option<Tone>,
+    // Seconds counted down before a take starts, 0 starts right away.
+    countdown_s: u32,
+    // Seconds left of the countdown in progress.
+    countdown: Option<u32>,
+    countdown_handle: Option<CmdHandle>,
+    countdown_beeps: bool,
+    // The latest countdown beep; replacing it closes the previous one's context.
+    beep: Option<Tone>,
+    // `Date::now()` when the take in progress started. Timestamps in the metadata are relative
+    // to it and don't include the time spent paused, see `take_elapsed_ms`.
+    take_started_at: f64,
+    paused_at: Option<f64>,
+    paused_ms: f64,
+    // `(elapsed_ms, duration_ms)` of each pause of the take, closed when it's resumed.
+    pauses: Vec<(f64, f64)>,
+    elapsed_ms: f64,
+    markers: Vec<Marker>,
+    marker_label: String,
+    devices: Vec<Device>,
+    // Device group the mic (and the camera) are taken from.
+    group_id: Option<String>,
+    // Takes the system's default (communications) mic instead of the group's.
+    default_mic: Option<DefaultRole>,
+    // Mics recorded together, mixed into one track - e.g. one per person in an interview.
+    // With fewer than two the mic comes from `default_mic` or `group_id` as usual.
+    mix_inputs: Vec<MixInput>,
+    mixer: Option<Mixer>,
+    audio_constraints: AudioConstraints,
+    // Of the current stream's mic; `None` before the first stream or without `getCapabilities`.
+    capabilities: Option<Capabilities>,
+    record_video: bool,
+    // Camera to prefer on phones, see `stream_constraints`.
+    facing_mode: FacingMode,
+    // Takes are stopped automatically once they reach this length.
+    max_duration_ms: Option<f64>,
+    // Render only the record toggle, see `start_compact`.
+    compact: bool,
+    // Requested bitrate of the audio track.
+    audio_bits_per_second: u32,
+    // `(audio, video)` bits per second the browser actually went with for the latest take.
+    effective_bitrates: Option<(Option<f64>, Option<f64>)>,
+    timeslice_ms: i32,
+    hints: ValidationHints,
+    // The take delivers only one blob at stop instead of a chunk every `timeslice_ms`.
+    single_blob: bool,
+    // Armed at start; if no chunk has arrived when it fires, the engine ignored the timeslice.
+    timeslice_check: Option<CmdHandle>,
+    error: Option<String>,
+    // Adaptations to the browser made without asking, e.g. a single blob instead of timesliced chunks,
+    // shown so that the behavior changes aren't a mystery. See `note_fallback`.
+    compatibility_notes: Vec<String>,
+    // A `getUserMedia` is in flight; further requests wait for it instead of opening a second stream.
+    stream_pending: bool,
+    #[cfg(feature = "test-input")]
+    test_input: bool,
+    // The source of the current synthetic stream; dropping it stops the tone.
+    #[cfg(feature = "test-input")]
+    test_input_source: Option<test_input::TestInput>,
+    // The latest `getUserMedia` failure; cleared once a stream is acquired.
+    stream_error: Option<RecorderError>,
+    // The track can be muted for a moment (e.g. by a phone notification) while the recorder keeps
+    // running; we remember those intervals so the user knows where the gaps are.
+    on_mute_callback: Option<Closure<dyn Fn(JsValue)>>,
+    on_unmute_callback: Option<Closure<dyn Fn(JsValue)>>,
+    muted_since: Option<f64>,
+    muted_regions: Vec<(f64, f64)>,
+    // `(recording id, chapters)` of the latest `Msg::ExportChapters`.
+    chapters: Option<(usize, Vec<Chapter>)>,
+    // Decoded samples of the recording with the given id, see `Msg::ExtractPcm`.
+    pcm: Option<(usize, Pcm)>,
+    // Measured from `pcm` when it is extracted.
+    loudness: Option<Loudness>,
+    // Module of the `AudioWorkletProcessor` the mic goes through before it's recorded, see `ProcessingGraph`.
+    // Empty means the mic is recorded as is.
+    worklet_url: String,
+    processing: Option<ProcessingGraph>,
+    // The graph is being built; the take starts once it's ready.
+    processing_pending: bool,
+    // Loading `worklet_url` failed - takes record the unprocessed mic until the URL changes.
+    processing_failed: bool,
+    // Applied to the mic of every take, see `Msg::Calibrate`. 1.0 leaves it as is.
+    input_gain: f32,
+    gain_stage: Option<GainStage>,
+    calibration: Option<Calibration>,
+    // Play the take in progress while recording, see `LivePlayback`.
+    live_review: bool,
+    live_playback: Option<LivePlayback>,
+    // WebSocket server the takes are streamed to while they're recorded, e.g. `?stream=wss://example.com/live`.
+    // Empty streams nothing.
+    stream_url: String,
+    stream_overflow: Overflow,
+    // Of the latest take; kept after it to show how the streaming went.
+    live_stream: Option<LiveStream>,
+    reconnect_handle: Option<CmdHandle>,
+    // `(peak, average)` level measured by the latest calibration.
+    calibrated_levels: Option<(f32, f32)>,
+    // Ids of the recordings ticked for `Msg::DownloadSelected`.
+    selected: Vec<usize>,
+    // Tag filter of the list, see `filter_recordings`.
+    filter: String,
+    // Download the selection as one ZIP rather than a file each.
+    zip_selected: bool,
+    upload_method: UploadMethod,
+    upload_endpoint: String,
+    // Where `UploadMethod::Put` sends the next upload; provided by the host app's backend.
+    presigned_url: String,
+    upload: Option<MultipartUpload>,
+    // A PUT to `presigned_url` is in flight.
+    put_pending: bool,
+    // Outcome of the latest `Msg::CopyDataUrl`.
+    clipboard_status: Option<String>,
+    // The outcome of the latest settings import, see `Msg::ImportConfig`.
+    config_status: Option<String>,
+    upload_status: Option<String>,
+    export_settings: ExportSettings,
+    // See `file_name::stem`.
+    file_name_template: String,
+}
+
+// How the recorder's chunks are turned into bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ChunkStrategy {
+    // Every chunk is read as soon as it arrives.
+    Immediate,
+    // Chunks are only collected and read in one go when the recorder stops.
+    // With small timeslices this saves us from lots of concurrent reads.
+    Batched,
+}
+
+impl ChunkStrategy {
+    const ALL: [Self; 2] = [Self::Immediate, Self::Batched];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Immediate => "immediate",
+            Self::Batched => "batched",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|strategy| strategy.as_str() == value)
+    }
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        Self::Immediate
+    }
+}
+
+// Shown next to a numeric input whose last value was corrected or ignored, see `limits`.
+#[derive(Default)]
+struct ValidationHints {
+    bitrate: Option<String>,
+    timeslice: Option<String>,
+    max_duration: Option<String>,
+}
+
+// Levels sampled while calibrating; the meter starts before the first sample.
+struct Calibration {
+    started_at: Option<f64>,
+    peak: f32,
+    level_sum: f32,
+    sample_count: u32,
+}
+
+impl Calibration {
+    const fn new() -> Self {
+        Self { started_at: None, peak: 0., level_sum: 0., sample_count: 0 }
+    }
+
+    fn add(&mut self, level: f32, now: f64) {
+        self.started_at.get_or_insert(now);
+        self.peak = self.peak.max(level);
+        self.level_sum += level;
+        self.sample_count += 1;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn average(&self) -> f32 {
+        if self.sample_count == 0 {
+            0.
+        } else {
+            self.level_sum / self.sample_count as f32
+        }
+    }
+}
+
+// What a take captures; picked before each take.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CaptureMode {
+    // Only the recorder's encoded output.
+    Compressed,
+    // Only the raw samples, saved as WAV. The recorder still runs as the take's clock
+    // (pause, stop, timers), but its output is thrown away unread.
+    Lossless,
+    // Both, the WAV as an extra download next to the encoded recording.
+    Both,
+}
+
+impl CaptureMode {
+    const ALL: [Self; 3] = [Self::Compressed, Self::Lossless, Self::Both];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Compressed => "compressed",
+            Self::Lossless => "lossless",
+            Self::Both => "both",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.as_str() == value)
+    }
+
+    const fn keeps_compressed(self) -> bool {
+        !matches!(self, Self::Lossless)
+    }
+
+    const fn keeps_lossless(self) -> bool {
+        !matches!(self, Self::Compressed)
+    }
+}
+
+impl Default for CaptureMode {
+    fn default() -> Self {
+        Self::Compressed
+    }
+}
+
+// Processing applied to the decoded PCM before it's encoded into an exported file.
+#[derive(Default)]
+struct ExportSettings {
+    // Average all channels into one, regardless of how many channels were recorded.
+    mono: bool,
+    // Resample to this rate, e.g. 16 kHz for speech-to-text services. `None` keeps the decoded rate.
+    sample_rate: Option<u32>,
+    // Write the ReplayGain-like track gain into the WAV's `LIST/INFO` comment.
+    embed_replay_gain: bool,
+    // Ramps at the start and the end against clicks at the clip boundaries, see `pcm::fade`.
+    // 0 leaves the samples as they are.
+    fade_in_ms: f64,
+    fade_out_ms: f64,
+    fade_curve: FadeCurve,
+    // Subtract each channel's mean, see `pcm::remove_dc`.
+    remove_dc: bool,
+    // Put the pauses of the take back as silence, so the file runs in real time, see `pcm::insert_silence`.
+    // Only for the whole recording; chapters are cut at markers in recorded time.
+    inject_pauses: bool,
+    // Seconds dropped from the end of the recording (and so of its last chapter), see `pcm::trim_end`.
+    trim_end_s: f32,
+    // Of the WAV, chapter and FLAC exports; FLAC can't be float.
+    bit_depth: BitDepth,
+}
+
+// A data URL is a third larger than the recording and some tools choke on long pastes already.
+const CLIPBOARD_WARNING_BYTES: usize = 1024 * 1024;
+const CLIPBOARD_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+const EXPORT_SAMPLE_RATES: [u32; 5] = [8000, 16000, 22050, 44100, 48000];
+
+// A finished take in the session list.
+struct Recording {
+    id: usize,
+    bytes: Vec<u8>,
+    mime: String,
+    // The WAV of a `CaptureMode::Both` take. A lossless-only take has its WAV in `bytes`.
+    lossless: Option<Vec<u8>>,
+    // `hash::fnv1a` of `bytes`, used to skip duplicates.
+    hash: u64,
+    metadata: RecordingMetadata,
+    // Free-text note, editable in the list.
+    description: String,
+    // Lowercase, without duplicates, in the order they were added.
+    tags: Vec<String>,
+    // Created lazily the first time the recording is played so we don't hold a URL
+    // (and the browser-side copy of the blob) for takes nobody listens to.
+    object_url: Option<String>,
+}
+
+#[derive(Default)]
+struct RecordingMetadata {
+    // `(start_ms, end_ms)` intervals, relative to the start of the recording, where the track was muted.
+    muted_regions: Vec<(f64, f64)>,
+    markers: Vec<Marker>,
+    // `(elapsed_ms, duration_ms)` of the pauses, see `ExportSettings::inject_pauses`.
+    pauses: Vec<(f64, f64)>,
+    capture_mode: CaptureMode,
+    // See `loudness::replay_gain_db`; known once the recording has been decoded (extracted or exported).
+    replay_gain_db: Option<f64>,
+    // How many recorder chunks `bytes` was concatenated from; unknown for restored recordings.
+    chunk_count: Option<usize>,
+    // Of those, the ones that couldn't be read and are missing; unknown for restored recordings.
+    failed_chunks: Option<usize>,
+    // Recorded time, i.e. without the pauses; unknown for restored recordings.
+    duration_ms: Option<f64>,
+    // Measured in the background after the take, see `Msg::QualityMeasured` and `measures_quality`.
+    quality: Option<Quality>,
+}
+
+// Whether the session list is written to IndexedDB.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Persistence {
+    // Until `Msg::RecordingsLoaded`.
+    Loading,
+    On,
+    // The stored recordings couldn't be loaded, so their ids aren't known and writing could overwrite them.
+    Off,
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self::Loading
+    }
+}
+
+// The arguments of an `add_recording` made while `Persistence::Loading`.
+struct EarlyTake {
+    bytes: Vec<u8>,
+    mime: String,
+    lossless: Option<Vec<u8>>,
+    metadata: RecordingMetadata,
+}
+
+struct MixInput {
+    device_id: String,
+    gain: f32,
+    // The mic went away during the take (e.g. it was unplugged); the others are still recorded.
+    ended: bool,
+}
+
+// What's left of takes the browser broke off, kept to be resumed (or saved as they are).
+struct RecoveredTake {
+    // `(bytes, mime)` of each take, complete files of their own; they're joined as PCM.
+    segments: Vec<(Vec<u8>, String)>,
+    capture_mode: CaptureMode,
+    // Recorded time of all the segments; a resumed take's timer and markers continue from it.
+    elapsed_ms: f64,
+    markers: Vec<Marker>,
+    muted_regions: Vec<(f64, f64)>,
+    pauses: Vec<(f64, f64)>,
+    reason: String,
+}
+
+impl RecoveredTake {
+    fn append(&mut self, bytes: Vec<u8>, mime: String, metadata: RecordingMetadata, elapsed_ms: f64) {
+        if !bytes.is_empty() {
+            self.segments.push((bytes, mime));
+        }
+        self.markers.extend(metadata.markers);
+        self.muted_regions.extend(metadata.muted_regions);
+        self.pauses.extend(metadata.pauses);
+        self.elapsed_ms = elapsed_ms;
+    }
+}
+
+// A stream acquired ahead of the first take, see `Msg::Prewarm`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Prewarm {
+    Off,
+    Acquiring,
+    // Held with its audio disabled until a take starts, or released after `PREWARM_TIMEOUT_MS`.
+    Ready,
+}
+
+impl Default for Prewarm {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+// A bookmark dropped while recording.
+struct Marker {
+    elapsed_ms: f64,
+    label: Option<String>,
+}
+
+// A part of a recording between two markers, see `pcm::chapter_ranges`.
+struct Chapter {
+    name: String,
+    wav: Vec<u8>,
+}
+
+impl Recording {
+    // `stem` from `file_stem`.
+    fn file_name(&self, stem: &str) -> String {
+        format!("{}.{}", stem, mime::extension(&self.mime))
+    }
+
+    fn object_url(&mut self) -> &str {
+        let (bytes, mime) = (&self.bytes, &self.mime);
+        self.object_url.get_or_insert_with(|| {
+            let blob = Blob::new_with_options(bytes.as_slice(), Some(mime));
+            web_sys::Url::create_object_url_with_blob(blob.as_ref()).unwrap()
+        })
+    }
+
+    // The URL is recreated by `object_url` when next needed.
+    fn release_object_url(&mut self) {
+        if let Some(url) = self.object_url.take() {
+            if let Err(error) = web_sys::Url::revoke_object_url(&url) {
+                log_error!("Error revoking an object URL:", error);
+            }
+        }
+    }
+
+    fn lossless_bytes(&self) -> Option<&[u8]> {
+        match self.metadata.capture_mode {
+            CaptureMode::Compressed => None,
+            CaptureMode::Lossless => Some(&self.bytes),
+            CaptureMode::Both => self.lossless.as_deref(),
+        }
+    }
+
+    // The chunks of a timesliced WebM take form one valid stream, but only the first one has
+    // the header - which was written before the length was known, and there are no cues.
+    fn original_may_not_seek(&self) -> bool {
+        self.metadata.chunk_count.map_or(false, |count| count > 1) && mime::extension(&self.mime) == "webm"
+    }
+
+    fn to_stored(&self) -> StoredRecording {
+        StoredRecording {
+            id: self.id,
+            bytes: self.bytes.clone(),
+            mime: self.mime.clone(),
+            lossless: self.lossless.clone(),
+            capture_mode: self.metadata.capture_mode.as_str().to_owned(),
+            muted_regions: self.metadata.muted_regions.clone(),
+            pauses: self.metadata.pauses.clone(),
+            markers: self.metadata.markers.iter()
+                .map(|marker| (marker.elapsed_ms, marker.label.clone()))
+                .collect(),
+            description: self.description.clone(),
+            tags: self.tags.clone(),
+            quality: self.metadata.quality.clone(),
+        }
+    }
+}
+
+impl From<StoredRecording> for Recording {
+    fn from(stored: StoredRecording) -> Self {
+        Self {
+            id: stored.id,
+            hash: hash::fnv1a(&stored.bytes),
+            bytes: stored.bytes,
+            mime: stored.mime,
+            lossless: stored.lossless,
+            metadata: RecordingMetadata {
+                capture_mode: CaptureMode::parse(&stored.capture_mode).unwrap_or_default(),
+                muted_regions: stored.muted_regions,
+                markers: stored.markers.into_iter()
+                    .map(|(elapsed_ms, label)| Marker { elapsed_ms, label })
+                    .collect(),
+                pauses: stored.pauses,
+                replay_gain_db: None,
+                chunk_count: None,
+                failed_chunks: None,
+                duration_ms: None,
+                quality: stored.quality,
+            },
+            description: stored.description,
+            tags: stored.tags,
+            object_url: None,
+        }
+    }
+}
+
+// Every way a recording leaves the list (delete, clear, teardown) goes through `Drop`,
+// so the cached URL can't leak.
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.release_object_url();
+    }
 }
 
 enum Msg {
+    StartRecording,
     AudioStream(MediaStream),
-    BlobReceived(Blob),
-    BlobRead(Vec<u8>),
+    RecordTabAudio,
+    TabAudioStream(MediaStream),
+    BlobReceived(u32, Blob),
+    BlobRead(u32, usize, Vec<u8>),
+    // `(epoch, sequence)`: the chunk is left out of the take, which goes on without it.
+    ChunkReadFailed(u32, usize),
     StopRecording,
+    // Stops the take but keeps the stream for the next one, see `RecorderHandle.stop_keep_stream`.
+    StopKeepStream,
+    RecorderStopped(u32),
+    FinalizeTimedOut(u32),
+    RecorderErrored(u32, String),
+    ResumeFromError,
+    SaveRecovered,
+    DiscardRecovered,
+    RecoveredJoined(RecoveredTake, Result<Vec<u8>, JsValue>),
+    CancelRecording,
+    PlayRecording(usize),
+    // Seconds into the recording whose PCM is extracted.
+    SeekTo(f64),
+    DrawWaveform,
+    DeleteRecording(usize),
+    ClearRecordings,
+    // Frees the in-memory list only; the stored recordings stay for the next visit.
+    ReleaseRecordings,
+    RecordingsLoaded(Result<storage::Loaded, JsValue>),
+    SetRecordingNote(usize, String),
+    AddTag(usize, String),
+    RemoveTag(usize, String),
+    SetFilter(String),
+    Stored(Result<(), JsValue>),
+    StorageEstimated(Result<f64, JsValue>),
+    ExportAllZip,
+    ToggleSelect(usize),
+    ToggleZipSelected,
+    DownloadSelected,
+    SetCaptureMode(String),
+    DownloadOriginal(usize),
+    DownloadLossless(usize),
+    ToggleVoiceActivation,
+    ToggleReplay,
+    SetReplayLength(String),
+    SaveReplay,
+    SetVoiceStartThreshold(String),
+    SetVoiceStopThreshold(String),
+    SetSilenceTimeout(String),
+    Tick,
+    VoiceActivityChange(bool),
+    SetLogLevel(String),
+    SetContainer(String),
+    SetCodec(String),
+    SetMimeType(String),
+    ExportConfig,
+    SetFileNameTemplate(String),
+    // The JSON of an `AppConfig`.
+    ImportConfig(String),
+    LoadConfigFile(web_sys::File),
+    ConfigFileFailed(String),
+    PlayTestTone,
+    TestToneEnded,
+    SetCountdown(String),
+    ToggleCountdownBeeps,
+    CountdownTick,
+    BeepEnded,
+    TrackMuted,
+    TrackUnmuted,
+    ExtractPcm(usize),
+    PcmExtracted(usize, Result<Pcm, JsValue>),
+    QualityMeasured(usize, Result<Quality, JsValue>),
+    ToggleLiveReview,
+    Calibrate,
+    CalibrationDone(f32),
+    ResetInputGain,
+    SetWorkletUrl(String),
+    ProcessingReady(Result<ProcessingGraph, JsValue>),
+    SetUploadMethod(String),
+    SetUploadEndpoint(String),
+    SetStreamUrl(String),
+    SetStreamOverflow(String),
+    LiveStreamEvent(u32, SocketEvent),
+    ReconnectLiveStream,
+    SetPresignedUrl(String),
+    UploadRecording(usize),
+    PutUploaded(Result<(), String>),
+    PartUploaded(usize, fetch::Result<()>),
+    RetryUpload,
+    UploadCompleted(fetch::Result<()>),
+    ToggleExportMono,
+    SetExportSampleRate(String),
+    ToggleEmbedReplayGain,
+    ToggleRemoveDc,
+    ToggleInjectPauses,
+    SetTrimEndSecs(f32),
+    SetBitDepth(String),
+    CopyDataUrl(usize),
+    DataUrlCopied(usize, Result<(), JsValue>),
+    SetFadeIn(String),
+    SetFadeOut(String),
+    SetFadeCurve(String),
+    ExportWav(usize),
+    ExportChapters(usize),
+    ChaptersDecoded(usize, Result<Pcm, JsValue>),
+    // `(recording id, chapter index)`
+    DownloadChapter(usize, usize),
+    WavDecoded(usize, Result<Pcm, JsValue>),
+    ExportFlac(usize),
+    FlacDecoded(usize, Result<Pcm, JsValue>),
+    SetChunkStrategy(String),
+    PauseRecording,
+    ResumeRecording,
+    SetMarkerLabel(String),
+    AddMarker(Option<String>),
+    DevicesEnumerated(Result<Vec<Device>, JsValue>),
+    SetDeviceGroup(String),
+    SetDefaultMic(String),
+    ToggleRecordVideo,
+    ToggleMixMic(String),
+    SetCaptureSampleRate(String),
+    SetCaptureChannelCount(String),
+    SetFeature(Feature, String),
+    SetMixGain(usize, String),
+    MixInputEnded(usize),
+    FlipCamera,
+    ToggleKeepStream,
+    SetMicGrace(String),
+    MicGraceExpired,
+    // Acquires the stream without recording, so that the next start doesn't wait for `getUserMedia`.
+    // Has to come from a user gesture in browsers that require one for the mic.
+    Prewarm,
+    PrewarmExpired,
+    ReleaseStream,
+    SetMaxDuration(String),
+    SetAudioBitrate(String),
+    SetTimeslice(String),
+    StreamFailed(RecorderError),
+    RetryStream,
+    Error(String),
+    DismissError,
+    ToggleMeters,
+    ToggleAccurateMeter,
+    WorkletMeterReady(Result<WorkletMeter, JsValue>),
+    ToggleSpectrum,
+    SetFftSize(String),
+    SetSpectrumBands(String),
+    ToggleSaveToFile,
+    FileSinkOpened(Result<Option<FileSink>, JsValue>),
+    FileSaved(String, Result<(), JsValue>),
+    // Copies the bytes of the newest recording into the cell, see `RecorderHandle`.
+    ReadLastRecording(Rc<RefCell<Option<Vec<u8>>>>),
+    ToggleKeepAlive,
+    ToggleLowPower,
+    TogglePushToTalk,
+    TalkPressed,
+    // Also sent when the pointer leaves the held button.
+    TalkReleased,
+    ToggleDedup,
+    TimesliceCheck(u32),
 }
 
+// Some engines never fire `stop`; the take is finalized with the chunks so far after this long.
+const FINALIZE_TIMEOUT_MS: u32 = 5000;
+
+// How often we sample the input level.
+const TICK_MS: u32 = 50;
+// Enough for the timer; the voice activation and the calibration still work, just coarser.
+const LOW_POWER_TICK_MS: u32 = 1000;
+
+// Test tone to check the speakers: one second of A4.
+const TEST_TONE_FREQUENCY: f32 = 440.;
+const TEST_TONE_DURATION_MS: u32 = 1000;
+
+const COUNTDOWN_OPTIONS_S: [u32; 3] = [0, 3, 5];
+const MIC_GRACE_OPTIONS_S: [u32; 5] = [0, 5, 15, 30, 60];
+// A prewarmed stream nobody records from is released after a minute.
+const PREWARM_TIMEOUT_MS: u32 = 60_000;
+const REPLAY_OPTIONS_S: [u32; 4] = [10, 30, 60, 120];
+const DEFAULT_REPLAY_S: u32 = 30;
+// A short A5 on each second of the countdown and an A6 on "go".
+const BEEP_FREQUENCY: f32 = 880.;
+const GO_BEEP_FREQUENCY: f32 = 1760.;
+const BEEP_DURATION_MS: u32 = 150;
+
+// The calibration listens this long and aims the loudest moment at about -6 dBFS.
+const CALIBRATION_MS: f64 = 3000.;
+const CALIBRATION_TARGET_PEAK: f32 = 0.5;
+
 fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
     match msg {
-        Msg::AudioStream(stream) => { 
-            // `App` clone is cheap. `msg_mapper` is necessary to satisfy Rust types 
-            // (`Msg` in `Orders` is hidden in an associated type).
-            let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
-
-            // `Closure::wrap` can be written as `Closure::new` 
-            //- `new` doesn't need boilerplate like `Box::new` and `as Box<..` however it's not stable yet.
-            // `Closure` is a bridge between Rust closures and JS callbacks. That's why the input is `JsValue`.
-            let on_data_callback = Closure::wrap(Box::new(move |blob: JsValue| {
-                // We are sure that our `JsValue` represents `BlobEvent` so we can use `unchecked_into` to improve performance.
-                let web_sys_blob = blob.unchecked_into::<BlobEvent>().data().unwrap();
-                // Convert `web_sys::Blob` into `gloo_file`'s one because `gloo_file`'s `Blob` wrapper has some nice methods
-                // that eliminate boilerplate a lot.
-                let msg = Msg::BlobReceived(Blob::from(web_sys_blob));
-                // Pass the message to Seed. Then Seed invokes our `update` function with passed message.
-                app.update(msg_mapper(msg));
-            }) as Box<dyn Fn(JsValue)>);
-
-            let recorder = create_recorder(stream);
-            recorder.set_ondataavailable(Some(on_data_callback.as_ref().unchecked_ref()));
-            // We want to receive recorded data each second.
-            recorder.start_with_time_slice(1000).unwrap();
-
-            // Store `recorder` in `Model` so we can control it later. 
-            // Also there are often attached some drop procedures so it's also safer to store the instance.
-            model.recorder = Some(recorder);
-            // We need to store callback handle into `Model` or `.forget()` (aka leak) it.
-            // Otherwise it'll be dropped and JS throw error once the callback is invoked 
-            // because JS callback is stored in recorder and still alive.
-            model.on_data_callback = Some(on_data_callback);
-            log!("Listening");
+        Msg::StartRecording => {
+            model.voice_take = false;
+            if !duplicate_start(model, StartStage::Request) {
+                // A kept stream can end meanwhile, e.g. when the device is unplugged.
+                if model.stream.as_ref().map_or(false, |stream| !stream.active()) {
+                    model.stream = None;
+                    model.level_meter = None;
+                    model.worklet_meter = None;
+                }
+                // Only compressed-only takes are streamed to a file; a WAV is built at the end anyway.
+                let compressed_only = model.capture_mode == CaptureMode::Compressed;
+                if compressed_only && model.save_to_file && model.file_sink.is_none() && file_sink::is_supported() {
+                    // The picker needs the click's user activation, so it comes before anything else.
+                    let suggested_name = format!("{}.{}", file_stem(model, model.next_recording_id), model.container.as_str());
+                    orders.perform_cmd(async move { Msg::FileSinkOpened(file_sink::open(&suggested_name).await) });
+                } else if model.countdown_s > 0 && !model.push_to_talk {
+                    // After the picker, which wouldn't open anymore once the click's activation has expired.
+                    model.countdown = Some(model.countdown_s);
+                    play_beep(model, orders, BEEP_FREQUENCY);
+                    model.countdown_handle = Some(orders.perform_cmd_with_handle(cmds::timeout(1000, || Msg::CountdownTick)));
+                } else {
+                    start_or_acquire(model, orders);
+                }
+            }
+        }
+        Msg::AudioStream(stream) => {
+            model.stream_pending = false;
+            model.tab_audio = false;
+            accept_stream(model, orders, stream);
+            // Now that we have the permission, the devices have labels.
+            orders.perform_cmd(async { Msg::DevicesEnumerated(devices::enumerate().await) });
         },
-        Msg::BlobReceived(blob) => {
-            log!("Blob received");
-            orders.perform_cmd(async move {
-                Msg::BlobRead(read_as_bytes(&blob).await.unwrap())
-            });
+        Msg::RecordTabAudio => {
+            let idle = model.recorder.is_none() && !model.stopping && !model.processing_pending;
+            if idle && !listening(model) && !model.stream_pending {
+                // A kept mic stream would otherwise be recorded along.
+                if let Some(stream) = model.stream.take() {
+                    release_level_meter_if_unused(model);
+                    stop_tracks(&stream);
+                }
+                orders.perform_cmd(get_tab_audio_stream());
+            }
         }
-        Msg::BlobRead(bytes) => {
-            model.last_chunk = bytes;
+        Msg::TabAudioStream(stream) => {
+            model.tab_audio = true;
+            accept_stream(model, orders, stream);
+        }
+        Msg::BlobReceived(epoch, _)
+        | Msg::BlobRead(epoch, ..)
+        | Msg::ChunkReadFailed(epoch, _)
+        | Msg::RecorderStopped(epoch)
+        | Msg::FinalizeTimedOut(epoch)
+        | Msg::RecorderErrored(epoch, _) if epoch != model.epoch => {
+            log_debug!("Ignoring message of a cancelled take");
+        }
+        Msg::BlobReceived(_, blob) => {
+            log_debug!("Blob received");
+            #[allow(clippy::cast_precision_loss)]
+            progress::emit("chunk", &[
+                ("bytes", Value::Number(blob.size() as f64)),
+                ("elapsed", Value::Number(take_elapsed_ms(model))),
+            ]);
+            model.timeslice_check = None;
+            if !model.take_capture_mode.keeps_compressed() {
+                return;
+            }
+            // Chunks are numbered in the order they're received, both when read right away and batched.
+            model.chunk_offsets.push(take_elapsed_ms(model));
+            model.last_chunk_size = blob.size();
+            #[allow(clippy::cast_precision_loss)]
+            let chunk_bytes = blob.size() as f64;
+            model.take_bytes += chunk_bytes;
+            match model.chunk_strategy {
+                ChunkStrategy::Immediate => read_chunk(model, orders, blob),
+                ChunkStrategy::Batched => model.raw_blobs.push(blob.into()),
+            }
+        }
+        Msg::BlobRead(_, index, bytes) => {
+            model.pending_reads -= 1;
+            call_chunk_callback(&bytes, index, model.chunk_offsets.get(index).copied().unwrap_or_default());
+            deliver_chunk(model, index, bytes);
+            finalize_if_done(model, orders);
         },
+        Msg::ChunkReadFailed(_, index) => {
+            model.pending_reads -= 1;
+            model.failed_chunks += 1;
+            log_error!("Error reading chunk, it's missing from the take:", index);
+            #[allow(clippy::cast_precision_loss)]
+            progress::emit("chunk-failed", &[("sequence", Value::Number(index as f64))]);
+            // Empty, so that the chunks after it aren't held back waiting for it.
+            deliver_chunk(model, index, Vec::new());
+            finalize_if_done(model, orders);
+        }
         Msg::StopRecording => {
-            // Stop recorder and drop it. 
-            //
-            //In an ideal world you should:
-            // 1. Stop the recorder.
-            // 2. Handle the last chunk.
-            // 3. Wait for official recorder death (register `onclose` and maybe also `onerror` callbacks).
-            // 4. Drop the recorder and drop all callbacks (aka `Closure`s).
+            model.keep_stream_for_next = false;
+            stop_recorder(model, orders);
+        }
+        Msg::StopKeepStream => {
+            model.keep_stream_for_next = true;
+            stop_recorder(model, orders);
+        }
+        Msg::CancelRecording if model.countdown.is_some() => {
+            log_info!("Countdown cancelled");
+            model.countdown = None;
+            model.countdown_handle = None;
+        }
+        Msg::CancelRecording => cancel_recorder(model),
+        Msg::RecorderStopped(_) if model.recorder.is_none() && !model.stopping => {
+            // The take was finalized by `Msg::FinalizeTimedOut` already.
+            log_debug!("Ignoring a late stop event");
+        }
+        Msg::FinalizeTimedOut(epoch) => {
+            model.finalize_timeout = None;
+            if model.stopping && !model.stopped {
+                log_error!("Warning: the recorder's stop event didn't arrive, finalizing with the chunks so far");
+                orders.send_msg(Msg::RecorderStopped(epoch));
+            }
+        }
+        Msg::RecorderStopped(_) => {
+            model.finalize_timeout = None;
+            // Still there if we didn't stop it - the browser did, e.g. because all the tracks ended.
             if let Some(recorder) = model.recorder.take() {
-                recorder.stop().unwrap();
-                log!("Recording stopped")
+                recorder.set_onerror(None);
+                log_error!("The recorder stopped on its own");
+                model.interrupted.get_or_insert_with(|| "the recorder stopped on its own".to_owned());
+                model.elapsed_ms = take_elapsed_ms(model);
             }
+            // All chunks have arrived, so the batch can be read now.
+            if !model.raw_blobs.is_empty() {
+                let blobs = model.raw_blobs.drain(..).collect::<js_sys::Array>();
+                let mut options = web_sys::BlobPropertyBag::new();
+                options.type_(&model.take_mime_type);
+                let blob = web_sys::Blob::new_with_blob_sequence_and_options(&blobs, &options).unwrap();
+                read_chunk(model, orders, Blob::from(blob));
+            }
+            model.stopped = true;
+            finalize_if_done(model, orders);
         }
-    }
-}
-
-// This is where I am trying to implement the functionality of the
-// closure from lines 8-67 of https://developer.mozilla.org/en-US/docs/Web/API/MediaRecorder#Example
-fn create_recorder(stream: MediaStream) -> MediaRecorder {
-    let mut options = MediaRecorderOptions::new();
-    options.audio_bits_per_second(64_000);
-    options.mime_type("audio/ogg;codecs=opus");
-
-    // And here is where I am stuck.
-    // `recorder` is a web_sys::MediaRecorder struct; it has a method `set_ondataavailable`
-    // which is where I should be doing something with audio bytes as they become
-    // available from the microphone.  What I _want_ to do is to write them to
-    // my Model.chunks field (and trigger a Msg that tells the seed runtime that there
-    // are bytes to process); but `set_ondataavailable` is a js_sys::Function, and I
-    // don't know how to do the interop between JS functions and memory allocation
-    // and `js_sys` or `wasm_bindgen` structs.
-    // I've looked through various chapters of the wasm_bindgen book
-    // https://rustwasm.github.io/wasm-bindgen/introduction.html
-    // and tried to figure out how to make the example from the wasm_bindgen Closure docs
-    // https://docs.rs/wasm-bindgen/0.2.63/wasm_bindgen/closure/struct.Closure.html
-    // work, but haven't been able to wrap my head around them -- at least not for this
-    // particular use case.
-    MediaRecorder::new_with_media_stream_and_media_recorder_options(
-        &stream,
-        &options,
-    ).unwrap()
-}
-
-fn view(model: &Model) -> Node<Msg> {
-    div![
-        "Last chunk length: ",
-        model.last_chunk.len(),
-        button!("Stop", ev(Ev::Click, |_| Msg::StopRecording))
-    ]
-}
-
-#[wasm_bindgen(start)]
-pub fn start() {
-    App::start("app", init, update, view);
+        Msg::RecorderErrored(_, error) => {
+            // The recorder stops after an error, with the chunks so far still to come.
+            log_error!("Recorder error:", error);
+            model.interrupted = Some(error);
+        }
+        Msg::ResumeFromError => {
+            if model.recovered.is_some() && model.recorder.is_none() && !model.stopping {
+                model.resuming = true;
+                orders.send_msg(Msg::StartRecording);
+            }
+        }
+        Msg::SaveRecovered => {
+            if let Some(recovered) = model.recovered.take() {
+                model.resuming = false;
+                save_recovered(model, orders, recovered);
+            }
+        }
+        Msg::DiscardRecovered => {
+            model.recovered = None;
+            model.resuming = false;
+        }
+        Msg::RecoveredJoined(recovered, Ok(bytes)) => {
+            let metadata = RecordingMetadata {
+                capture_mode: CaptureMode::Lossless,
+                muted_regions: recovered.muted_regions,
+                markers: recovered.markers,
+                pauses: recovered.pauses,
+                replay_gain_db: None,
+                chunk_count: None,
+                failed_chunks: None,
+                duration_ms: Some(recovered.elapsed_ms),
+                quality: None,
+            };
+            add_recording(model, orders, bytes, "audio/wav".to_owned(), None, metadata);
+        }
+        Msg::RecoveredJoined(recovered, Err(error)) => {
+            log_error!("Error joining the recovered takes:", error);
+            model.recovered = Some(recovered);
+            orders.send_msg(Msg::Error("The recovered takes couldn't be joined".to_owned()));
+        }
+        Msg::PlayRecording(id) => {
+            if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+                let player = model.player.get().expect("get player element");
+                player.set_src(recording.object_url());
+                model.player_id = Some(id);
+                // `play` returns a promise; a rejected one (e.g. autoplay policy) just leaves the player paused.
+                let _ = player.play();
+                // The playhead follows on `Msg::Tick`.
+                ensure_ticking(model, orders);
+            }
+        }
+        Msg::SeekTo(seconds) => {
+            if let Some((pcm_id, pcm)) = model.pcm.as_ref() {
+                if !seconds.is_finite() {
+                    return;
+                }
+                // A click past the end of the decoded samples seeks to the end.
+                let seconds = seconds.max(0.).min(pcm.duration_s());
+                let player = model.player.get().expect("get player element");
+                if model.player_id != Some(*pcm_id) {
+                    match model.recordings.iter_mut().find(|r| r.id == *pcm_id) {
+                        Some(recording) => player.set_src(recording.object_url()),
+                        None => return,
+                    }
+                    model.player_id = Some(*pcm_id);
+                }
+                player.set_current_time(seconds);
+                draw_waveform(model);
+            }
+        }
+        Msg::DrawWaveform => draw_waveform(model),
+        Msg::DeleteRecording(id) => {
+            release_removed(model, Some(id));
+            model.recordings.retain(|r| r.id != id);
+            model.selected.retain(|selected| *selected != id);
+            if model.persistence == Persistence::On {
+                orders.perform_cmd(async move { Msg::Stored(storage::delete(id).await) });
+            }
+        }
+        Msg::ClearRecordings => {
+            release_removed(model, None);
+            model.recordings.clear();
+            model.selected.clear();
+            if model.persistence == Persistence::On {
+                orders.perform_cmd(async { Msg::Stored(storage::clear().await) });
+            }
+        }
+        Msg::SetCaptureMode(value) => {
+            if let Some(mode) = CaptureMode::parse(&value) {
+                model.capture_mode = mode;
+            }
+        }
+        Msg::DownloadOriginal(id) => {
+            // The bytes as the recorder produced them - no decoding, no re-encoding.
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                download(&recording.bytes, &recording.mime, &recording.file_name(&file_stem(model, id)));
+            }
+        }
+        Msg::DownloadLossless(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                if let Some(bytes) = recording.lossless_bytes() {
+                    download(bytes, "audio/wav", &format!("{}.wav", file_stem(model, id)));
+                }
+            }
+        }
+        Msg::ExportAllZip => {
+            let files = zip_files(model, model.recordings.iter());
+            if !files.is_empty() {
+                download(&zip::store(&files), "application/zip", "recordings.zip");
+            }
+        }
+        Msg::ToggleSelect(id) => {
+            match model.selected.iter().position(|selected| *selected == id) {
+                Some(index) => {
+                    model.selected.remove(index);
+                }
+                None => model.selected.push(id),
+            }
+        }
+        Msg::ToggleZipSelected => {
+            model.zip_selected = !model.zip_selected;
+        }
+        Msg::DownloadSelected => {
+            let selected = model.recordings.iter().filter(|recording| model.selected.contains(&recording.id));
+            if model.zip_selected {
+                let files = zip_files(model, selected);
+                if !files.is_empty() {
+                    download(&zip::store(&files), "application/zip", "selected-recordings.zip");
+                }
+            } else {
+                // The browser may ask once whether the page can download several files.
+                for recording in selected {
+                    download(&recording.bytes, &recording.mime, &recording.file_name(&file_stem(model, recording.id)));
+                }
+            }
+        }
+        // The page may be restored from the back-forward cache, so the takes stay - only
+        // their URLs go.
+        Msg::ReleaseRecordings => {
+            for recording in &mut model.recordings {
+                recording.release_object_url();
+            }
+        }
+        Msg::RecordingsLoaded(Ok(loaded)) => {
+            let mut recordings = loaded.recordings.into_iter().map(Recording::from).collect::<Vec<_>>();
+            recordings.sort_by_key(|recording| recording.id);
+            model.recordings = recordings;
+            model.next_recording_id = loaded.next_id;
+            model.persistence = Persistence::On;
+            add_early_takes(model, orders);
+        }
+        Msg::RecordingsLoaded(Err(error)) => {
+            log_error!("Error loading stored recordings:", error);
+            note_fallback(
+                &mut model.compatibility_notes,
+                "The stored recordings couldn't be loaded, this session's takes aren't saved".to_owned(),
+            );
+            model.persistence = Persistence::Off;
+            add_early_takes(model, orders);
+        }
+        Msg::SetRecordingNote(id, note) => {
+            if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+                recording.description = note;
+                store_note(recording, model.persistence, orders);
+            }
+        }
+        Msg::AddTag(id, tag) => {
+            let tag = tag.trim().to_lowercase();
+            if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+                if tag.is_empty() || recording.tags.contains(&tag) {
+                    return;
+                }
+                recording.tags.push(tag);
+                store_note(recording, model.persistence, orders);
+            }
+        }
+        Msg::RemoveTag(id, tag) => {
+            if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+                recording.tags.retain(|t| *t != tag);
+                store_note(recording, model.persistence, orders);
+            }
+        }
+        Msg::SetFilter(filter) => model.filter = filter,
+        Msg::Stored(Ok(())) => request_storage_estimate(orders),
+        Msg::Stored(Err(error)) => {
+            log_error!("Error storing recordings:", error);
+        }
+        Msg::StorageEstimated(Ok(remaining_bytes)) => {
+            model.storage_remaining = Some(remaining_bytes);
+        }
+        Msg::StorageEstimated(Err(error)) => {
+            log_debug!("No storage estimate:", error);
+        }
+        Msg::ToggleReplay => {
+            model.replay = !model.replay;
+            model.replay_tap = None;
+            if !model.replay {
+                model.replay_acquiring = false;
+                // Nothing else needs the mic while idle.
+                if model.recorder.is_none() && !model.stopping && !model.keep_stream && !listening(model) {
+                    if let Some(stream) = model.stream.take() {
+                        release_level_meter_if_unused(model);
+                        stop_tracks(&stream);
+                    }
+                }
+            } else if model.stream.is_some() {
+                start_replay_tap(model);
+            } else {
+                model.replay_acquiring = true;
+                request_audio_stream(model, orders);
+            }
+        }
+        Msg::SetReplayLength(value) => {
+            if let Ok(seconds) = value.parse() {
+                model.replay_s = seconds;
+                // The buffer is sized for the length, so the replay starts over.
+                if model.replay_tap.take().is_some() {
+                    start_replay_tap(model);
+                }
+            }
+        }
+        Msg::SaveReplay => {
+            if let Some(tap) = model.replay_tap.as_ref() {
+                let pcm = tap.latest(model.replay_s);
+                if pcm.frames() > 0 {
+                    let metadata = RecordingMetadata {
+                        capture_mode: CaptureMode::Lossless,
+                        duration_ms: Some(pcm.duration_s() * 1000.),
+                        ..RecordingMetadata::default()
+                    };
+                    add_recording(model, orders, wav::encode(&pcm), "audio/wav".to_owned(), None, metadata);
+                }
+            }
+        }
+        Msg::ToggleVoiceActivation => {
+            model.voice_activation = !model.voice_activation;
+            model.voice_detector.reset();
+            model.voice_take = false;
+            if !model.voice_activation {
+                release_level_meter_if_unused(model);
+                // Nothing else needs the mic while idle.
+                if model.recorder.is_none() && !model.keep_stream && !listening(model) {
+                    if let Some(stream) = model.stream.take() {
+                        stop_tracks(&stream);
+                    }
+                }
+            } else if model.stream.is_some() {
+                start_level_meter(model, orders);
+            } else {
+                request_audio_stream(model, orders);
+            }
+        }
+        Msg::SetVoiceStartThreshold(value) => {
+            if let Ok(threshold) = value.parse() {
+                model.voice_detector.start_threshold = threshold;
+            }
+        }
+        Msg::SetVoiceStopThreshold(value) => {
+            if let Ok(threshold) = value.parse() {
+                model.voice_detector.stop_threshold = threshold;
+            }
+        }
+        Msg::SetSilenceTimeout(value) => {
+            if let Ok(timeout) = value.parse() {
+                model.voice_detector.silence_timeout_ms = timeout;
+            }
+        }
+        Msg::Tick => {
+            // The playhead is only drawn on the waveform.
+            let playing = !model.low_power && model.player.get().map_or(false, |player| !player.paused());
+            if model.level_meter.is_none() && model.recorder.is_none() && !playing {
+                model.tick_handle = None;
+                return;
+            }
+            if playing {
+                draw_waveform(model);
+            }
+            if model.recorder.is_some() {
+                model.elapsed_ms = take_elapsed_ms(model);
+                if model.max_duration_ms.map_or(false, |max| model.elapsed_ms >= max) {
+                    log_info!("Max duration reached");
+                    stop_recorder(model, orders);
+                }
+            }
+            if let Some(level_meter) = model.level_meter.as_mut() {
+                model.level = level_meter.level();
+                if let Some((peak, rms)) = model.worklet_meter.as_ref().and_then(WorkletMeter::take_levels) {
+                    model.level = peak;
+                    model.rms = rms;
+                }
+                model.channel_levels = level_meter.channel_levels();
+                if model.show_spectrum && !model.low_power {
+                    model.spectrum = audio::frequency_bands(level_meter.frequency_data(), model.spectrum_bands);
+                }
+                let detecting = model.voice_activation && model.calibration.is_none();
+                if let Some(active) = voice_activity_change(&mut model.voice_detector, detecting, model.level, js_sys::Date::now()) {
+                    orders.send_msg(Msg::VoiceActivityChange(active));
+                }
+                if let Some(calibration) = model.calibration.as_mut() {
+                    let now = js_sys::Date::now();
+                    calibration.add(model.level, now);
+                    if calibration.started_at.map_or(false, |started_at| now - started_at >= CALIBRATION_MS) {
+                        model.calibrated_levels = Some((calibration.peak, calibration.average()));
+                        let gain = audio::suggested_gain(calibration.peak, CALIBRATION_TARGET_PEAK);
+                        model.calibration = None;
+                        orders.send_msg(Msg::CalibrationDone(gain));
+                    }
+                }
+            }
+        }
+        Msg::VoiceActivityChange(true) => {
+            log_info!("Voice detected");
+            if model.recorder.is_none() && !model.stopping && !model.processing_pending {
+                start_take(model, orders);
+                model.voice_take = true;
+            }
+        }
+        Msg::VoiceActivityChange(false) => {
+            log_info!("Silence detected");
+            // A take started by hand is stopped by hand.
+            if model.voice_take {
+                model.voice_take = false;
+                stop_recorder(model, orders);
+            }
+        }
+        Msg::SetLogLevel(value) => {
+            if let Some(level) = LogLevel::parse(&value) {
+                model.log_level = level;
+                logging::set_level(level);
+            }
+        }
+        Msg::SetContainer(value) => {
+            if let Some(container) = Container::parse(&value) {
+                model.container = container;
+                // Keep the combination valid - switch to a codec the new container supports.
+                if !model.supported_formats.contains(&(container, model.codec)) {
+                    if let Some((_, codec)) = model.supported_formats.iter().find(|(c, _)| *c == container) {
+                        model.codec = *codec;
+                    }
+                }
+                model.mime_type = mime::mime_type(model.container, model.codec);
+            }
+        }
+        Msg::SetMimeType(mime_type) => {
+            // Keep the container/codec selects in sync when the type is one they can express.
+            if let Some((container, codec)) = mime::parse_mime_type(&mime_type) {
+                model.container = container;
+                model.codec = codec;
+            }
+            model.mime_type = mime_type;
+        }
+        Msg::ExportConfig => match app_config(model).to_json() {
+            Ok(json) => download(json.as_bytes(), "application/json", "mediarecorder-settings.json"),
+            Err(error) => {
+                log_error!("Error exporting settings:", error);
+                model.config_status = Some(error);
+            }
+        },
+        Msg::SetFileNameTemplate(template) => model.file_name_template = template,
+        Msg::ImportConfig(json) => import_config(model, &json),
+        Msg::LoadConfigFile(file) => {
+            orders.perform_cmd(async move {
+                match read_as_text(&gloo_file::File::from(file)).await {
+                    Ok(json) => Msg::ImportConfig(json),
+                    Err(error) => Msg::ConfigFileFailed(error.to_string()),
+                }
+            });
+        }
+        Msg::ConfigFileFailed(error) => {
+            log_error!("Error reading settings file:", error);
+            model.config_status = Some("The file couldn't be read".to_owned());
+        }
+        Msg::SetCodec(value) => {
+            if let Some(codec) = Codec::parse(&value) {
+                model.codec = codec;
+                model.mime_type = mime::mime_type(model.container, model.codec);
+            }
+        }
+        Msg::PlayTestTone => {
+            if model.test_tone.is_none() {
+                match Tone::play(TEST_TONE_FREQUENCY, f64::from(TEST_TONE_DURATION_MS) / 1000.) {
+                    Ok(tone) => {
+                        model.test_tone = Some(tone);
+                        orders.perform_cmd(cmds::timeout(TEST_TONE_DURATION_MS, || Msg::TestToneEnded));
+                    }
+                    Err(error) => log_error!("Error playing test tone:", error),
+                }
+            }
+        }
+        Msg::TestToneEnded => {
+            model.test_tone = None;
+        }
+        Msg::SetCountdown(value) => {
+            model.countdown_s = value.parse().unwrap_or_default();
+        }
+        Msg::ToggleCountdownBeeps => {
+            model.countdown_beeps = !model.countdown_beeps;
+        }
+        Msg::CountdownTick => {
+            match model.countdown {
+                Some(remaining) if remaining > 1 => {
+                    model.countdown = Some(remaining - 1);
+                    play_beep(model, orders, BEEP_FREQUENCY);
+                    model.countdown_handle = Some(orders.perform_cmd_with_handle(cmds::timeout(1000, || Msg::CountdownTick)));
+                }
+                Some(_) => {
+                    model.countdown = None;
+                    model.countdown_handle = None;
+                    play_beep(model, orders, GO_BEEP_FREQUENCY);
+                    start_or_acquire(model, orders);
+                }
+                // Cancelled.
+                None => (),
+            }
+        }
+        Msg::BeepEnded => {
+            model.beep = None;
+        }
+        Msg::TrackMuted => {
+            if model.recorder.is_some() && model.muted_since.is_none() {
+                log_info!("Track muted");
+                model.muted_since = Some(take_elapsed_ms(model));
+            }
+        }
+        Msg::TrackUnmuted => {
+            if let Some(start_ms) = model.muted_since.take() {
+                log_info!("Track unmuted");
+                model.muted_regions.push((start_ms, take_elapsed_ms(model)));
+            }
+        }
+        Msg::ExtractPcm(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                let bytes = recording.bytes.clone();
+                orders.perform_cmd(async move {
+                    Msg::PcmExtracted(id, audio::decode(&bytes).await)
+                });
+            }
+        }
+        Msg::PcmExtracted(id, Ok(pcm)) => {
+            log_info!("PCM extracted");
+            call_pcm_callback(&pcm);
+            let loudness = loudness::measure(&pcm.channels, pcm.sample_rate);
+            set_replay_gain(model, id, &loudness);
+            model.waveform = pcm::waveform(&pcm.channels, WAVEFORM_WIDTH as usize);
+            orders.after_next_render(|_| Msg::DrawWaveform);
+            model.loudness = Some(loudness);
+            model.pcm = Some((id, pcm));
+        }
+        Msg::PcmExtracted(_, Err(error)) => {
+            log_error!("Error decoding recording:", error);
+        }
+        Msg::QualityMeasured(id, Ok(quality)) => {
+            if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+                recording.metadata.quality = Some(quality);
+                if model.persistence == Persistence::On {
+                    let stored = recording.to_stored();
+                    orders.perform_cmd(async { Msg::Stored(storage::put(stored).await) });
+                }
+            }
+        }
+        Msg::QualityMeasured(_, Err(error)) => {
+            log_error!("Error decoding recording for the quality summary:", error);
+        }
+        Msg::ToggleLiveReview => {
+            // Applies from the next take; the chunks before now are gone already.
+            model.live_review = !model.live_review;
+        }
+        Msg::Calibrate => {
+            if model.recorder.is_some() || model.stopping || model.calibration.is_some() {
+                return;
+            }
+            model.calibration = Some(Calibration::new());
+            if model.stream.is_none() {
+                request_audio_stream(model, orders);
+            } else if model.level_meter.is_none() {
+                start_level_meter(model, orders);
+            }
+        }
+        Msg::CalibrationDone(gain) => {
+            log_info!("Calibrated input gain:", gain);
+            model.input_gain = gain;
+            release_level_meter_if_unused(model);
+            if !model.keep_stream {
+                release_kept_stream(model);
+            }
+        }
+        Msg::ResetInputGain => {
+            model.input_gain = 1.;
+            model.calibrated_levels = None;
+        }
+        Msg::SetWorkletUrl(url) => {
+            let url = url.trim().to_owned();
+            if url != model.worklet_url {
+                model.worklet_url = url;
+                model.processing_failed = false;
+                // The take in progress keeps its graph; the next one builds a new graph.
+                if model.recorder.is_none() {
+                    model.processing = None;
+                }
+            }
+        }
+        Msg::ProcessingReady(result) => {
+            model.processing_pending = false;
+            match result {
+                Ok(graph) => model.processing = Some(graph),
+                Err(error) => {
+                    log_error!("Error loading the worklet, recording the unprocessed mic:", error);
+                    model.processing_failed = true;
+                    note_fallback(
+                        &mut model.compatibility_notes,
+                        format!("The processing worklet couldn't be loaded, the mic is recorded unprocessed ({:?})", error),
+                    );
+                }
+            }
+            if model.recorder.is_none() && !model.stopping && model.stream.is_some() {
+                start_recorder(model, orders);
+            }
+        }
+        Msg::SetUploadMethod(method) => {
+            if let Some(method) = UploadMethod::parse(&method) {
+                model.upload_method = method;
+            }
+        }
+        Msg::SetUploadEndpoint(endpoint) => {
+            model.upload_endpoint = endpoint;
+        }
+        Msg::SetStreamUrl(url) => {
+            model.stream_url = url;
+        }
+        Msg::SetStreamOverflow(value) => {
+            if let Some(overflow) = Overflow::parse(&value) {
+                model.stream_overflow = overflow;
+            }
+        }
+        Msg::LiveStreamEvent(generation, event) => {
+            if let Some(live_stream) = model.live_stream.as_mut().filter(|live| live.generation == generation) {
+                match event {
+                    SocketEvent::Opened => {
+                        log_info!("Live stream connected");
+                        live_stream.opened();
+                    }
+                    SocketEvent::Closed => schedule_reconnect(model, orders),
+                }
+            }
+        }
+        Msg::ReconnectLiveStream => {
+            model.reconnect_handle = None;
+            connect_live_stream(model, orders);
+        }
+        Msg::SetPresignedUrl(url) => {
+            model.presigned_url = url;
+        }
+        Msg::UploadRecording(id) => {
+            if model.upload.is_some() || model.put_pending {
+                return;
+            }
+            if model.upload_method == UploadMethod::Put {
+                put_recording(model, orders, id);
+                return;
+            }
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                model.upload = Some(MultipartUpload::new(id, recording.bytes.len(), UPLOAD_PART_SIZE, UPLOAD_MAX_ATTEMPTS));
+                model.upload_status = None;
+                send_next_part(model, orders);
+            }
+        }
+        Msg::PartUploaded(index, result) => {
+            if let Some(upload) = model.upload.as_mut() {
+                match result {
+                    Ok(()) => upload.part_succeeded(index),
+                    Err(error) => {
+                        log_error!("Error uploading part:", index, error);
+                        upload.part_failed(index);
+                    }
+                }
+                send_next_part(model, orders);
+            }
+        }
+        Msg::RetryUpload => {
+            if let Some(upload) = model.upload.as_mut() {
+                upload.retry_failed();
+                send_next_part(model, orders);
+            }
+        }
+        Msg::PutUploaded(result) => {
+            model.put_pending = false;
+            match result {
+                Ok(()) => model.upload_status = Some("Upload complete".to_owned()),
+                Err(error) => {
+                    model.upload_status = Some("Upload failed".to_owned());
+                    orders.send_msg(Msg::Error(format!("Upload failed: {}", error)));
+                }
+            }
+        }
+        Msg::UploadCompleted(result) => {
+            model.upload = None;
+            model.upload_status = Some(match result {
+                Ok(()) => "Upload complete".to_owned(),
+                Err(error) => {
+                    log_error!("Error completing upload:", error);
+                    "Upload failed".to_owned()
+                }
+            });
+        }
+        Msg::ToggleExportMono => {
+            model.export_settings.mono = !model.export_settings.mono;
+        }
+        Msg::SetExportSampleRate(value) => {
+            model.export_settings.sample_rate = value.parse().ok();
+        }
+        Msg::SetFadeIn(value) => {
+            if let Ok(ms) = value.parse::<f64>() {
+                model.export_settings.fade_in_ms = ms.max(0.);
+            }
+        }
+        Msg::SetFadeOut(value) => {
+            if let Ok(ms) = value.parse::<f64>() {
+                model.export_settings.fade_out_ms = ms.max(0.);
+            }
+        }
+        Msg::SetFadeCurve(value) => {
+            if let Some(curve) = FadeCurve::parse(&value) {
+                model.export_settings.fade_curve = curve;
+            }
+        }
+        Msg::ToggleEmbedReplayGain => {
+            model.export_settings.embed_replay_gain = !model.export_settings.embed_replay_gain;
+        }
+        Msg::ToggleRemoveDc => {
+            model.export_settings.remove_dc = !model.export_settings.remove_dc;
+        }
+        Msg::ToggleInjectPauses => {
+            model.export_settings.inject_pauses = !model.export_settings.inject_pauses;
+        }
+        Msg::SetTrimEndSecs(seconds) => {
+            model.export_settings.trim_end_s = seconds.max(0.);
+        }
+        Msg::SetBitDepth(value) => {
+            if let Some(bit_depth) = BitDepth::parse(&value) {
+                model.export_settings.bit_depth = bit_depth;
+            }
+        }
+        Msg::CopyDataUrl(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                if recording.bytes.len() <= CLIPBOARD_MAX_BYTES {
+                    let url = base64::data_url(&recording.mime, &recording.bytes);
+                    model.clipboard_status = None;
+                    orders.perform_cmd(async move { Msg::DataUrlCopied(id, write_clipboard_text(&url).await) });
+                }
+            }
+        }
+        Msg::DataUrlCopied(id, Ok(())) => {
+            model.clipboard_status = Some(format!("Recording {} copied as a data URL", id + 1));
+        }
+        Msg::DataUrlCopied(id, Err(error)) => {
+            log_error!("Error copying to the clipboard:", error);
+            model.clipboard_status = Some(format!("Couldn't copy recording {} to the clipboard", id + 1));
+        }
+        Msg::ExportWav(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                let bytes = recording.bytes.clone();
+                orders.perform_cmd(async move {
+                    Msg::WavDecoded(id, audio::decode(&bytes).await)
+                });
+            }
+        }
+        Msg::ExportFlac(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                let bytes = recording.bytes.clone();
+                orders.perform_cmd(async move {
+                    Msg::FlacDecoded(id, audio::decode(&bytes).await)
+                });
+            }
+        }
+        Msg::ExportChapters(id) => {
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                let bytes = recording.bytes.clone();
+                orders.perform_cmd(async move {
+                    Msg::ChaptersDecoded(id, audio::decode(&bytes).await)
+                });
+            }
+        }
+        Msg::ChaptersDecoded(id, Ok(mut pcm)) => {
+            pcm::trim_end(&mut pcm, model.export_settings.trim_end_s);
+            if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+                let mut markers = recording.metadata.markers.iter().collect::<Vec<_>>();
+                markers.sort_by(|a, b| a.elapsed_ms.partial_cmp(&b.elapsed_ms).unwrap_or(std::cmp::Ordering::Equal));
+                let markers_ms = markers.iter().map(|marker| marker.elapsed_ms).collect::<Vec<_>>();
+                let ranges = pcm::chapter_ranges(&markers_ms, pcm.duration_s(), pcm.sample_rate);
+                // A chapter is named after the marker it starts at.
+                let names = std::iter::once("Start".to_owned()).chain(markers.iter().enumerate().map(|(index, marker)| {
+                    marker.label.clone().unwrap_or_else(|| format!("Marker {}", index + 1))
+                }));
+                let chapters = names.zip(ranges)
+                    .filter(|(_, range)| !range.is_empty())
+                    .map(|(name, range)| {
+                        let chapter = process_for_export(pcm::slice(&pcm, range), &model.export_settings);
+                        Chapter { name, wav: wav::encode_with(&chapter, model.export_settings.bit_depth, None) }
+                    })
+                    .collect();
+                model.chapters = Some((id, chapters));
+            }
+        }
+        Msg::ChaptersDecoded(_, Err(error)) => {
+            log_error!("Error decoding recording:", error);
+        }
+        Msg::DownloadChapter(id, index) => {
+            if let Some((_, chapters)) = model.chapters.as_ref().filter(|(chapters_id, _)| *chapters_id == id) {
+                if let Some(chapter) = chapters.get(index) {
+                    download(&chapter.wav, "audio/wav", &format!("{}-chapter-{}.wav", file_stem(model, id), index + 1));
+                }
+            }
+        }
+        Msg::WavDecoded(id, Ok(pcm)) => {
+            let (pcm, comment) = prepare_export(model, id, pcm);
+            let wav = wav::encode_with(&pcm, model.export_settings.bit_depth, comment.as_deref());
+            download(&wav, "audio/wav", &format!("{}.wav", file_stem(model, id)));
+        }
+        Msg::FlacDecoded(id, Ok(pcm)) => {
+            let (pcm, comment) = prepare_export(model, id, pcm);
+            match flac::encode(&pcm, model.export_settings.bit_depth, comment.as_slice()) {
+                Ok(flac) => download(&flac, "audio/flac", &format!("{}.flac", file_stem(model, id))),
+                Err(error) => {
+                    orders.send_msg(Msg::Error(format!("The FLAC couldn't be exported: {}", error)));
+                }
+            }
+        }
+        Msg::WavDecoded(_, Err(error)) | Msg::FlacDecoded(_, Err(error)) => {
+            log_error!("Error decoding recording:", error);
+        }
+        Msg::SetChunkStrategy(value) => {
+            if let Some(strategy) = ChunkStrategy::parse(&value) {
+                model.chunk_strategy = strategy;
+            }
+        }
+        Msg::PauseRecording => {
+            if let Some(recorder) = model.recorder.as_ref() {
+                // Not yet started, or already made inactive by the browser - its `stop` event follows then.
+                if model.paused_at.is_none() && recorder.state() == RecordingState::Recording {
+                    if let Err(error) = recorder.pause() {
+                        orders.send_msg(Msg::Error(format!("Recording couldn't be paused: {:?}", error)));
+                        return;
+                    }
+                    if let Some(tap) = model.pcm_tap.as_ref() {
+                        tap.set_paused(true);
+                    }
+                    model.paused_at = Some(js_sys::Date::now());
+                    log_info!("Recording paused");
+                    progress::emit("paused", &[("elapsed", Value::Number(take_elapsed_ms(model)))]);
+                }
+            }
+        }
+        Msg::ResumeRecording => {
+            if let Some(recorder) = model.recorder.as_ref() {
+                if model.paused_at.is_none() || recorder.state() != RecordingState::Paused {
+                    return;
+                }
+                if let Err(error) = recorder.resume() {
+                    orders.send_msg(Msg::Error(format!("Recording couldn't be resumed: {:?}", error)));
+                    return;
+                }
+                if let Some(paused_at) = model.paused_at.take() {
+                    if let Some(tap) = model.pcm_tap.as_ref() {
+                        tap.set_paused(false);
+                    }
+                    let duration_ms = js_sys::Date::now() - paused_at;
+                    model.paused_ms += duration_ms;
+                    // Nothing was recorded in the meantime, so the elapsed time now is where the pause was.
+                    model.pauses.push((take_elapsed_ms(model), duration_ms));
+                    log_info!("Recording resumed");
+                    progress::emit("resumed", &[("elapsed", Value::Number(take_elapsed_ms(model)))]);
+                }
+            }
+        }
+        Msg::SetMarkerLabel(label) => {
+            model.marker_label = label;
+        }
+        Msg::AddMarker(label) => {
+            if model.recorder.is_some() {
+                // While paused the elapsed time is frozen, so the marker lands where the recording continues.
+                model.markers.push(Marker { elapsed_ms: take_elapsed_ms(model), label });
+                model.marker_label.clear();
+            }
+        }
+        Msg::DevicesEnumerated(Ok(devices)) => {
+            model.devices = devices;
+        }
+        Msg::DevicesEnumerated(Err(error)) => {
+            log_error!("Error enumerating devices:", error);
+        }
+        Msg::SetDeviceGroup(group_id) => {
+            // Applied on the next stream acquisition.
+            model.group_id = if group_id.is_empty() { None } else { Some(group_id) };
+            release_kept_stream(model);
+        }
+        Msg::SetDefaultMic(role) => {
+            model.default_mic = DefaultRole::parse(&role);
+            release_kept_stream(model);
+        }
+        Msg::ToggleRecordVideo => {
+            model.record_video = !model.record_video;
+            release_kept_stream(model);
+        }
+        Msg::SetCaptureSampleRate(value) => {
+            model.audio_constraints.sample_rate = value.parse().ok();
+            release_kept_stream(model);
+        }
+        Msg::SetCaptureChannelCount(value) => {
+            model.audio_constraints.channel_count = value.parse().ok();
+            release_kept_stream(model);
+        }
+        Msg::SetFeature(feature, value) => {
+            model.audio_constraints.set_feature(feature, value.parse().ok());
+            release_kept_stream(model);
+        }
+        Msg::ToggleMixMic(device_id) => {
+            match model.mix_inputs.iter().position(|input| input.device_id == device_id) {
+                Some(index) => {
+                    model.mix_inputs.remove(index);
+                }
+                None => model.mix_inputs.push(MixInput { device_id, gain: 1., ended: false }),
+            }
+            release_kept_stream(model);
+        }
+        Msg::SetMixGain(index, gain) => {
+            if let (Some(input), Ok(gain)) = (model.mix_inputs.get_mut(index), gain.parse::<f32>()) {
+                input.gain = gain;
+                if let Some(mixer) = model.mixer.as_ref() {
+                    mixer.set_gain(index, gain);
+                }
+            }
+        }
+        Msg::MixInputEnded(index) => {
+            log_info!("A mixed mic has ended:", index);
+            if let Some(input) = model.mix_inputs.get_mut(index) {
+                input.ended = true;
+            }
+        }
+        Msg::FlipCamera => {
+            // A recorder can't switch tracks, so the other camera is taken with the next stream.
+            model.facing_mode = model.facing_mode.flipped();
+            release_kept_stream(model);
+        }
+        Msg::ToggleKeepStream => {
+            model.keep_stream = !model.keep_stream;
+            if !model.keep_stream {
+                release_kept_stream(model);
+            }
+        }
+        Msg::SetMicGrace(value) => {
+            model.mic_grace_s = value.parse().unwrap_or_default();
+        }
+        Msg::MicGraceExpired => {
+            model.mic_release_handle = None;
+            if !model.keep_stream && !model.keep_stream_for_next && !model.push_to_talk {
+                log_info!("Releasing the idle mic");
+                release_kept_stream(model);
+            }
+        }
+        Msg::Prewarm => {
+            model.prewarm_gesture = None;
+            let idle = model.recorder.is_none() && !model.stopping;
+            if idle && model.stream.is_none() && !model.stream_pending {
+                model.prewarm = Prewarm::Acquiring;
+                request_audio_stream(model, orders);
+            }
+        }
+        Msg::PrewarmExpired => {
+            model.prewarm_release_handle = None;
+            if model.prewarm == Prewarm::Ready {
+                log_info!("Releasing the unused prewarmed stream");
+                model.prewarm = Prewarm::Off;
+                release_kept_stream(model);
+            }
+        }
+        Msg::ReleaseStream => {
+            model.keep_stream_for_next = false;
+            release_kept_stream(model);
+        }
+        Msg::StreamFailed(error) => {
+            model.stream_pending = false;
+            model.prewarm = Prewarm::Off;
+            // Nothing waits for the stream anymore.
+            model.calibration = None;
+            model.replay_acquiring = false;
+            if let Some(sink) = model.file_sink.take() {
+                sink.abort();
+            }
+            // Back to idle as if nothing was asked for.
+            if !error.is_benign() {
+                model.stream_error = Some(error);
+            }
+        }
+        Msg::RetryStream => {
+            model.stream_error = None;
+            if model.stream.is_none() {
+                request_audio_stream(model, orders);
+            }
+        }
+        Msg::Error(error) => {
+            log_error!(error);
+            model.error = Some(error);
+        }
+        Msg::DismissError => {
+            model.error = None;
+            model.stream_error = None;
+        }
+        Msg::ReadLastRecording(cell) => {
+            *cell.borrow_mut() = model.recordings.last().map(|recording| recording.bytes.clone());
+        }
+        Msg::TimesliceCheck(epoch) => {
+            model.timeslice_check = None;
+            // A paused recorder doesn't emit chunks either, so that's no evidence.
+            if epoch == model.epoch && model.recorder.is_some() && model.paused_at.is_none() {
+                log_info!("No chunk within twice the timeslice, expecting a single blob at stop");
+                model.single_blob = true;
+                note_fallback(
+                    &mut model.compatibility_notes,
+                    format!("{} ignores the timeslice, the data arrives at stop", model.take_mime_type),
+                );
+            }
+        }
+        Msg::ToggleDedup => {
+            model.dedup = !model.dedup;
+            model.dedup_notice = None;
+        }
+        Msg::TogglePushToTalk => {
+            model.push_to_talk = !model.push_to_talk;
+            model.talk_held = false;
+            if !model.push_to_talk && !model.keep_stream {
+                release_kept_stream(model);
+            }
+        }
+        Msg::TalkPressed => {
+            if model.push_to_talk && !model.talk_held {
+                model.talk_held = true;
+                // A press while the previous snippet is being finalized starts once it's done.
+                if !model.stopping {
+                    orders.send_msg(Msg::StartRecording);
+                }
+            }
+        }
+        Msg::TalkReleased => {
+            if model.talk_held {
+                model.talk_held = false;
+                if model.recorder.is_some() {
+                    stop_recorder(model, orders);
+                }
+            }
+        }
+        Msg::ToggleKeepAlive => {
+            model.keep_alive_in_background = !model.keep_alive_in_background;
+            if !model.keep_alive_in_background {
+                model.keep_alive = None;
+            } else if model.recorder.is_some() {
+                start_keep_alive(model);
+            }
+        }
+        Msg::ToggleLowPower => {
+            model.low_power = !model.low_power;
+            if model.low_power {
+                model.spectrum.clear();
+                release_level_meter_if_unused(model);
+            } else {
+                if visualizing(model) && model.recorder.is_some() && model.level_meter.is_none() {
+                    start_level_meter(model, orders);
+                }
+                orders.after_next_render(|_| Msg::DrawWaveform);
+            }
+            // At the new rate; it stops on its own if there's nothing to tick for.
+            model.tick_handle = None;
+            ensure_ticking(model, orders);
+        }
+        Msg::ToggleSaveToFile => {
+            model.save_to_file = !model.save_to_file;
+        }
+        Msg::FileSinkOpened(Ok(Some(sink))) => {
+            log_info!("Saving to", sink.name);
+            model.file_sink = Some(sink);
+            orders.send_msg(Msg::StartRecording);
+        }
+        Msg::FileSinkOpened(Ok(None)) => {
+            log_info!("Save picker cancelled");
+        }
+        Msg::FileSinkOpened(Err(error)) => {
+            log_error!("Error opening file:", error);
+            model.error = Some(format!("Could not open the file: {:?}", error));
+        }
+        Msg::FileSaved(name, result) => {
+            model.file_status = Some(match result {
+                Ok(()) => format!("Saved to {}", name),
+                Err(error) => {
+                    log_error!("Error saving file:", error);
+                    format!("Saving to {} failed", name)
+                }
+            });
+        }
+        Msg::ToggleMeters => {
+            model.show_meters = !model.show_meters;
+            if model.show_meters {
+                if visualizing(model) && model.recorder.is_some() && model.level_meter.is_none() {
+                    start_level_meter(model, orders);
+                }
+            } else {
+                release_level_meter_if_unused(model);
+            }
+        }
+        Msg::ToggleAccurateMeter => {
+            model.accurate_meter = !model.accurate_meter;
+            if model.accurate_meter {
+                if model.level_meter.is_some() {
+                    request_worklet_meter(model, orders);
+                }
+            } else {
+                model.worklet_meter = None;
+                model.rms = 0.;
+            }
+        }
+        Msg::WorkletMeterReady(Ok(worklet_meter)) => {
+            // The analyser may have been released while the module was loading.
+            if model.accurate_meter && model.level_meter.is_some() {
+                model.worklet_meter = Some(worklet_meter);
+            }
+        }
+        Msg::WorkletMeterReady(Err(error)) => {
+            log_error!("AudioWorklet meter unavailable, using the analyser:", error);
+            note_fallback(&mut model.compatibility_notes, "No AudioWorklet meter, the level comes from the analyser".to_owned());
+        }
+        Msg::ToggleSpectrum => {
+            model.show_spectrum = !model.show_spectrum;
+            if model.show_spectrum {
+                if visualizing(model) && model.recorder.is_some() && model.level_meter.is_none() {
+                    start_level_meter(model, orders);
+                }
+            } else {
+                model.spectrum.clear();
+                release_level_meter_if_unused(model);
+            }
+        }
+        Msg::SetFftSize(value) => {
+            if let Some(fft_size) = value.parse().ok().filter(|size| FFT_SIZES.contains(size)) {
+                model.fft_size = fft_size;
+                if let Some(level_meter) = model.level_meter.as_mut() {
+                    level_meter.set_fft_size(fft_size);
+                }
+            }
+        }
+        Msg::SetSpectrumBands(value) => {
+            if let Ok(bands) = value.parse() {
+                model.spectrum_bands = bands;
+            }
+        }
+        Msg::SetMaxDuration(seconds) => {
+            // An empty value means no limit.
+            model.hints.max_duration = None;
+            if seconds.trim().is_empty() {
+                model.max_duration_ms = None;
+            } else if let Ok(seconds) = seconds.parse::<f64>() {
+                let clamped = limits::clamp_max_duration_s(seconds);
+                if (clamped - seconds).abs() > f64::EPSILON {
+                    model.hints.max_duration = Some(format!(
+                        "Limited to {} - {} s",
+                        limits::MIN_MAX_DURATION_S,
+                        limits::MAX_MAX_DURATION_S,
+                    ));
+                }
+                model.max_duration_ms = Some(clamped * 1000.);
+            } else {
+                model.hints.max_duration = Some("Not a number".to_owned());
+            }
+        }
+        Msg::SetAudioBitrate(kbps) => {
+            model.hints.bitrate = None;
+            match kbps.parse::<f64>() {
+                Ok(kbps) if kbps.is_finite() => {
+                    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                    let requested = (kbps * 1000.).round().max(0.) as u32;
+                    model.audio_bits_per_second = limits::clamp_bits_per_second(requested);
+                    if model.audio_bits_per_second != requested {
+                        model.hints.bitrate = Some(format!(
+                            "Limited to {} - {} kbps",
+                            limits::MIN_BITS_PER_SECOND / 1000,
+                            limits::MAX_BITS_PER_SECOND / 1000,
+                        ));
+                    }
+                }
+                _ => model.hints.bitrate = Some("Not a number".to_owned()),
+            }
+        }
+        Msg::SetTimeslice(ms) => {
+            model.hints.timeslice = None;
+            match ms.parse::<i32>() {
+                Ok(requested) => {
+                    model.timeslice_ms = limits::clamp_timeslice_ms(requested);
+                    if model.timeslice_ms != requested {
+                        model.hints.timeslice = Some(format!(
+                            "Limited to {} - {} ms",
+                            limits::MIN_TIMESLICE_MS,
+                            limits::MAX_TIMESLICE_MS,
+                        ));
+                    }
+                }
+                Err(_) => model.hints.timeslice = Some("Not a whole number".to_owned()),
+            }
+        }
+    }
+}
+
+// Reads `blob` into the next slot of `model.chunks`.
+fn read_chunk(model: &mut Model, orders: &mut impl Orders<Msg>, blob: Blob) {
+    let index = model.chunks.len();
+    model.chunks.push(Vec::new());
+    model.pending_reads += 1;
+    let epoch = model.epoch;
+    orders.perform_cmd(async move {
+        match read_as_bytes(&blob).await {
+            Ok(bytes) => Msg::BlobRead(epoch, index, bytes),
+            Err(error) => {
+                log_error!("Error reading chunk:", error.to_string());
+                Msg::ChunkReadFailed(epoch, index)
+            }
+        }
+    });
+}
+
+// Hands the chunk at `index` to wherever the take goes, all of which put the chunks in order.
+fn deliver_chunk(model: &mut Model, index: usize, bytes: Vec<u8>) {
+    if let Some(live_playback) = model.live_playback.as_ref() {
+        live_playback.append(index, bytes.clone());
+    }
+    if let Some(live_stream) = model.live_stream.as_mut() {
+        live_stream.push(index, bytes.clone());
+    }
+    match model.file_sink.as_mut() {
+        Some(sink) => {
+            if let Err(error) = sink.write_chunk(index, bytes) {
+                log_error!("Error writing to file:", error);
+            }
+        }
+        None => model.chunks[index] = bytes,
+    }
+}
+
+// The take's bytes: the chunks in their slots' order, whatever order they were read in.
+// A batched take has a single slot, of the blobs already concatenated in the order received.
+fn concat_chunks(chunks: &mut Vec<Vec<u8>>) -> Vec<u8> {
+    chunks.drain(..).flatten().collect()
+}
+
+// The waveform of `pcm`, with the playhead if the player plays that recording.
+fn draw_waveform(model: &Model) {
+    let ((id, pcm), canvas) = match (model.pcm.as_ref(), model.waveform_canvas.get()) {
+        (Some(pcm), Some(canvas)) => (pcm, canvas),
+        _ => return,
+    };
+    let context = match canvas.get_context("2d") {
+        Ok(Some(context)) => context.unchecked_into::<CanvasRenderingContext2d>(),
+        _ => return,
+    };
+    let (width, height) = (f64::from(WAVEFORM_WIDTH), f64::from(WAVEFORM_HEIGHT));
+    context.clear_rect(0., 0., width, height);
+    context.set_fill_style(&"steelblue".into());
+    for (column, peak) in model.waveform.iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let x = column as f64;
+        let bar = f64::from(*peak) * height;
+        context.fill_rect(x, (height - bar) / 2., 1., bar.max(1.));
+    }
+    if model.player_id == Some(*id) {
+        if let Some(player) = model.player.get() {
+            let duration = pcm.duration_s();
+            if duration > 0. {
+                context.set_fill_style(&"red".into());
+                context.fill_rect((player.current_time() / duration).min(1.) * width, 0., 1., height);
+            }
+        }
+    }
+}
+
+// Silence has no loudness and so no gain.
+fn set_replay_gain(model: &mut Model, id: usize, loudness: &Loudness) -> Option<f64> {
+    let replay_gain_db = loudness.integrated_lufs.map(loudness::replay_gain_db);
+    if let Some(recording) = model.recordings.iter_mut().find(|r| r.id == id) {
+        recording.metadata.replay_gain_db = replay_gain_db;
+    }
+    replay_gain_db
+}
+
+// The whole decoded recording with the `ExportSettings` applied, and the ReplayGain comment to embed if any.
+fn prepare_export(model: &mut Model, id: usize, mut pcm: Pcm) -> (Pcm, Option<String>) {
+    // Before the pauses go in - the trim is of recorded time like the markers.
+    pcm::trim_end(&mut pcm, model.export_settings.trim_end_s);
+    if model.export_settings.inject_pauses {
+        if let Some(recording) = model.recordings.iter().find(|r| r.id == id) {
+            let pauses = &recording.metadata.pauses;
+            pcm.channels = pcm.channels.iter()
+                .map(|channel| pcm::insert_silence(channel, pcm.sample_rate, pauses))
+                .collect();
+        }
+    }
+    let pcm = process_for_export(pcm, &model.export_settings);
+    // Measured after the processing - the downmix changes the loudness.
+    let replay_gain_db = set_replay_gain(model, id, &loudness::measure(&pcm.channels, pcm.sample_rate));
+    let comment = replay_gain_db
+        .filter(|_| model.export_settings.embed_replay_gain)
+        .map(|gain| format!("REPLAYGAIN_TRACK_GAIN={:+.2} dB", gain));
+    (pcm, comment)
+}
+
+fn process_for_export(mut pcm: Pcm, settings: &ExportSettings) -> Pcm {
+    if settings.mono {
+        pcm.channels = vec![pcm::downmix_to_mono(&pcm.channels)];
+    }
+    if let Some(rate) = settings.sample_rate {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let from = pcm.sample_rate as u32;
+        pcm.channels = pcm.channels.iter().map(|channel| pcm::resample_linear(channel, from, rate)).collect();
+        #[allow(clippy::cast_precision_loss)]
+        let sample_rate = rate as f32;
+        pcm.sample_rate = sample_rate;
+    }
+    for channel in &mut pcm.channels {
+        if settings.remove_dc {
+            pcm::remove_dc(channel);
+        }
+        pcm::fade(channel, pcm.sample_rate, settings.fade_in_ms, settings.fade_out_ms, settings.fade_curve);
+    }
+    pcm
+}
+
+// The name without the extension of the files of recording `id`.
+fn file_stem(model: &Model, id: usize) -> String {
+    let today = js_sys::Date::new_0();
+    let date = format!("{:04}-{:02}-{:02}", today.get_full_year(), today.get_month() + 1, today.get_date());
+    file_name::stem(&model.file_name_template, id + 1, &date)
+}
+
+fn zip_files<'a>(model: &Model, recordings: impl Iterator<Item = &'a Recording>) -> Vec<(String, Vec<u8>)> {
+    let (names, bytes): (Vec<_>, Vec<_>) = recordings
+        .map(|recording| (recording.file_name(&file_stem(model, recording.id)), recording.bytes.clone()))
+        .unzip();
+    file_name::unique(names).into_iter().zip(bytes).collect()
+}
+
+fn app_config(model: &Model) -> AppConfig {
+    AppConfig {
+        audio_bits_per_second: model.audio_bits_per_second,
+        timeslice_ms: model.timeslice_ms,
+        mime_type: model.mime_type.clone(),
+        sample_rate: model.audio_constraints.sample_rate,
+        channel_count: model.audio_constraints.channel_count,
+        features: Feature::ALL.iter()
+            .filter_map(|feature| Some((feature.as_str().to_owned(), model.audio_constraints.feature(*feature)?)))
+            .collect(),
+        pipeline: PipelineConfig {
+            capture_mode: model.capture_mode.as_str().to_owned(),
+            chunk_strategy: model.chunk_strategy.as_str().to_owned(),
+            worklet_url: model.worklet_url.clone(),
+        },
+        file_name_template: model.file_name_template.clone(),
+    }
+}
+
+// Applied like the same settings changed one by one: numbers are clamped, the stream is reacquired
+// for the next take. Nothing is applied when the config is invalid.
+fn import_config(model: &mut Model, json: &str) {
+    if model.recorder.is_some() || model.stopping {
+        model.config_status = Some("Settings can't be imported while recording".to_owned());
+        return;
+    }
+    let config = match AppConfig::from_json(json) {
+        Ok(config) => config,
+        Err(error) => {
+            log_error!("Error importing settings:", error);
+            model.config_status = Some(error);
+            return;
+        }
+    };
+    let mut notes = Vec::new();
+    model.audio_bits_per_second = limits::clamp_bits_per_second(config.audio_bits_per_second);
+    if model.audio_bits_per_second != config.audio_bits_per_second {
+        notes.push(format!("audio bitrate limited to {} kbps", model.audio_bits_per_second / 1000));
+    }
+    model.timeslice_ms = limits::clamp_timeslice_ms(config.timeslice_ms);
+    if model.timeslice_ms != config.timeslice_ms {
+        notes.push(format!("timeslice limited to {} ms", model.timeslice_ms));
+    }
+    model.hints.bitrate = None;
+    model.hints.timeslice = None;
+    if let Some((container, codec)) = mime::parse_mime_type(&config.mime_type) {
+        model.container = container;
+        model.codec = codec;
+    }
+    model.mime_type = config.mime_type.clone();
+    model.audio_constraints.sample_rate = config.sample_rate;
+    model.audio_constraints.channel_count = config.channel_count;
+    for feature in &Feature::ALL {
+        let enabled = config.features().find(|(f, _)| f == feature).map(|(_, enabled)| enabled);
+        model.audio_constraints.set_feature(*feature, enabled);
+    }
+    // Validated by `from_json`.
+    model.capture_mode = CaptureMode::parse(&config.pipeline.capture_mode).unwrap_or_default();
+    model.chunk_strategy = ChunkStrategy::parse(&config.pipeline.chunk_strategy).unwrap_or_default();
+    model.file_name_template = config.file_name_template.clone();
+    let worklet_url = config.pipeline.worklet_url.trim();
+    if worklet_url != model.worklet_url {
+        model.worklet_url = worklet_url.to_owned();
+        model.processing_failed = false;
+        model.processing = None;
+    }
+    release_kept_stream(model);
+    model.config_status = Some(if notes.is_empty() {
+        "Settings imported".to_owned()
+    } else {
+        format!("Settings imported, {}", notes.join(", "))
+    });
+}
+
+// How long the temporary URL of a download outlives the click - some browsers only start
+// fetching it after the click handler has returned.
+const DOWNLOAD_URL_LIFETIME_MS: i32 = 10_000;
+
+// Saves `bytes` as a file through a temporary object URL and a clicked `<a download>`.
+fn download(bytes: &[u8], mime: &str, filename: &str) {
+    let blob = Blob::new_with_options(bytes, Some(mime));
+    let url = match web_sys::Url::create_object_url_with_blob(blob.as_ref()) {
+        Ok(url) => url,
+        Err(error) => {
+            log_error!("Error creating the download URL:", error);
+            return;
+        }
+    };
+    match document().create_element("a") {
+        Ok(anchor) => {
+            let anchor = anchor.unchecked_into::<web_sys::HtmlAnchorElement>();
+            anchor.set_href(&url);
+            anchor.set_download(filename);
+            anchor.click();
+        }
+        Err(error) => log_error!("Error creating the download link:", error),
+    }
+    let revoke = Closure::once_into_js(move || {
+        if let Err(error) = web_sys::Url::revoke_object_url(&url) {
+            log_error!("Error revoking the download URL:", error);
+        }
+    });
+    if let Err(error) = window().set_timeout_with_callback_and_timeout_and_arguments_0(
+        revoke.unchecked_ref(),
+        DOWNLOAD_URL_LIFETIME_MS,
+    ) {
+        log_error!("Error scheduling the download URL's revoke:", error);
+    }
+}
+
+// Sends the next pending part of `model.upload`, or the final "complete" request once all parts are done.
+// Every part carries its index and the part count in headers so the server can reassemble them.
+fn send_next_part(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    let upload = match model.upload.as_mut() {
+        Some(upload) => upload,
+        None => return,
+    };
+    let recording_id = upload.recording_id;
+    let part_count = upload.part_count().to_string();
+    let endpoint = model.upload_endpoint.clone();
+
+    if upload.is_complete() {
+        orders.perform_cmd(async move {
+            let result = Request::new(endpoint)
+                .method(Method::Post)
+                .header(Header::custom("X-Upload-Id", recording_id.to_string()))
+                .header(Header::custom("X-Part-Count", part_count))
+                .header(Header::custom("X-Upload-Complete", "true"))
+                .fetch()
+                .await
+                .and_then(Response::check_status)
+                .map(drop);
+            Msg::UploadCompleted(result)
+        });
+        return;
+    }
+
+    if let Some((index, range)) = upload.next_part() {
+        let recording = match model.recordings.iter().find(|r| r.id == recording_id) {
+            Some(recording) => recording,
+            // The recording has been deleted in the meantime.
+            None => {
+                model.upload = None;
+                return;
+            }
+        };
+        let body = js_sys::Uint8Array::from(&recording.bytes[range]);
+        let content_type = recording.mime.clone();
+        orders.perform_cmd(async move {
+            let result = Request::new(endpoint)
+                .method(Method::Post)
+                .header(Header::content_type(content_type))
+                .header(Header::custom("X-Upload-Id", recording_id.to_string()))
+                .header(Header::custom("X-Part-Index", index.to_string()))
+                .header(Header::custom("X-Part-Count", part_count))
+                .body(body.into())
+                .fetch()
+                .await
+                .and_then(Response::check_status)
+                .map(drop);
+            Msg::PartUploaded(index, result)
+        });
+    }
+}
+
+// The whole recording in one request, see `UploadMethod::Put`.
+fn put_recording(model: &mut Model, orders: &mut impl Orders<Msg>, id: usize) {
+    let recording = match model.recordings.iter().find(|r| r.id == id) {
+        Some(recording) => recording,
+        None => return,
+    };
+    let request = match upload::put_request(&model.presigned_url, &recording.mime) {
+        Ok(request) => request,
+        Err(error) => {
+            orders.send_msg(Msg::Error(format!("Upload failed: {}", error)));
+            return;
+        }
+    };
+    let body = js_sys::Uint8Array::from(recording.bytes.as_slice());
+    model.put_pending = true;
+    model.upload_status = None;
+    orders.perform_cmd(async move {
+        let result = match Request::new(request.url)
+            .method(Method::Put)
+            .header(Header::content_type(request.content_type))
+            .body(body.into())
+            .fetch()
+            .await
+        {
+            Ok(response) if upload::put_succeeded(response.status().code) => Ok(()),
+            Ok(response) => Err(format!("the server answered {} {}", response.status().code, response.status().text)),
+            Err(error) => {
+                log_error!("Error uploading:", error);
+                Err("the request didn't go through (network or CORS)".to_owned())
+            }
+        };
+        Msg::PutUploaded(result)
+    });
+}
+
+fn connect_live_stream(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_event = Rc::new(move |generation, event| app.update(msg_mapper(Msg::LiveStreamEvent(generation, event))));
+    if let Some(live_stream) = model.live_stream.as_mut() {
+        if let Err(error) = live_stream.connect(on_event) {
+            log_error!("Error connecting the live stream:", error);
+            schedule_reconnect(model, orders);
+        }
+    }
+}
+
+// After the socket closed (or couldn't be opened), unless the stream is done or has given up.
+fn schedule_reconnect(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if let Some(delay_ms) = model.live_stream.as_mut().and_then(LiveStream::closed) {
+        log_info!("Live stream disconnected, reconnecting in ms:", delay_ms);
+        model.reconnect_handle = Some(orders.perform_cmd_with_handle(cmds::timeout(
+            delay_ms,
+            || Msg::ReconnectLiveStream,
+        )));
+    }
+}
+
+// `navigator.clipboard` only exists in secure contexts; `web_sys` binds it as an unstable API only.
+async fn write_clipboard_text(text: &str) -> Result<(), JsValue> {
+    let clipboard = js_sys::Reflect::get(&window().navigator(), &"clipboard".into())?;
+    if clipboard.is_undefined() {
+        return Err(js_sys::Error::new("The clipboard is not available").into());
+    }
+    let write_text = js_sys::Reflect::get(&clipboard, &"writeText".into())?.dyn_into::<js_sys::Function>()?;
+    JsFuture::from(write_text.call1(&clipboard, &text.into())?.dyn_into::<js_sys::Promise>()?).await?;
+    Ok(())
+}
+
+// Recorded time of the take in progress - the clock stops while the recorder is paused.
+fn take_elapsed_ms(model: &Model) -> f64 {
+    let now = model.paused_at.unwrap_or_else(js_sys::Date::now);
+    now - model.take_started_at - model.paused_ms + model.take_offset_ms
+}
+
+fn probe_formats() -> Vec<(Container, Codec)> {
+    Container::ALL.iter()
+        .flat_map(|container| Codec::ALL.iter().map(move |codec| (*container, *codec)))
+        .filter(|(container, codec)| MediaRecorder::is_type_supported(&mime::mime_type(*container, *codec)))
+        .collect()
+}
+
+fn start_or_acquire(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if model.stream.is_some() {
+        start_take(model, orders);
+    } else {
+        // A prewarm in flight delivers the stream for this take instead.
+        if model.prewarm == Prewarm::Acquiring {
+            model.prewarm = Prewarm::Off;
+        }
+        request_audio_stream(model, orders);
+    }
+}
+
+fn play_beep(model: &mut Model, orders: &mut impl Orders<Msg>, frequency: f32) {
+    if !model.countdown_beeps {
+        return;
+    }
+    model.beep = None;
+    match Tone::play(frequency, f64::from(BEEP_DURATION_MS) / 1000.) {
+        Ok(beep) => {
+            model.beep = Some(beep);
+            orders.perform_cmd(cmds::timeout(BEEP_DURATION_MS, || Msg::BeepEnded));
+        }
+        Err(error) => log_error!("Error playing the countdown beep:", error),
+    }
+}
+
+// Starts the recorder right away, or once the custom processing graph for the stream has been built.
+fn start_take(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    let stream = model.stream.clone().expect("start take without stream");
+    if model.worklet_url.is_empty() || model.processing_failed {
+        start_recorder(model, orders);
+        return;
+    }
+    if model.processing.as_ref().map_or(false, |graph| graph.reads(&stream)) {
+        start_recorder(model, orders);
+        return;
+    }
+    // A graph made for a previous stream reads a stopped mic.
+    model.processing = None;
+    model.processing_pending = true;
+    let (module_url, channel_count) = (model.worklet_url.clone(), channel_count(&stream));
+    orders.perform_cmd(async move {
+        Msg::ProcessingReady(ProcessingGraph::new(&stream, &module_url, channel_count).await)
+    });
+}
+
+fn start_recorder(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if duplicate_start(model, StartStage::Recorder) {
+        return;
+    }
+    // Released while the mic (or the processing) was still being set up - nothing to record.
+    if model.push_to_talk && !model.talk_held {
+        log_info!("Talk button released before the recorder started");
+        return;
+    }
+    let stream = model.stream.as_ref().expect("start recorder without stream");
+    if model.prewarm == Prewarm::Ready {
+        set_audio_enabled(stream, true);
+        model.prewarm = Prewarm::Off;
+        model.prewarm_release_handle = None;
+    }
+    model.mixer = None;
+    if model.mix_inputs.len() >= 2 && stream.get_audio_tracks().length() >= 2 {
+        let gains = model.mix_inputs.iter().map(|input| input.gain).collect::<Vec<_>>();
+        let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+        let on_ended = Rc::new(move |index| app.update(msg_mapper(Msg::MixInputEnded(index))));
+        match Mixer::new(stream, &gains, on_ended) {
+            Ok(mixer) => model.mixer = Some(mixer),
+            Err(error) => log_error!("Error mixing the mics, recording the first one only:", error),
+        }
+        for input in &mut model.mix_inputs {
+            input.ended = false;
+        }
+    }
+    // What's recorded - the mix, or the processed audio when the graph reads this stream,
+    // otherwise the stream itself. The graph doesn't take a mix.
+    let mixed = model.mixer.as_ref().map(Mixer::output);
+    let processing = model.processing.as_ref().filter(|graph| mixed.is_none() && graph.reads(stream));
+    let mut recorded = mixed.or_else(|| processing.map(ProcessingGraph::output)).unwrap_or(stream).clone();
+    model.gain_stage = None;
+    if (model.input_gain - 1.).abs() > f32::EPSILON {
+        match GainStage::new(&recorded, model.input_gain) {
+            Ok(gain_stage) => {
+                recorded = gain_stage.output().clone();
+                model.gain_stage = Some(gain_stage);
+            }
+            Err(error) => log_error!("Error applying the input gain, recording without it:", error),
+        }
+    }
+    model.epoch += 1;
+    let epoch = model.epoch;
+
+    // `App` clone is cheap. `msg_mapper` is necessary to satisfy Rust types 
+    // (`Msg` in `Orders` is hidden in an associated type).
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+
+    // `Closure::wrap` can be written as `Closure::new` 
+    //- `new` doesn't need boilerplate like `Box::new` and `as Box<..` however it's not stable yet.
+    // `Closure` is a bridge between Rust closures and JS callbacks. That's why the input is `JsValue`.
+    let on_data_callback = Closure::wrap(Box::new(move |blob: JsValue| {
+        // We are sure that our `JsValue` represents `BlobEvent` so we can use `unchecked_into` to improve performance.
+        let web_sys_blob = blob.unchecked_into::<BlobEvent>().data().unwrap();
+        // Convert `web_sys::Blob` into `gloo_file`'s one because `gloo_file`'s `Blob` wrapper has some nice methods
+        // that eliminate boilerplate a lot.
+        let msg = Msg::BlobReceived(epoch, Blob::from(web_sys_blob));
+        // Pass the message to Seed. Then Seed invokes our `update` function with passed message.
+        app.update(msg_mapper(msg));
+    }) as Box<dyn Fn(JsValue)>);
+
+    // `stop` fires the last `dataavailable` before `stop`, so once we get here
+    // every chunk of the take has at least been received.
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_stop_callback = Closure::wrap(Box::new(move |_: JsValue| {
+        app.update(msg_mapper(Msg::RecorderStopped(epoch)));
+    }) as Box<dyn Fn(JsValue)>);
+
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_error_callback = Closure::wrap(Box::new(move |event: JsValue| {
+        let name = js_sys::Reflect::get(&event, &"error".into())
+            .and_then(|error| js_sys::Reflect::get(&error, &"name".into()))
+            .ok()
+            .and_then(|name| name.as_string())
+            .unwrap_or_else(|| "unknown error".to_owned());
+        app.update(msg_mapper(Msg::RecorderErrored(epoch, name)));
+    }) as Box<dyn Fn(JsValue)>);
+
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_mute_callback = Closure::wrap(Box::new(move |_: JsValue| {
+        app.update(msg_mapper(Msg::TrackMuted));
+    }) as Box<dyn Fn(JsValue)>);
+    let (app, msg_mapper) = (orders.clone_app(), orders.msg_mapper());
+    let on_unmute_callback = Closure::wrap(Box::new(move |_: JsValue| {
+        app.update(msg_mapper(Msg::TrackUnmuted));
+    }) as Box<dyn Fn(JsValue)>);
+
+    let mime_type = if model.record_video && !model.tab_audio {
+        mime::video_mime_type(model.container)
+    } else {
+        model.mime_type.clone()
+    };
+    let mime_type = if mime_type.is_empty() || MediaRecorder::is_type_supported(&mime_type) {
+        mime_type
+    } else {
+        note_fallback(
+            &mut model.compatibility_notes,
+            format!("{} isn't supported, the browser's default format is used", mime_type),
+        );
+        String::new()
+    };
+    let recorder = match create_recorder(&recorded, &mime_type, model.audio_bits_per_second) {
+        Ok(recorder) => recorder,
+        Err(error) => {
+            if !listening(model) {
+                if let Some(stream) = model.stream.take() {
+                    stop_tracks(&stream);
+                }
+            }
+            orders.send_msg(Msg::Error(format!("The recorder couldn't be created: {:?}", error)));
+            return;
+        }
+    };
+    recorder.set_ondataavailable(Some(on_data_callback.as_ref().unchecked_ref()));
+    recorder.set_onstop(Some(on_stop_callback.as_ref().unchecked_ref()));
+    recorder.set_onerror(Some(on_error_callback.as_ref().unchecked_ref()));
+    // The mic is granted and the recorder constructed, but nothing is recorded yet.
+    let ready_mime_type = recorder.mime_type();
+    call_ready_callback(&ready_mime_type);
+    progress::emit("ready", &[("mimeType", Value::Text(&ready_mime_type))]);
+    // We want to receive recorded data each second.
+    // Some engines can't do timesliced output for some codecs and throw - then we
+    // fall back to a single blob delivered at stop.
+    model.single_blob = false;
+    if let Err(error) = recorder.start_with_time_slice(model.timeslice_ms) {
+        log_info!("Timesliced recording failed, falling back to a single blob:", error);
+        note_fallback(
+            &mut model.compatibility_notes,
+            format!("{} can't be recorded in chunks, the data arrives at stop", mime_type),
+        );
+        if let Err(error) = recorder.start() {
+            recorder.set_ondataavailable(None);
+            recorder.set_onstop(None);
+            recorder.set_onerror(None);
+            if !listening(model) {
+                if let Some(stream) = model.stream.take() {
+                    stop_tracks(&stream);
+                }
+            }
+            orders.send_msg(Msg::Error(format!("Recording couldn't be started: {:?}", error)));
+            return;
+        }
+        model.single_blob = true;
+    } else {
+        // Some engines accept the timeslice but deliver only at stop anyway.
+        model.timeslice_check = Some(orders.perform_cmd_with_handle(cmds::timeout(
+            2 * model.timeslice_ms.unsigned_abs(),
+            move || Msg::TimesliceCheck(epoch),
+        )));
+    }
+    if let Some(graph) = processing {
+        graph.post("start");
+    }
+    // Batched chunks are only read at stop - too late for live playback.
+    model.live_playback = None;
+    let live = model.live_review
+        && model.chunk_strategy == ChunkStrategy::Immediate
+        && model.capture_mode.keeps_compressed();
+    if live {
+        let recorded_mime_type = recorder.mime_type();
+        if live_playback::is_supported(&recorded_mime_type) {
+            match LivePlayback::new(&recorded_mime_type) {
+                Ok(live_playback) => model.live_playback = Some(live_playback),
+                Err(error) => log_error!("Error starting live playback:", error),
+            }
+        } else {
+            note_fallback(
+                &mut model.compatibility_notes,
+                format!("{} can't be played while recording, it plays once stopped", recorded_mime_type),
+            );
+        }
+    }
+    for track in stream.get_audio_tracks().iter() {
+        let track = track.unchecked_into::<MediaStreamTrack>();
+        track.set_onmute(Some(on_mute_callback.as_ref().unchecked_ref()));
+        track.set_onunmute(Some(on_unmute_callback.as_ref().unchecked_ref()));
+    }
+    // The browser may clamp the requested bitrate; the real values are only known once started.
+    model.effective_bitrates = Some(effective_bitrates(&recorder));
+    if let Some((Some(audio), _)) = model.effective_bitrates {
+        if (audio - f64::from(model.audio_bits_per_second)).abs() >= 1000. {
+            note_fallback(
+                &mut model.compatibility_notes,
+                format!(
+                    "Asked for {:.0} kbps of audio, the browser went with {:.0} kbps",
+                    f64::from(model.audio_bits_per_second) / 1000.,
+                    audio / 1000.,
+                ),
+            );
+        }
+    }
+
+    // Store `recorder` in `Model` so we can control it later. 
+    // Also there are often attached some drop procedures so it's also safer to store the instance.
+    model.recorder = Some(recorder);
+    // We need to store callback handle into `Model` or `.forget()` (aka leak) it.
+    // Otherwise it'll be dropped and JS throw error once the callback is invoked 
+    // because JS callback is stored in recorder and still alive.
+    model.on_data_callback = Some(on_data_callback);
+    model.on_stop_callback = Some(on_stop_callback);
+    model.on_error_callback = Some(on_error_callback);
+    model.on_mute_callback = Some(on_mute_callback);
+    model.on_unmute_callback = Some(on_unmute_callback);
+    model.take_started_at = js_sys::Date::now();
+    model.paused_at = None;
+    model.paused_ms = 0.;
+    model.pauses.clear();
+    // This take reuses the stream, so it's not idle anymore.
+    model.mic_release_handle = None;
+    model.interrupted = None;
+    model.take_offset_ms = match model.recovered.as_ref() {
+        Some(recovered) if model.resuming => recovered.elapsed_ms,
+        _ => 0.,
+    };
+    model.elapsed_ms = model.take_offset_ms;
+    model.markers.clear();
+    model.muted_since = None;
+    model.muted_regions.clear();
+    model.chunks.clear();
+    model.chunk_offsets.clear();
+    model.failed_chunks = 0;
+    model.take_bytes = 0.;
+    request_storage_estimate(orders);
+    model.stopped = false;
+    // With the browser's default format only the recorder knows what it records.
+    model.take_mime_type = match model.recorder.as_ref() {
+        Some(recorder) if mime_type.is_empty() => recorder.mime_type(),
+        _ => mime_type,
+    };
+    model.take_capture_mode = model.capture_mode;
+    model.pcm_tap = None;
+    if model.capture_mode.keeps_lossless() {
+        match PcmTap::new(&recorded, channel_count(stream)) {
+            Ok(tap) => model.pcm_tap = Some(tap),
+            Err(error) => {
+                log_error!("Error tapping the samples, the take won't have a WAV:", error);
+                // A lossless-only take would end up empty; the recorder's chunks are kept instead.
+                model.take_capture_mode = CaptureMode::Compressed;
+                note_fallback(
+                    &mut model.compatibility_notes,
+                    format!("The samples couldn't be tapped, the take is recorded compressed only ({:?})", error),
+                );
+            }
+        }
+    }
+    if visualizing(model) && model.level_meter.is_none() {
+        start_level_meter(model, orders);
+    }
+    if model.keep_alive_in_background {
+        start_keep_alive(model);
+    }
+    model.live_stream = None;
+    model.reconnect_handle = None;
+    if !model.stream_url.is_empty() && model.take_capture_mode.keeps_compressed() {
+        model.live_stream = Some(LiveStream::new(model.stream_url.clone(), model.stream_overflow));
+        connect_live_stream(model, orders);
+    }
+    ensure_ticking(model, orders);
+    log_info!("Recording");
+    progress::emit("recording", &[("mimeType", Value::Text(&model.take_mime_type))]);
+}
+
+fn request_storage_estimate(orders: &mut impl Orders<Msg>) {
+    orders.perform_cmd(async { Msg::StorageEstimated(storage::remaining_bytes().await) });
+}
+
+// `Msg::Tick` drives the timer and the level meter; it stops itself once neither is running.
+fn ensure_ticking(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if model.tick_handle.is_none() {
+        model.tick_handle = Some(orders.stream_with_handle(streams::interval(tick_ms(model), || Msg::Tick)));
+    }
+}
+
+const fn tick_ms(model: &Model) -> u32 {
+    if model.low_power {
+        LOW_POWER_TICK_MS
+    } else {
+        TICK_MS
+    }
+}
+
+// The meters and the spectrum, while recording.
+const fn visualizing(model: &Model) -> bool {
+    !model.low_power && (model.show_spectrum || model.show_meters)
+}
+
+fn stop_recorder(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    // Stop recorder and drop it. 
+    //
+    //In an ideal world you should:
+    // 1. Stop the recorder.
+    // 2. Handle the last chunk.
+    // 3. Wait for official recorder death (register `onclose` and maybe also `onerror` callbacks).
+    // 4. Drop the recorder and drop all callbacks (aka `Closure`s).
+    //
+    let action = match model.recorder.as_ref() {
+        Some(recorder) => stop_action(recorder.state()),
+        None => return,
+    };
+    if let Some(recorder) = model.recorder.take() {
+        if action == StopAction::Stop {
+            if let Err(error) = recorder.stop() {
+                log_error!("Error stopping the recorder:", error);
+            }
+        } else {
+            log_info!("The recorder was stopped by the browser, finalizing with what it recorded");
+        }
+        if let Some(graph) = model.processing.as_ref() {
+            graph.post("stop");
+        }
+        model.stopping = true;
+        let epoch = model.epoch;
+        model.finalize_timeout = Some(orders.perform_cmd_with_handle(cmds::timeout(
+            FINALIZE_TIMEOUT_MS,
+            move || Msg::FinalizeTimedOut(epoch),
+        )));
+        // Freeze the timer at the moment of the stop.
+        model.elapsed_ms = take_elapsed_ms(model);
+        model.paused_at.get_or_insert_with(js_sys::Date::now);
+        log_info!("Recording stopped");
+        progress::emit("stopped", &[("elapsed", Value::Number(model.elapsed_ms))]);
+    }
+}
+
+// The level meter is also up for the spectrum, the meters and the calibration; the detector only runs
+// for the voice activation, so that none of those start or stop takes.
+fn voice_activity_change(detector: &mut VoiceActivityDetector, detecting: bool, level: f32, now_ms: f64) -> Option<bool> {
+    if detecting {
+        detector.update(level, now_ms)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum StopAction {
+    Stop,
+    // The recorder is inactive already: its `stop` event is on the way, there's nothing to call.
+    Finalize,
+}
+
+// `start` takes synchronously before the recorder is kept in `Model`, so an inactive one was stopped
+// by the browser - `stop` would throw an `InvalidStateError`. What it recorded is kept, chunks or
+// not (a lossless take has none); `Msg::FinalizeTimedOut` covers a stop event that never arrives.
+fn stop_action(state: RecordingState) -> StopAction {
+    match state {
+        RecordingState::Inactive => StopAction::Finalize,
+        _ => StopAction::Stop,
+    }
+}
+
+// Aborts the take in progress: nothing ends up in the session list and nothing is dispatched.
+// Unlike a stop this doesn't wait for the recorder - its remaining events are ignored via the epoch.
+fn cancel_recorder(model: &mut Model) {
+    if let Some(recorder) = model.recorder.take() {
+        recorder.set_ondataavailable(None);
+        recorder.set_onstop(None);
+        recorder.set_onerror(None);
+        // It may already be inactive if it was stopped right before.
+        let _ = recorder.stop();
+        if let Some(graph) = model.processing.as_ref() {
+            graph.post("stop");
+        }
+    }
+    model.epoch += 1;
+    model.finalize_timeout = None;
+    if let Some(sink) = model.file_sink.take() {
+        sink.abort();
+    }
+    model.pcm_tap = None;
+    model.gain_stage = None;
+    model.mixer = None;
+    model.live_playback = None;
+    model.live_stream = None;
+    model.reconnect_handle = None;
+    // We aren't inside any of the callbacks here, so they can go right away.
+    model.on_data_callback = None;
+    model.on_stop_callback = None;
+    model.on_error_callback = None;
+    model.on_mute_callback = None;
+    model.on_unmute_callback = None;
+    // Voice activation (and the replay) keep listening; otherwise the mic is released.
+    release_level_meter_if_unused(model);
+    model.keep_alive = None;
+    if !listening(model) {
+        if let Some(stream) = model.stream.take() {
+            stop_tracks(&stream);
+        }
+    }
+    model.chunks.clear();
+    model.chunk_offsets.clear();
+    model.raw_blobs.clear();
+    model.pending_reads = 0;
+    model.failed_chunks = 0;
+    model.stopping = false;
+    model.stopped = false;
+    model.muted_since = None;
+    model.muted_regions.clear();
+    model.markers.clear();
+    model.pauses.clear();
+    model.elapsed_ms = 0.;
+    // A cancelled resume leaves `recovered` as it was.
+    model.interrupted = None;
+    model.resuming = false;
+    log_info!("Recording cancelled");
+    progress::emit("cancelled", &[]);
+}
+
+fn start_level_meter(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    let stream = model.stream.as_ref().expect("start level meter without stream");
+    match LevelMeter::new(stream, model.fft_size, channel_count(stream)) {
+        Ok(level_meter) => {
+            model.level_meter = Some(level_meter);
+            if model.accurate_meter {
+                request_worklet_meter(model, orders);
+            }
+            ensure_ticking(model, orders);
+            log_info!("Listening");
+        }
+        Err(error) => log_error!("Error creating level meter:", error),
+    }
+}
+
+// The mic stays open between the takes for these.
+fn listening(model: &Model) -> bool {
+    model.voice_activation || model.replay
+}
+
+fn start_replay_tap(model: &mut Model) {
+    if let Some(stream) = model.stream.as_ref() {
+        match ReplayTap::new(stream, channel_count(stream), model.replay_s) {
+            Ok(tap) => model.replay_tap = Some(tap),
+            Err(error) => log_error!("Error starting the instant replay:", error),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum StreamAfterTake {
+    Stop,
+    // Until `MicGraceExpired`.
+    Grace,
+    Keep,
+}
+
+// What happens to the stream once a take is done. `keep_stream` is for the setting,
+// `RecorderHandle.stop_keep_stream` and push-to-talk; a tab's capture is never kept.
+fn stream_after_take(keep_stream: bool, listening: bool, tab_audio: bool, mic_grace: bool) -> StreamAfterTake {
+    if !listening && (tab_audio || !(keep_stream || mic_grace)) {
+        StreamAfterTake::Stop
+    } else if !keep_stream && mic_grace {
+        StreamAfterTake::Grace
+    } else {
+        StreamAfterTake::Keep
+    }
+}
+
+// Stops a stream that's only kept open for the next take, so that the next take acquires
+// a new one - with the current device and video settings.
+fn release_kept_stream(model: &mut Model) {
+    if model.recorder.is_none() && !model.stopping && !listening(model) {
+        if let Some(stream) = model.stream.take() {
+            release_level_meter_if_unused(model);
+            stop_tracks(&stream);
+        }
+    }
+}
+
+fn request_worklet_meter(model: &Model, orders: &mut impl Orders<Msg>) {
+    if let Some(stream) = model.stream.clone() {
+        orders.perform_cmd(async move { Msg::WorkletMeterReady(WorkletMeter::new(&stream).await) });
+    }
+}
+
+fn start_keep_alive(model: &mut Model) {
+    match KeepAlive::start() {
+        Ok(keep_alive) => model.keep_alive = Some(keep_alive),
+        Err(error) => log_error!("Error starting background keep-alive:", error),
+    }
+}
+
+// The analyser runs for the voice activation, and for the spectrum and the meters while recording.
+fn release_level_meter_if_unused(model: &mut Model) {
+    let needed = model.voice_activation
+        || model.calibration.is_some()
+        || (visualizing(model) && model.recorder.is_some());
+    if !needed {
+        model.level_meter = None;
+        model.worklet_meter = None;
+        model.level = 0.;
+        model.rms = 0.;
+        model.channel_levels.clear();
+        model.spectrum.clear();
+    }
+}
+
+// Turns the collected chunks into a session list entry once the recorder has stopped
+// and the last chunk has been read.
+fn finalize_if_done(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if !model.stopped || model.pending_reads > 0 {
+        return;
+    }
+    model.stopped = false;
+    model.stopping = false;
+    if model.push_to_talk && model.talk_held {
+        orders.send_msg(Msg::StartRecording);
+    }
+    // The callbacks stay in `Model` until the next take replaces them - we may be running
+    // inside `on_stop_callback` right now and must not drop it from under itself.
+    if let Some(stream) = model.stream.as_ref() {
+        for track in stream.get_audio_tracks().iter() {
+            let track = track.unchecked_into::<MediaStreamTrack>();
+            track.set_onmute(None);
+            track.set_onunmute(None);
+        }
+    }
+    let wav = model.pcm_tap.take().map(|tap| wav::encode(&tap.finish()));
+    model.gain_stage = None;
+    model.mixer = None;
+    // The take is in the list (or the file) now and plays from there.
+    model.live_playback = None;
+    // All the chunks are read by now; what's still buffered goes out once (re)connected.
+    if let Some(live_stream) = model.live_stream.as_mut() {
+        live_stream.finish();
+    }
+    release_level_meter_if_unused(model);
+    model.keep_alive = None;
+    let keep_stream = model.keep_stream || model.keep_stream_for_next || model.push_to_talk;
+    match stream_after_take(keep_stream, listening(model), model.tab_audio, model.mic_grace_s > 0) {
+        StreamAfterTake::Stop => {
+            if let Some(stream) = model.stream.take() {
+                stop_tracks(&stream);
+            }
+        }
+        StreamAfterTake::Grace if model.stream.is_some() => {
+            model.mic_release_handle = Some(orders.perform_cmd_with_handle(cmds::timeout(
+                model.mic_grace_s * 1000,
+                || Msg::MicGraceExpired,
+            )));
+        }
+        StreamAfterTake::Grace | StreamAfterTake::Keep => {}
+    }
+
+    // A mute that is still going on when the recorder stops lasts until the end.
+    if let Some(start_ms) = model.muted_since.take() {
+        let end_ms = take_elapsed_ms(model);
+        model.muted_regions.push((start_ms, end_ms));
+    }
+
+    let interrupted = model.interrupted.take();
+    // The take is on disk already, there are no bytes to keep in the session list.
+    if let Some(sink) = model.file_sink.take() {
+        if let Some(reason) = interrupted {
+            log_error!("The take was interrupted, the file has what was recorded until then:", reason);
+        }
+        model.resuming = false;
+        model.chunks.clear();
+        model.markers.clear();
+        model.muted_regions.clear();
+        model.pauses.clear();
+        let name = sink.name.clone();
+        orders.perform_cmd(async move { Msg::FileSaved(name, sink.close().await) });
+        return;
+    }
+
+    let capture_mode = model.take_capture_mode;
+    let chunk_count = Some(model.chunks.len()).filter(|_| capture_mode != CaptureMode::Lossless);
+    let (bytes, mime, lossless) = match capture_mode {
+        CaptureMode::Lossless => (wav.unwrap_or_default(), "audio/wav".to_owned(), None),
+        _ => (concat_chunks(&mut model.chunks), model.take_mime_type.clone(), wav),
+    };
+    model.chunks.clear();
+    let metadata = RecordingMetadata {
+        capture_mode,
+        muted_regions: std::mem::take(&mut model.muted_regions),
+        markers: std::mem::take(&mut model.markers),
+        // A pause still going on at the stop isn't a gap in the recording, so it's not among them.
+        pauses: std::mem::take(&mut model.pauses),
+        replay_gain_db: None,
+        chunk_count,
+        failed_chunks: chunk_count.map(|_| model.failed_chunks),
+        duration_ms: Some(model.elapsed_ms),
+        quality: None,
+    };
+    let resumed = std::mem::take(&mut model.resuming);
+    let recovered = if resumed { model.recovered.take() } else { None };
+    if let Some(reason) = interrupted {
+        // Kept rather than lost; a take that doesn't resume the previous one replaces it.
+        let mut recovered = recovered.unwrap_or_else(|| RecoveredTake {
+            segments: Vec::new(),
+            capture_mode,
+            elapsed_ms: 0.,
+            markers: Vec::new(),
+            muted_regions: Vec::new(),
+            pauses: Vec::new(),
+            reason: String::new(),
+        });
+        recovered.append(bytes, mime, metadata, model.elapsed_ms);
+        recovered.reason = reason;
+        model.recovered = Some(recovered).filter(|recovered| !recovered.segments.is_empty());
+        return;
+    }
+    if let Some(mut recovered) = recovered {
+        recovered.append(bytes, mime, metadata, model.elapsed_ms);
+        save_recovered(model, orders, recovered);
+        return;
+    }
+    add_recording(model, orders, bytes, mime, lossless, metadata);
+}
+
+// The interrupted takes as one recording: a single one as it is, several joined through PCM into a WAV.
+fn save_recovered(model: &mut Model, orders: &mut impl Orders<Msg>, mut recovered: RecoveredTake) {
+    if recovered.segments.len() == 1 {
+        let (bytes, mime) = recovered.segments.remove(0);
+        let metadata = RecordingMetadata {
+            // Without the WAV of a `CaptureMode::Both` take.
+            capture_mode: if recovered.capture_mode == CaptureMode::Lossless { CaptureMode::Lossless } else { CaptureMode::Compressed },
+            muted_regions: recovered.muted_regions,
+            markers: recovered.markers,
+            pauses: recovered.pauses,
+            replay_gain_db: None,
+            chunk_count: None,
+            failed_chunks: None,
+            duration_ms: Some(recovered.elapsed_ms),
+            quality: None,
+        };
+        add_recording(model, orders, bytes, mime, None, metadata);
+        return;
+    }
+    orders.perform_cmd(async move {
+        let joined = decode_all(&recovered.segments).await.map(|parts| wav::encode(&pcm::concat(&parts)));
+        Msg::RecoveredJoined(recovered, joined)
+    });
+}
+
+async fn decode_all(segments: &[(Vec<u8>, String)]) -> Result<Vec<Pcm>, JsValue> {
+    let mut parts = Vec::new();
+    for (bytes, _) in segments {
+        parts.push(audio::decode(bytes).await?);
+    }
+    Ok(parts)
+}
+
+fn add_recording(
+    model: &mut Model,
+    orders: &mut impl Orders<Msg>,
+    bytes: Vec<u8>,
+    mime: String,
+    lossless: Option<Vec<u8>>,
+    metadata: RecordingMetadata,
+) {
+    if model.persistence == Persistence::Loading {
+        log_info!("Adding the take once the stored recordings are loaded");
+        model.early_takes.push(EarlyTake { bytes, mime, lossless, metadata });
+        return;
+    }
+    let hash = hash::fnv1a(&bytes);
+    if model.dedup {
+        if let Some(existing) = model.recordings.iter().find(|recording| recording.hash == hash) {
+            log_info!("Skipping duplicate recording");
+            model.dedup_notice = Some(format!("The last take was identical to recording {}, not added", existing.id + 1));
+            return;
+        }
+    }
+    model.dedup_notice = None;
+    model.recordings.push(Recording {
+        id: model.next_recording_id,
+        bytes,
+        mime,
+        lossless,
+        hash,
+        metadata,
+        description: String::new(),
+        tags: Vec::new(),
+        object_url: None,
+    });
+    model.next_recording_id += 1;
+
+    let recording = model.recordings.last_mut().unwrap();
+    #[allow(clippy::cast_precision_loss)]
+    progress::emit("finalized", &[
+        ("id", Value::Number(recording.id as f64)),
+        ("bytes", Value::Number(recording.bytes.len() as f64)),
+    ]);
+    if model.persistence == Persistence::On {
+        let stored = recording.to_stored();
+        orders.perform_cmd(async { Msg::Stored(storage::put(stored).await) });
+    }
+    if measures_quality(model.low_power, recording.metadata.duration_ms, recording.bytes.len()) {
+        let (id, bytes) = (recording.id, recording.bytes.clone());
+        orders.perform_cmd(async move {
+            let quality = audio::decode(&bytes).await.map(|pcm| quality::measure(&pcm.channels, pcm.sample_rate));
+            Msg::QualityMeasured(id, quality)
+        });
+    }
+    dispatch_complete_event(&model.complete_event, recording);
+}
+
+// Measuring decodes the whole take to PCM - about 23 MB a minute of 48 kHz stereo.
+const QUALITY_MAX_DURATION_MS: f64 = 10. * 60. * 1000.;
+// For takes of unknown duration; compressed audio of this size is about 10 minutes at 64 kbit/s.
+const QUALITY_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+fn measures_quality(low_power: bool, duration_ms: Option<f64>, bytes: usize) -> bool {
+    !low_power && duration_ms.map_or(bytes <= QUALITY_MAX_BYTES, |duration_ms| duration_ms <= QUALITY_MAX_DURATION_MS)
+}
+
+// Lets go of what refers to the removed recording `id`, or to any of them for `None` (a clear):
+// its object URL is revoked with it, so the player mustn't be left on it.
+fn release_removed(model: &mut Model, id: Option<usize>) {
+    let removed = |other: usize| id.map_or(true, |id| id == other);
+    if model.player_id.map_or(false, removed) {
+        if let Some(player) = model.player.get() {
+            if let Err(error) = player.remove_attribute("src") {
+                log_error!("Error unloading the player:", error);
+            }
+            player.load();
+        }
+        model.player_id = None;
+    }
+    if model.pcm.as_ref().map_or(false, |(pcm_id, _)| removed(*pcm_id)) {
+        model.pcm = None;
+    }
+    if model.chapters.as_ref().map_or(false, |(chapters_id, _)| removed(*chapters_id)) {
+        model.chapters = None;
+    }
+}
+
+fn add_early_takes(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    for take in std::mem::take(&mut model.early_takes) {
+        add_recording(model, orders, take.bytes, take.mime, take.lossless, take.metadata);
+    }
+}
+
+// Lets JS hosts that don't use Seed pick finished recordings up:
+// `document.addEventListener("recording-complete", e => e.detail.url)`.
+// The URL is the recording's cached one, so it's revoked once the recording is deleted.
+fn dispatch_complete_event(name: &str, recording: &mut Recording) {
+    let detail = js_sys::Object::new();
+    let set = |key: &str, value: &JsValue| js_sys::Reflect::set(&detail, &key.into(), value).unwrap();
+    #[allow(clippy::cast_precision_loss)]
+    set("bytes", &(recording.bytes.len() as f64).into());
+    set("mimeType", &recording.mime.as_str().into());
+    set("url", &recording.object_url().into());
+
+    let mut init = CustomEventInit::new();
+    init.detail(&detail);
+    match CustomEvent::new_with_event_init_dict(name, &init) {
+        Ok(event) => {
+            if let Err(error) = document().dispatch_event(&event) {
+                log_error!("Error dispatching event:", error);
+            }
+        }
+        Err(error) => log_error!("Error creating event:", error),
+    }
+}
+
+// Where a start is checked for being a repeat, e.g. of a double click.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum StartStage {
+    // `Msg::StartRecording`
+    Request,
+    // `accept_stream`
+    Stream,
+    // `start_recorder`
+    Recorder,
+}
+
+#[derive(Clone, Copy, Default)]
+struct StartState {
+    recorder: bool,
+    stream: bool,
+    // Stopping, setting up the processing or counting down.
+    busy: bool,
+}
+
+// Only one recorder, and one stream under it, at a time - a second one would double the chunks.
+fn is_duplicate_start(stage: StartStage, state: StartState) -> bool {
+    state.recorder
+        || match stage {
+            StartStage::Request => state.busy,
+            StartStage::Stream => state.stream,
+            StartStage::Recorder => false,
+        }
+}
+
+fn duplicate_start(model: &Model, stage: StartStage) -> bool {
+    let state = StartState {
+        recorder: model.recorder.is_some(),
+        stream: model.stream.is_some(),
+        busy: model.stopping || model.processing_pending || model.countdown.is_some(),
+    };
+    let duplicate = is_duplicate_start(stage, state);
+    if duplicate {
+        log_info!("Already recording, ignoring the duplicate start at", format!("{:?}", stage));
+    }
+    duplicate
+}
+
+fn request_audio_stream(model: &mut Model, orders: &mut impl Orders<Msg>) {
+    if model.stream_pending {
+        log_debug!("Waiting for the requested stream already");
+        return;
+    }
+    model.stream_pending = true;
+    #[cfg(feature = "test-input")]
+    if model.test_input {
+        match test_input::TestInput::new() {
+            Ok(input) => {
+                orders.send_msg(Msg::AudioStream(input.stream()));
+                model.test_input_source = Some(input);
+            }
+            Err(error) => {
+                orders.send_msg(Msg::StreamFailed(RecorderError::classify(&error)));
+            }
+        }
+        return;
+    }
+    if model.mix_inputs.len() >= 2 {
+        orders.perform_cmd(get_mix_stream(mix_constraints(model)));
+    } else {
+        orders.perform_cmd(get_audio_stream(stream_constraints(model)));
+    }
+}
+
+fn accept_stream(model: &mut Model, orders: &mut impl Orders<Msg>, stream: MediaStream) {
+    if duplicate_start(model, StartStage::Stream) {
+        stop_tracks(&stream);
+        return;
+    }
+    // See `stream_constraints`.
+    if let Some(group_id) = model.group_id.as_deref() {
+        if model.record_video && !devices::group_has_kind(&model.devices, group_id, MediaDeviceKind::Videoinput) {
+            note_fallback(&mut model.compatibility_notes, "The device group has no camera, the default one is used".to_owned());
+        }
+    }
+    model.capabilities = stream.get_audio_tracks().iter().next()
+        .and_then(|track| constraints::read(&track.unchecked_into()));
+    if model.prewarm == Prewarm::Acquiring {
+        set_audio_enabled(&stream, false);
+        model.stream = Some(stream);
+        model.stream_error = None;
+        model.prewarm = Prewarm::Ready;
+        model.prewarm_release_handle = Some(orders.perform_cmd_with_handle(cmds::timeout(
+            PREWARM_TIMEOUT_MS,
+            || Msg::PrewarmExpired,
+        )));
+        log_info!("Stream prewarmed");
+        return;
+    }
+    model.stream = Some(stream);
+    model.stream_error = None;
+    // A new stream (e.g. of another mic) starts the replay over.
+    if model.replay {
+        start_replay_tap(model);
+    }
+    if model.voice_activation || model.calibration.is_some() {
+        // Listening but not recording - the level decides when the recorder starts (or the gain).
+        start_level_meter(model, orders);
+    } else if std::mem::take(&mut model.replay_acquiring) {
+        log_info!("Listening for the replay");
+    } else {
+        start_take(model, orders);
+    }
+}
+
+// Each note is kept once, however often the fallback happens.
+fn note_fallback(notes: &mut Vec<String>, note: String) {
+    if !notes.contains(&note) {
+        log_info!("Compatibility:", note);
+        notes.push(note);
+    }
+}
+
+fn set_audio_enabled(stream: &MediaStream, enabled: bool) {
+    for track in stream.get_audio_tracks().iter() {
+        track.unchecked_into::<MediaStreamTrack>().set_enabled(enabled);
+    }
+}
+
+fn stop_tracks(stream: &MediaStream) {
+    for track in stream.get_tracks().iter() {
+        track.unchecked_into::<MediaStreamTrack>().stop();
+    }
+}
+
+// Channels of the stream's mic as reported by `getSettings`, which `web_sys` doesn't bind yet.
+fn channel_count(stream: &MediaStream) -> u32 {
+    stream.get_audio_tracks().iter().next()
+        .and_then(|track| {
+            let settings = js_sys::Reflect::get(&track, &"getSettings".into()).ok()?
+                .dyn_into::<js_sys::Function>().ok()?
+                .call0(&track).ok()?;
+            js_sys::Reflect::get(&settings, &"channelCount".into()).ok()?.as_f64()
+        })
+        .filter(|count| *count >= 1.)
+        .map_or(1, |count| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let count = count as u32;
+            count
+        })
+}
+
+// `web_sys` doesn't expose the bitrate getters yet.
+fn effective_bitrates(recorder: &MediaRecorder) -> (Option<f64>, Option<f64>) {
+    let get = |key: &str| js_sys::Reflect::get(recorder, &key.into()).ok().and_then(|value| value.as_f64());
+    (get("audioBitsPerSecond"), get("videoBitsPerSecond"))
+}
+
+// Throws for a MIME type or bitrate the browser rejects, or a stream without live tracks.
+fn create_recorder(stream: &MediaStream, mime_type: &str, audio_bits_per_second: u32) -> Result<MediaRecorder, JsValue> {
+    let mut options = MediaRecorderOptions::new();
+    options.audio_bits_per_second(audio_bits_per_second);
+    options.mime_type(mime_type);
+    MediaRecorder::new_with_media_stream_and_media_recorder_options(stream, &options)
+}
+
+fn view(model: &Model) -> Node<Msg> {
+    if model.compact {
+        return view_compact(model);
+    }
+    div![
+        model.error.as_ref().map(|error| div![
+            C!["error"],
+            error,
+            button!("Dismiss", ev(Ev::Click, |_| Msg::DismissError)),
+        ]),
+        model.stream_error.as_ref().map(|error| div![
+            C!["error"],
+            error.message(),
+            IF!(*error == RecorderError::DeviceBusy => button!("Retry", ev(Ev::Click, |_| Msg::RetryStream))),
+            button!("Dismiss", ev(Ev::Click, |_| Msg::DismissError)),
+        ]),
+        "Last chunk length: ",
+        model.last_chunk_size,
+        IF!(model.single_blob && model.recorder.is_some() => " (single blob - the data arrives at stop)"),
+        IF!(model.failed_chunks > 0 && (model.recorder.is_some() || model.stopping) => span![
+            C!["warning"],
+            format!(" {} chunk(s) couldn't be read and are missing", model.failed_chunks),
+        ]),
+        if model.recorder.is_some() {
+            vec![
+                button!("Stop", ev(Ev::Click, |_| Msg::StopRecording)),
+                button!("✕ Cancel", ev(Ev::Click, |_| Msg::CancelRecording)),
+            ]
+        } else if let Some(remaining) = model.countdown {
+            vec![
+                span![C!["countdown"], format!("Recording in {} ", remaining)],
+                button!("✕ Cancel", ev(Ev::Click, |_| Msg::CancelRecording)),
+            ]
+        } else {
+            let kept = model.stream.is_some() && (model.keep_stream || model.keep_stream_for_next);
+            vec![
+                button!(if kept { "Record again" } else { "Record" }, ev(Ev::Click, |_| Msg::StartRecording)),
+                if kept && !listening(model) {
+                    button!("Release mic", ev(Ev::Click, |_| Msg::ReleaseStream))
+                } else {
+                    empty![]
+                },
+                if tab_audio_supported() && !listening(model) {
+                    button!("Record Tab Audio", ev(Ev::Click, |_| Msg::RecordTabAudio))
+                } else {
+                    empty![]
+                },
+            ]
+        },
+        IF!(model.recorder.is_some() => vec![
+            if model.paused_at.is_some() {
+                button!("Resume", ev(Ev::Click, |_| Msg::ResumeRecording))
+            } else {
+                button!("Pause", ev(Ev::Click, |_| Msg::PauseRecording))
+            },
+            span![format!(" {} ", format_duration(model.elapsed_ms))],
+            view_duration_progress(model),
+            view_markers_input(model),
+        ]),
+        view_recovered(model),
+        view_storage_estimate(model),
+        button![
+            "Test speakers",
+            attrs! {At::Disabled => model.test_tone.is_some().as_at_value()},
+            ev(Ev::Click, |_| Msg::PlayTestTone),
+        ],
+        label![
+            "Max duration (s) ",
+            input![
+                attrs! {
+                    At::Type => "number",
+                    At::Min => 0,
+                    At::Value => model.max_duration_ms.map_or_else(String::new, |ms| (ms / 1000.).to_string()),
+                },
+                input_ev(Ev::Change, Msg::SetMaxDuration),
+            ],
+            view_hint(model.hints.max_duration.as_deref()),
+        ],
+        view_countdown(model),
+        view_bitrates(model),
+        view_devices(model),
+        view_format(model),
+        view_voice_activation(model),
+        view_replay(model),
+        view_push_to_talk(model),
+        view_calibration(model),
+        IF!(!model.low_power => vec![view_meters(model), view_spectrum(model)]),
+        view_processing(model),
+        view_upload(model),
+        view_live_stream(model),
+        view_export_settings(model),
+        view_save_to_file(model),
+        view_keep_alive(model),
+        view_chunk_strategy(model),
+        view_capture_mode(model),
+        view_live_review(model),
+        view_recordings(model),
+        view_compatibility_notes(&model.compatibility_notes),
+        view_log_level(model),
+        view_config(model),
+    ]
+}
+
+fn view_config(model: &Model) -> Node<Msg> {
+    div![
+        "Settings (bitrate, timeslice, format, constraints, pipeline, file names): ",
+        button!("Export", ev(Ev::Click, |_| Msg::ExportConfig)),
+        label![
+            " Import ",
+            input![
+                attrs! {
+                    At::Type => "file",
+                    At::Accept => "application/json,.json",
+                    At::Disabled => (model.recorder.is_some() || model.stopping).as_at_value(),
+                },
+                ev(Ev::Change, |event| {
+                    let input = event.target()?.dyn_into::<HtmlInputElement>().ok()?;
+                    let file = input.files()?.get(0)?;
+                    // So that choosing the same file again imports it again.
+                    input.set_value("");
+                    Some(Msg::LoadConfigFile(file))
+                }),
+            ],
+        ],
+        model.config_status.as_ref().map(|status| span![" ", status]),
+    ]
+}
+
+fn view_compatibility_notes(notes: &[String]) -> Node<Msg> {
+    if notes.is_empty() {
+        return empty![];
+    }
+    div![
+        "Compatibility notes",
+        ul![notes.iter().map(|note| li![note])],
+    ]
+}
+
+fn view_log_level(model: &Model) -> Node<Msg> {
+    label![
+        "Log level ",
+        select![
+            LogLevel::ALL.iter().map(|level| option![
+                attrs! {
+                    At::Value => level.as_str(),
+                    At::Selected => (*level == model.log_level).as_at_value(),
+                },
+                level.as_str(),
+            ]),
+            input_ev(Ev::Change, Msg::SetLogLevel),
+        ],
+    ]
+}
+
+fn view_hint(hint: Option<&str>) -> Node<Msg> {
+    hint.map_or_else(|| empty![], |hint| span![style! {St::Color => "darkorange"}, format!(" {}", hint)])
+}
+
+fn view_bitrates(model: &Model) -> Node<Msg> {
+    let kbps = |bits: f64| format!("{:.0} kbps", bits / 1000.);
+    let (audio, video) = model.effective_bitrates.unwrap_or_default();
+    let disabled = model.recorder.is_some().as_at_value();
+    div![
+        label![
+            "Audio bitrate (kbps) ",
+            input![
+                attrs! {
+                    At::Type => "number",
+                    At::Min => limits::MIN_BITS_PER_SECOND / 1000,
+                    At::Max => limits::MAX_BITS_PER_SECOND / 1000,
+                    At::Value => model.audio_bits_per_second / 1000,
+                    At::Disabled => disabled,
+                },
+                input_ev(Ev::Change, Msg::SetAudioBitrate),
+            ],
+            view_hint(model.hints.bitrate.as_deref()),
+        ],
+        label![
+            " Timeslice (ms) ",
+            input![
+                attrs! {
+                    At::Type => "number",
+                    At::Min => limits::MIN_TIMESLICE_MS,
+                    At::Max => limits::MAX_TIMESLICE_MS,
+                    At::Value => model.timeslice_ms,
+                    At::Disabled => disabled,
+                },
+                input_ev(Ev::Change, Msg::SetTimeslice),
+            ],
+            view_hint(model.hints.timeslice.as_deref()),
+        ],
+        br![],
+        format!("Audio bitrate: requested {}", kbps(f64::from(model.audio_bits_per_second))),
+        audio.map(|bits| format!(", actual {}", kbps(bits))),
+        IF!(model.record_video => video.map(|bits| format!(" | Video bitrate: actual {}", kbps(bits)))),
+    ]
+}
+
+fn view_devices(model: &Model) -> Node<Msg> {
+    let kind_name = |kind| match kind {
+        MediaDeviceKind::Audioinput => "mic",
+        MediaDeviceKind::Videoinput => "camera",
+        _ => "other",
+    };
+    let groups = devices::groups(&model.devices);
+    let selected = model.group_id.as_deref().unwrap_or_default();
+    div![
+        label![
+            "Device group ",
+            select![
+                attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+                option![attrs! {At::Value => ""}, "Default devices"],
+                groups.iter().enumerate().map(|(index, (group_id, devices))| option![
+                    attrs! {
+                        At::Value => group_id,
+                        At::Selected => (*group_id == selected).as_at_value(),
+                    },
+                    format!(
+                        "Group {}: {}",
+                        index + 1,
+                        devices.iter()
+                            .map(|device| format!("{} ({})", device.label, kind_name(device.kind)))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                ]),
+                input_ev(Ev::Change, Msg::SetDeviceGroup),
+            ],
+        ],
+        model.group_id.as_deref().map(|group_id| {
+            let has_mic = devices::group_has_kind(&model.devices, group_id, MediaDeviceKind::Audioinput);
+            let has_camera = devices::group_has_kind(&model.devices, group_id, MediaDeviceKind::Videoinput);
+            match (has_mic, has_camera) {
+                (true, false) if model.record_video => span![" (no camera in this group - the default one is used)"],
+                (false, _) if model.default_mic.is_none() => span![" (no mic in this group - the default one is used)"],
+                _ => empty![],
+            }
+        }),
+        view_default_mic(model),
+        view_mix(model),
+        view_audio_constraints(model),
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.record_video.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleRecordVideo),
+            ],
+            "Record video",
+        ],
+        IF!(model.record_video => button![
+            attrs! {
+                At::Disabled => (model.recorder.is_some() || devices::camera_count(&model.devices) < 2).as_at_value(),
+            },
+            ev(Ev::Click, |_| Msg::FlipCamera),
+            match model.facing_mode {
+                FacingMode::User => "Switch to the rear camera",
+                FacingMode::Environment => "Switch to the front camera",
+            },
+        ]),
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.keep_stream.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleKeepStream),
+            ],
+            "Keep the mic open between takes (faster restart, the mic indicator stays on)",
+        ],
+        IF!(!model.keep_stream => label![
+            " otherwise release it ",
+            select![
+                MIC_GRACE_OPTIONS_S.iter().map(|seconds| option![
+                    attrs! {
+                        At::Value => seconds,
+                        At::Selected => (*seconds == model.mic_grace_s).as_at_value(),
+                    },
+                    if *seconds == 0 { "right away".to_owned() } else { format!("after {} s idle", seconds) },
+                ]),
+                input_ev(Ev::Change, Msg::SetMicGrace),
+            ],
+        ]),
+        IF!(model.stream.is_some() => span![C!["mic-active"], " ● mic active"]),
+    ]
+}
+
+// Only where the browser lists the default devices; the labels name the mic they currently stand for.
+fn view_default_mic(model: &Model) -> Node<Msg> {
+    let roles = DefaultRole::ALL.iter()
+        .filter_map(|role| devices::default_mic(&model.devices, *role).map(|device| (*role, device)))
+        .collect::<Vec<_>>();
+    if roles.is_empty() {
+        return empty![];
+    }
+    label![
+        " Mic ",
+        select![
+            attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+            option![attrs! {At::Value => ""}, "From the device group"],
+            roles.iter().map(|(role, device)| option![
+                attrs! {
+                    At::Value => role.as_str(),
+                    At::Selected => (model.default_mic == Some(*role)).as_at_value(),
+                },
+                match role {
+                    DefaultRole::Default => format!("Default device: {}", device.label),
+                    DefaultRole::Communications => format!("Default communications device: {}", device.label),
+                },
+            ]),
+            input_ev(Ev::Change, Msg::SetDefaultMic),
+        ],
+    ]
+}
+
+// Only the options the current mic reports it supports, see `constraints::read`.
+fn view_audio_constraints(model: &Model) -> Node<Msg> {
+    let capabilities = model.capabilities.as_ref();
+    let disabled = model.recorder.is_some();
+    let select_number = |label: &str, values: Vec<u32>, selected: Option<u32>, unit: &str, on_change: fn(String) -> Msg| label![
+        format!(" {} ", label),
+        select![
+            attrs! {At::Disabled => disabled.as_at_value()},
+            option![attrs! {At::Value => ""}, "Default"],
+            values.iter().map(|value| option![
+                attrs! {At::Value => value, At::Selected => (Some(*value) == selected).as_at_value()},
+                format!("{}{}", value, unit),
+            ]),
+            input_ev(Ev::Change, on_change),
+        ],
+    ];
+    div![
+        "Mic",
+        select_number(
+            "sample rate",
+            constraints::sample_rates(capabilities),
+            model.audio_constraints.sample_rate,
+            " Hz",
+            Msg::SetCaptureSampleRate,
+        ),
+        select_number(
+            "channels",
+            constraints::channel_counts(capabilities),
+            model.audio_constraints.channel_count,
+            "",
+            Msg::SetCaptureChannelCount,
+        ),
+        Feature::ALL.iter().map(|feature| {
+            let feature = *feature;
+            let selected = model.audio_constraints.feature(feature);
+            label![
+                format!(" {} ", feature.label()),
+                select![
+                    attrs! {At::Disabled => disabled.as_at_value()},
+                    option![attrs! {At::Value => ""}, "Default"],
+                    constraints::feature_values(capabilities, feature).iter().map(|value| option![
+                        attrs! {At::Value => value, At::Selected => (Some(*value) == selected).as_at_value()},
+                        if *value { "On" } else { "Off" },
+                    ]),
+                    input_ev(Ev::Change, move |value| Msg::SetFeature(feature, value)),
+                ],
+            ]
+        }),
+    ]
+}
+
+fn view_mix(model: &Model) -> Node<Msg> {
+    let mics = model.devices.iter()
+        .filter(|device| device.kind == MediaDeviceKind::Audioinput && device.default_role().is_none())
+        .collect::<Vec<_>>();
+    if mics.len() < 2 {
+        return empty![];
+    }
+    div![
+        "Mix mics ",
+        mics.iter().map(|mic| {
+            let device_id = mic.id.clone();
+            let selected = model.mix_inputs.iter().position(|input| input.device_id == mic.id);
+            label![
+                input![
+                    attrs! {
+                        At::Type => "checkbox",
+                        At::Checked => selected.is_some().as_at_value(),
+                        At::Disabled => model.recorder.is_some().as_at_value(),
+                    },
+                    ev(Ev::Change, move |_| Msg::ToggleMixMic(device_id)),
+                ],
+                &mic.label,
+                selected.map(|index| {
+                    let input = &model.mix_inputs[index];
+                    span![
+                        " gain ",
+                        input![
+                            attrs! {At::Type => "range", At::Min => 0, At::Max => 2, At::Step => 0.05, At::Value => input.gain},
+                            input_ev(Ev::Input, move |gain| Msg::SetMixGain(index, gain)),
+                        ],
+                        format!("{:.2}", input.gain),
+                        IF!(input.ended => " (disconnected)"),
+                    ]
+                }),
+                " ",
+            ]
+        }),
+        IF!(model.mix_inputs.len() == 1 => span!["(pick another one to mix)"]),
+    ]
+}
+
+// Combinations the browser can't record are disabled based on the other selection.
+fn view_format(model: &Model) -> Node<Msg> {
+    let supported = &model.supported_formats;
+    div![
+        label![
+            "Container ",
+            select![
+                Container::ALL.iter().map(|container| option![
+                    attrs! {
+                        At::Value => container.as_str(),
+                        At::Selected => (*container == model.container).as_at_value(),
+                        At::Disabled => (!supported.iter().any(|(c, _)| c == container)).as_at_value(),
+                    },
+                    container.as_str(),
+                ]),
+                input_ev(Ev::Change, Msg::SetContainer),
+            ],
+        ],
+        label![
+            " Codec ",
+            select![
+                Codec::ALL.iter().map(|codec| option![
+                    attrs! {
+                        At::Value => codec.as_str(),
+                        At::Selected => (*codec == model.codec).as_at_value(),
+                        At::Disabled => (!supported.contains(&(model.container, *codec))).as_at_value(),
+                    },
+                    codec.as_str(),
+                ]),
+                input_ev(Ev::Change, Msg::SetCodec),
+            ],
+        ],
+        label![
+            " MIME type ",
+            select![
+                attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+                // A composed type that isn't among the candidates still shows as selected.
+                IF!(model.mime_candidates.iter().all(|(candidate, _)| *candidate != model.mime_type) =>
+                    option![attrs! {At::Value => model.mime_type, At::Selected => true.as_at_value()}, &model.mime_type]),
+                model.mime_candidates.iter().map(|(candidate, supported)| option![
+                    attrs! {
+                        At::Value => candidate,
+                        At::Selected => (*candidate == model.mime_type).as_at_value(),
+                        At::Disabled => (!supported).as_at_value(),
+                    },
+                    format!("{} {}", candidate, if *supported { "(supported)" } else { "(unsupported)" }),
+                ]),
+                input_ev(Ev::Change, Msg::SetMimeType),
+            ],
+        ],
+        IF!(!MediaRecorder::is_type_supported(&model.mime_type) => " (not supported by this browser)"),
+    ]
+}
+
+// A single button cycling record -> stop, for floating record buttons and similar embeds.
+fn view_compact(model: &Model) -> Node<Msg> {
+    let recording = model.recorder.is_some();
+    let counting_down = model.countdown.is_some();
+    button![
+        style! {
+            St::Color => if recording { "red" } else { "inherit" },
+        },
+        attrs! {
+            At::Title => if recording { "Stop recording" } else { "Start recording" },
+            At::Disabled => model.stopping.as_at_value(),
+        },
+        match model.countdown {
+            Some(remaining) => remaining.to_string(),
+            None if recording => "■".to_owned(),
+            None => "●".to_owned(),
+        },
+        ev(Ev::Click, move |_| match (recording, counting_down) {
+            (true, _) => Msg::StopRecording,
+            (false, true) => Msg::CancelRecording,
+            (false, false) => Msg::StartRecording,
+        }),
+    ]
+}
+
+// Without a max duration there is nothing to fill up - the elapsed time next to it says it all.
+fn view_duration_progress(model: &Model) -> Node<Msg> {
+    let max_ms = match model.max_duration_ms {
+        Some(max_ms) => max_ms,
+        None => return empty![],
+    };
+    // The last 10 % of the allotted time are highlighted.
+    let warning = model.elapsed_ms >= max_ms * 0.9;
+    progress![
+        C![IF!(warning => "warning")],
+        style! {St::from("accent-color") => if warning { "orange" } else { "green" }},
+        attrs! {At::Max => max_ms, At::Value => model.elapsed_ms.min(max_ms)},
+    ]
+}
+
+// Below this the estimate is shown as a warning.
+const QUOTA_WARNING_MINUTES: f64 = 2.;
+
+fn view_storage_estimate(model: &Model) -> Node<Msg> {
+    let remaining_bytes = match model.storage_remaining {
+        Some(remaining_bytes) if model.recorder.is_some() || model.stopping => remaining_bytes - model.take_bytes,
+        Some(remaining_bytes) => remaining_bytes,
+        None => return empty![],
+    };
+    // What the browser went with for the latest take, otherwise the requested audio bitrate.
+    let bits_per_second = match model.effective_bitrates {
+        Some((audio, video)) => audio.unwrap_or_else(|| f64::from(model.audio_bits_per_second)) + video.unwrap_or(0.),
+        None => f64::from(model.audio_bits_per_second),
+    };
+    match storage::minutes_remaining(remaining_bytes, bits_per_second) {
+        Some(minutes) => {
+            let warning = minutes < QUOTA_WARNING_MINUTES;
+            div![
+                C![IF!(warning => "warning")],
+                format!("About {:.0} min of recording left in storage", minutes.floor()),
+                IF!(warning => " - delete or download some recordings"),
+            ]
+        }
+        None => empty![],
+    }
+}
+
+fn view_markers_input(model: &Model) -> Node<Msg> {
+    let label = model.marker_label.trim().to_owned();
+    div![
+        input![
+            attrs! {At::Placeholder => "Marker label", At::Value => model.marker_label},
+            input_ev(Ev::Input, Msg::SetMarkerLabel),
+        ],
+        button![
+            "Add marker",
+            ev(Ev::Click, move |_| Msg::AddMarker(if label.is_empty() { None } else { Some(label) })),
+        ],
+        view_markers(&model.markers),
+    ]
+}
+
+fn view_markers(markers: &[Marker]) -> Node<Msg> {
+    if markers.is_empty() {
+        return empty![];
+    }
+    ul![markers.iter().map(|marker| li![
+        format_duration(marker.elapsed_ms),
+        marker.label.as_ref().map(|label| format!(" {}", label)),
+    ])]
+}
+
+// `mm:ss.s`
+fn format_duration(ms: f64) -> String {
+    let seconds = ms.max(0.) / 1000.;
+    format!("{:02}:{:04.1}", (seconds / 60.).floor(), seconds % 60.)
+}
+
+fn view_chunk_strategy(model: &Model) -> Node<Msg> {
+    label![
+        "Chunk reading ",
+        select![
+            attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+            ChunkStrategy::ALL.iter().map(|strategy| option![
+                attrs! {
+                    At::Value => strategy.as_str(),
+                    At::Selected => (*strategy == model.chunk_strategy).as_at_value(),
+                },
+                strategy.as_str(),
+            ]),
+            input_ev(Ev::Change, Msg::SetChunkStrategy),
+        ],
+    ]
+}
+
+fn view_capture_mode(model: &Model) -> Node<Msg> {
+    label![
+        "Next take captures ",
+        select![
+            attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+            CaptureMode::ALL.iter().map(|mode| option![
+                attrs! {
+                    At::Value => mode.as_str(),
+                    At::Selected => (*mode == model.capture_mode).as_at_value(),
+                },
+                mode.as_str(),
+            ]),
+            input_ev(Ev::Change, Msg::SetCaptureMode),
+        ],
+    ]
+}
+
+// Without Media Source Extensions (or for a codec they can't play) takes are played once stopped.
+fn view_live_review(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.live_review.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleLiveReview),
+            ],
+            "Review the take while recording",
+        ],
+        model.live_playback.as_ref().map(|live_playback| {
+            let attrs = attrs! {At::Src => live_playback.url, At::Controls => AtValue::None};
+            if model.record_video && !model.tab_audio {
+                video![attrs]
+            } else {
+                audio![attrs]
+            }
+        }),
+    ]
+}
+
+fn view_export_settings(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            "File names ",
+            input![
+                attrs! {At::Value => model.file_name_template, At::Placeholder => file_name::DEFAULT_TEMPLATE},
+                input_ev(Ev::Input, Msg::SetFileNameTemplate),
+            ],
+            " ({n} is the number, {date} today) ",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.export_settings.mono.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleExportMono),
+            ],
+            "Export as mono",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.export_settings.embed_replay_gain.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleEmbedReplayGain),
+            ],
+            "Embed ReplayGain comment",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.export_settings.remove_dc.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleRemoveDc),
+            ],
+            "Remove DC offset",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.export_settings.inject_pauses.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleInjectPauses),
+            ],
+            "Insert pauses as silence",
+        ],
+        label![
+            " Discard last (s) ",
+            input![
+                attrs! {At::Type => "number", At::Min => 0, At::Step => 0.5, At::Value => model.export_settings.trim_end_s},
+                input_ev(Ev::Change, |value| value.parse().ok().map(Msg::SetTrimEndSecs)),
+            ],
+        ],
+        label![
+            " Fade in (ms) ",
+            input![
+                attrs! {At::Type => "number", At::Min => 0, At::Step => 10, At::Value => model.export_settings.fade_in_ms},
+                input_ev(Ev::Change, Msg::SetFadeIn),
+            ],
+        ],
+        label![
+            " Fade out (ms) ",
+            input![
+                attrs! {At::Type => "number", At::Min => 0, At::Step => 10, At::Value => model.export_settings.fade_out_ms},
+                input_ev(Ev::Change, Msg::SetFadeOut),
+            ],
+        ],
+        select![
+            input_ev(Ev::Change, Msg::SetFadeCurve),
+            FadeCurve::ALL.iter().map(|curve| option![
+                attrs! {
+                    At::Value => curve.as_str(),
+                    At::Selected => (*curve == model.export_settings.fade_curve).as_at_value(),
+                },
+                curve.as_str(),
+            ]),
+        ],
+        label![
+            " Sample rate ",
+            select![
+                input_ev(Ev::Change, Msg::SetExportSampleRate),
+                option![
+                    attrs! {At::Value => "", At::Selected => model.export_settings.sample_rate.is_none().as_at_value()},
+                    "Original",
+                ],
+                EXPORT_SAMPLE_RATES.iter().map(|rate| option![
+                    attrs! {
+                        At::Value => rate,
+                        At::Selected => (model.export_settings.sample_rate == Some(*rate)).as_at_value(),
+                    },
+                    format!("{} Hz", rate),
+                ]),
+            ],
+        ],
+        label![
+            " Bit depth ",
+            select![
+                input_ev(Ev::Change, Msg::SetBitDepth),
+                BitDepth::ALL.iter().map(|bit_depth| option![
+                    attrs! {
+                        At::Value => bit_depth.as_str(),
+                        At::Selected => (*bit_depth == model.export_settings.bit_depth).as_at_value(),
+                    },
+                    bit_depth.as_str(),
+                ]),
+            ],
+        ],
+    ]
+}
+
+fn view_countdown(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            "Countdown ",
+            select![
+                COUNTDOWN_OPTIONS_S.iter().map(|seconds| option![
+                    attrs! {
+                        At::Value => seconds,
+                        At::Selected => (*seconds == model.countdown_s).as_at_value(),
+                    },
+                    if *seconds == 0 { "Off".to_owned() } else { format!("{} s", seconds) },
+                ]),
+                input_ev(Ev::Change, Msg::SetCountdown),
+            ],
+        ],
+        IF!(model.countdown_s > 0 => label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.countdown_beeps.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleCountdownBeeps),
+            ],
+            "Beep on each second",
+        ]),
+    ]
+}
+
+fn view_push_to_talk(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.push_to_talk.as_at_value()},
+                ev(Ev::Change, |_| Msg::TogglePushToTalk),
+            ],
+            "Push to talk",
+        ],
+        IF!(model.push_to_talk => button![
+            C![IF!(model.talk_held => "held")],
+            style! {St::Background => if model.talk_held { "red" } else { "" }},
+            ev(Ev::MouseDown, |_| Msg::TalkPressed),
+            ev(Ev::MouseUp, |_| Msg::TalkReleased),
+            ev(Ev::MouseLeave, |_| Msg::TalkReleased),
+            // Without `preventDefault` the touch is followed by emulated mouse events
+            // and a long press opens the context menu.
+            ev(Ev::TouchStart, |event| {
+                event.prevent_default();
+                Msg::TalkPressed
+            }),
+            ev(Ev::TouchEnd, |_| Msg::TalkReleased),
+            ev(Ev::TouchCancel, |_| Msg::TalkReleased),
+            if model.talk_held { "Recording - release to stop" } else { "Hold to talk" },
+        ]),
+    ]
+}
+
+fn view_keep_alive(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.keep_alive_in_background.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleKeepAlive),
+            ],
+            "Keep recording accurately in a background tab (uses a bit more power)",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.low_power.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleLowPower),
+            ],
+            "Low power (no meters, spectrum or waveform; the timer updates once a second)",
+        ],
+    ]
+}
+
+// Without the File System Access API takes stay in memory and are downloaded from the list.
+fn view_save_to_file(model: &Model) -> Node<Msg> {
+    if !file_sink::is_supported() {
+        return empty![];
+    }
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.save_to_file.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleSaveToFile),
+            ],
+            "Save takes directly to a file",
+        ],
+        model.file_sink.as_ref().map(|sink| span![format!(" Writing {}", sink.name)]),
+        model.file_status.as_ref().map(|status| span![format!(" {}", status)]),
+    ]
+}
+
+fn view_processing(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            "Processing worklet URL ",
+            input![
+                attrs! {At::Value => model.worklet_url, At::Placeholder => "none"},
+                input_ev(Ev::Change, Msg::SetWorkletUrl),
+            ],
+        ],
+        IF!(model.processing_failed => span![" Couldn't be loaded, recording the unprocessed mic"]),
+    ]
+}
+
+fn view_live_stream(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            "Stream to (WebSocket) ",
+            input![
+                attrs! {
+                    At::Type => "url",
+                    At::Placeholder => "wss://...",
+                    At::Value => model.stream_url,
+                    At::Disabled => model.recorder.is_some().as_at_value(),
+                },
+                input_ev(Ev::Input, Msg::SetStreamUrl),
+            ],
+        ],
+        label![
+            " When the buffer is full ",
+            select![
+                attrs! {At::Disabled => model.recorder.is_some().as_at_value()},
+                Overflow::ALL.iter().map(|overflow| option![
+                    attrs! {
+                        At::Value => overflow.as_str(),
+                        At::Selected => (*overflow == model.stream_overflow).as_at_value(),
+                    },
+                    overflow.as_str(),
+                ]),
+                input_ev(Ev::Change, Msg::SetStreamOverflow),
+            ],
+        ],
+        model.live_stream.as_ref().map(|live_stream| {
+            let state = match &live_stream.connection {
+                Connection::Connecting => "Connecting...".to_owned(),
+                Connection::Open => "Streaming".to_owned(),
+                Connection::Reconnecting(attempt) => format!(
+                    "Disconnected, reconnecting (attempt {} of {})...",
+                    attempt,
+                    live_stream::MAX_RECONNECT_ATTEMPTS,
+                ),
+                Connection::Done => "All sent".to_owned(),
+                Connection::Failed(reason) => format!("Stopped: {}", reason),
+            };
+            let buffer = &live_stream.buffer;
+            span![
+                format!(" {}", state),
+                IF!(!buffer.is_empty() => span![format!(" ({} KB buffered)", buffer.queued_bytes() / 1024)]),
+                IF!(buffer.dropped > 0 => span![format!(" ({} chunks dropped)", buffer.dropped)]),
+                view_stream_backlog(model, live_stream),
+            ]
+        }),
+    ]
+}
+
+// Above this much audio waiting to go out the network isn't keeping up.
+const BACKLOG_WARNING_S: f64 = 5.;
+
+// What's captured but not sent yet, up to the size of the buffer - it grows when the network falls behind.
+fn view_stream_backlog(model: &Model, live_stream: &LiveStream) -> Node<Msg> {
+    if live_stream.connection == Connection::Done {
+        return empty![];
+    }
+    let backlog_bytes = live_stream.backlog_bytes();
+    let bits_per_second = model.effective_bitrates
+        .and_then(|(audio, _)| audio)
+        .unwrap_or_else(|| f64::from(model.audio_bits_per_second));
+    #[allow(clippy::cast_precision_loss)]
+    let backlog_s = backlog_bytes as f64 * 8. / bits_per_second.max(1.);
+    let warning = backlog_s > BACKLOG_WARNING_S;
+    span![
+        " Backlog ",
+        progress![
+            C![IF!(warning => "warning")],
+            style! {St::from("accent-color") => if warning { "orange" } else { "green" }},
+            attrs! {At::Max => live_stream::BUFFER_BYTES, At::Value => backlog_bytes.min(live_stream::BUFFER_BYTES)},
+        ],
+        format!(" {:.1} s", backlog_s),
+        IF!(warning => span![C!["warning"], " - the network isn't keeping up"]),
+    ]
+}
+
+fn view_upload(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            "Upload with ",
+            select![
+                UploadMethod::ALL.iter().map(|method| option![
+                    attrs! {
+                        At::Value => method.as_str(),
+                        At::Selected => (*method == model.upload_method).as_at_value(),
+                    },
+                    match method {
+                        UploadMethod::Post => "POST in parts",
+                        UploadMethod::Put => "PUT to a presigned URL",
+                    },
+                ]),
+                input_ev(Ev::Change, Msg::SetUploadMethod),
+            ],
+        ],
+        match model.upload_method {
+            UploadMethod::Post => label![
+                " Upload endpoint ",
+                input![
+                    attrs! {At::Value => model.upload_endpoint},
+                    input_ev(Ev::Input, Msg::SetUploadEndpoint),
+                ],
+            ],
+            UploadMethod::Put => label![
+                " Presigned URL ",
+                input![
+                    attrs! {At::Value => model.presigned_url, At::Type => "url"},
+                    input_ev(Ev::Input, Msg::SetPresignedUrl),
+                ],
+            ],
+        },
+        IF!(model.put_pending => span![" Uploading..."]),
+        model.upload.as_ref().map(|upload| span![
+            format!(" Uploaded {} of {} parts", upload.done_count(), upload.part_count()),
+            IF!(upload.has_failed() => button!("Retry", ev(Ev::Click, |_| Msg::RetryUpload))),
+        ]),
+        model.upload_status.as_ref().map(|status| span![format!(" {}", status)]),
+    ]
+}
+
+fn view_meters(model: &Model) -> Node<Msg> {
+    let names: &[&str] = if model.channel_levels.len() == 2 { &["L", "R"] } else { &[""] };
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.show_meters.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleMeters),
+            ],
+            "Meters while recording",
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.accurate_meter.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleAccurateMeter),
+            ],
+            "Sample-accurate peak (AudioWorklet)",
+        ],
+        IF!(model.worklet_meter.is_some() => div![format!("Peak {:.2}, RMS {:.2}", model.level, model.rms)]),
+        model.channel_levels.iter().zip(names).map(|(level, name)| div![
+            style! {St::Display => "flex", St::AlignItems => "center"},
+            span![style! {St::Width => px(16)}, name],
+            div![
+                style! {St::Width => px(200), St::Height => px(8), St::BackgroundColor => "#ddd"},
+                div![style! {
+                    St::Width => format!("{}%", level.min(1.) * 100.),
+                    St::Height => "100%",
+                    St::BackgroundColor => if *level >= 1. { "crimson" } else { "seagreen" },
+                }],
+            ],
+        ]),
+    ]
+}
+
+fn view_spectrum(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.show_spectrum.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleSpectrum),
+            ],
+            "Spectrum",
+        ],
+        IF!(model.show_spectrum => vec![
+            label![
+                " FFT size ",
+                select![
+                    FFT_SIZES.iter().map(|size| option![
+                        attrs! {At::Value => size, At::Selected => (*size == model.fft_size).as_at_value()},
+                        size.to_string(),
+                    ]),
+                    input_ev(Ev::Change, Msg::SetFftSize),
+                ],
+            ],
+            label![
+                " Bands ",
+                input![
+                    attrs! {At::Type => "number", At::Min => 1, At::Value => model.spectrum_bands},
+                    input_ev(Ev::Input, Msg::SetSpectrumBands),
+                ],
+            ],
+            div![
+                style! {St::Display => "flex", St::AlignItems => "flex-end", St::Height => px(100)},
+                model.spectrum.iter().map(|magnitude| div![style! {
+                    St::Flex => "1",
+                    St::MarginRight => px(1),
+                    St::BackgroundColor => "steelblue",
+                    St::Height => format!("{}%", f32::from(*magnitude) / 2.55),
+                }]),
+            ],
+        ]),
+    ]
+}
+
+fn view_replay(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.replay.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleReplay),
+            ],
+            "Instant replay",
+        ],
+        label![
+            " Keep the last ",
+            select![
+                REPLAY_OPTIONS_S.iter().map(|seconds| option![
+                    attrs! {At::Value => seconds, At::Selected => (*seconds == model.replay_s).as_at_value()},
+                    format!("{} s", seconds),
+                ]),
+                input_ev(Ev::Change, Msg::SetReplayLength),
+            ],
+        ],
+        model.replay_tap.as_ref().map(|tap| span![
+            button!("Save Replay", ev(Ev::Click, |_| Msg::SaveReplay)),
+            format!(" {:.0} s buffered", tap.buffered_s().min(f64::from(model.replay_s))),
+        ]),
+    ]
+}
+
+fn view_voice_activation(model: &Model) -> Node<Msg> {
+    let detector = &model.voice_detector;
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.voice_activation.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleVoiceActivation),
+            ],
+            "Voice activation",
+        ],
+        IF!(model.voice_activation => div![
+            format!("Level: {:.2} ", model.level),
+            IF!(detector.is_active() => "(voice) "),
+            label![
+                "Start threshold ",
+                input![
+                    attrs! {At::Type => "number", At::Step => "0.01", At::Value => detector.start_threshold},
+                    input_ev(Ev::Input, Msg::SetVoiceStartThreshold),
+                ],
+            ],
+            label![
+                "Stop threshold ",
+                input![
+                    attrs! {At::Type => "number", At::Step => "0.01", At::Value => detector.stop_threshold},
+                    input_ev(Ev::Input, Msg::SetVoiceStopThreshold),
+                ],
+            ],
+            label![
+                "Silence timeout (ms) ",
+                input![
+                    attrs! {At::Type => "number", At::Step => "100", At::Value => detector.silence_timeout_ms},
+                    input_ev(Ev::Input, Msg::SetSilenceTimeout),
+                ],
+            ],
+        ]),
+    ]
+}
+
+fn view_calibration(model: &Model) -> Node<Msg> {
+    let busy = model.recorder.is_some() || model.stopping || model.calibration.is_some();
+    div![
+        button![
+            attrs! {At::Disabled => busy.as_at_value()},
+            ev(Ev::Click, |_| Msg::Calibrate),
+            "Calibrate levels",
+        ],
+        if model.calibration.is_some() {
+            span![" Speak as you would while recording..."]
+        } else {
+            empty![]
+        },
+        model.calibrated_levels.map(|(peak, average)| span![
+            format!(" Peak {:.2}, average {:.2} - input gain x{:.2} ", peak, average, model.input_gain),
+            button!["Reset gain", ev(Ev::Click, |_| Msg::ResetInputGain)],
+        ]),
+    ]
+}
+
+// Hidden while the resumed take is recording - its timer carries on from the recovered time.
+fn view_recovered(model: &Model) -> Node<Msg> {
+    let recovered = match model.recovered.as_ref() {
+        Some(recovered) if model.recorder.is_none() && !model.stopping => recovered,
+        _ => return empty![],
+    };
+    div![
+        C!["error"],
+        format!(
+            "The recording was interrupted ({}), the {} recorded so far are kept. ",
+            recovered.reason,
+            format_duration(recovered.elapsed_ms),
+        ),
+        button!("Resume", ev(Ev::Click, |_| Msg::ResumeFromError)),
+        button!("Save as it is", ev(Ev::Click, |_| Msg::SaveRecovered)),
+        button!("Discard", ev(Ev::Click, |_| Msg::DiscardRecovered)),
+    ]
+}
+
+fn store_note(recording: &Recording, persistence: Persistence, orders: &mut impl Orders<Msg>) {
+    if persistence != Persistence::On {
+        return;
+    }
+    let (id, text, tags) = (recording.id, recording.description.clone(), recording.tags.clone());
+    orders.perform_cmd(async move { Msg::Stored(storage::put_note(id, text, tags).await) });
+}
+
+// The recordings with a tag that contains `filter`, ignoring case; all of them for an empty filter.
+fn filter_recordings<'a>(recordings: &'a [Recording], filter: &str) -> Vec<&'a Recording> {
+    let filter = filter.trim().to_lowercase();
+    recordings.iter()
+        .filter(|recording| filter.is_empty() || recording.tags.iter().any(|tag| tag.contains(&filter)))
+        .collect()
+}
+
+fn view_recordings(model: &Model) -> Node<Msg> {
+    div![
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.dedup.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleDedup),
+            ],
+            "Skip duplicate takes",
+        ],
+        model.dedup_notice.as_ref().map(|notice| div![notice]),
+        model.clipboard_status.as_ref().map(|status| div![status]),
+        audio![el_ref(&model.player), attrs! {At::Controls => AtValue::None}],
+        IF!(!model.recordings.is_empty() => label![
+            "Filter by tag ",
+            input![
+                attrs! {At::Type => "search", At::Value => model.filter},
+                input_ev(Ev::Input, Msg::SetFilter),
+            ],
+        ]),
+        ul![filter_recordings(&model.recordings, &model.filter).into_iter().map(|recording| {
+            let id = recording.id;
+            li![
+                input![
+                    attrs! {At::Type => "checkbox", At::Checked => model.selected.contains(&id).as_at_value()},
+                    ev(Ev::Change, move |_| Msg::ToggleSelect(id)),
+                ],
+                format!(
+                    "Recording {} ({} bytes, {}) ",
+                    id + 1,
+                    recording.bytes.len(),
+                    recording.metadata.capture_mode.as_str(),
+                ),
+                button!("Play", ev(Ev::Click, move |_| Msg::PlayRecording(id))),
+                button!("Delete", ev(Ev::Click, move |_| Msg::DeleteRecording(id))),
+                button!("Extract PCM", ev(Ev::Click, move |_| Msg::ExtractPcm(id))),
+                button!("Download original", ev(Ev::Click, move |_| Msg::DownloadOriginal(id))),
+                recording.metadata.failed_chunks.filter(|count| *count > 0).map(|count| span![
+                    C!["warning"],
+                    format!(" ({} chunk(s) couldn't be read - there are gaps) ", count),
+                ]),
+                IF!(recording.original_may_not_seek() =>
+                    span![" (joined from chunks - some players can't seek in it or show its length) "]),
+                button!("Export WAV", ev(Ev::Click, move |_| Msg::ExportWav(id))),
+                button!("Export FLAC", ev(Ev::Click, move |_| Msg::ExportFlac(id))),
+                view_trimmed_duration(model, recording),
+                IF!(!recording.metadata.markers.is_empty() =>
+                    button!("Export chapters", ev(Ev::Click, move |_| Msg::ExportChapters(id)))),
+                IF!(recording.lossless_bytes().is_some() =>
+                    button!("Download lossless WAV", ev(Ev::Click, move |_| Msg::DownloadLossless(id)))),
+                view_copy_data_url(recording),
+                button![
+                    "Upload",
+                    attrs! {At::Disabled => (model.upload.is_some() || model.put_pending).as_at_value()},
+                    ev(Ev::Click, move |_| Msg::UploadRecording(id)),
+                ],
+                div![textarea![
+                    attrs! {At::Placeholder => "Notes", At::Value => recording.description},
+                    input_ev(Ev::Input, move |note| Msg::SetRecordingNote(id, note)),
+                ]],
+                view_tags(recording),
+                recording.metadata.replay_gain_db.map(|gain| div![format!("Suggested playback gain {:+.1} dB", gain)]),
+                match &model.chapters {
+                    Some((chapters_id, chapters)) if *chapters_id == id => div![
+                        "Chapters: ",
+                        chapters.iter().enumerate().map(|(index, chapter)| {
+                            button!(&chapter.name, ev(Ev::Click, move |_| Msg::DownloadChapter(id, index)))
+                        }),
+                    ],
+                    _ => empty![],
+                },
+                recording.metadata.quality.as_ref().map(view_quality),
+                view_muted_regions(&recording.metadata.muted_regions),
+                view_markers(&recording.metadata.markers),
+                match &model.pcm {
+                    Some((pcm_id, pcm)) if *pcm_id == id => div![
+                        format!(
+                            "PCM: {} channel(s), {} Hz, {:.1} s",
+                            pcm.channels.len(),
+                            pcm.sample_rate,
+                            pcm.duration_s(),
+                        ),
+                        model.loudness.as_ref().map(view_loudness),
+                        IF!(!model.low_power => view_waveform(model, pcm.duration_s())),
+                    ],
+                    _ => empty![],
+                },
+            ]
+        })],
+        button![
+            "Download all (ZIP)",
+            attrs! {At::Disabled => model.recordings.is_empty().as_at_value()},
+            ev(Ev::Click, |_| Msg::ExportAllZip),
+        ],
+        button![
+            format!("Download selected ({})", model.selected.len()),
+            attrs! {At::Disabled => model.selected.is_empty().as_at_value()},
+            ev(Ev::Click, |_| Msg::DownloadSelected),
+        ],
+        label![
+            input![
+                attrs! {At::Type => "checkbox", At::Checked => model.zip_selected.as_at_value()},
+                ev(Ev::Change, |_| Msg::ToggleZipSelected),
+            ],
+            "as one ZIP",
+        ],
+        IF!(!model.recordings.is_empty() => button!("Clear", ev(Ev::Click, |_| Msg::ClearRecordings))),
+    ]
+}
+
+fn view_tags(recording: &Recording) -> Node<Msg> {
+    let id = recording.id;
+    div![
+        recording.tags.iter().map(|tag| {
+            let removed = tag.clone();
+            span![
+                C!["tag"],
+                tag,
+                button!("x", attrs! {At::Title => "Remove tag"}, ev(Ev::Click, move |_| Msg::RemoveTag(id, removed))),
+                " ",
+            ]
+        }),
+        // Added on Enter (or when leaving the field), then cleared for the next one.
+        input![
+            attrs! {At::Placeholder => "Add tag"},
+            ev(Ev::Change, move |event| {
+                let input = event.target()?.dyn_into::<HtmlInputElement>().ok()?;
+                let tag = input.value();
+                input.set_value("");
+                Some(Msg::AddTag(id, tag))
+            }),
+        ],
+    ]
+}
+
+fn view_copy_data_url(recording: &Recording) -> Node<Msg> {
+    let id = recording.id;
+    let size = recording.bytes.len();
+    span![
+        button![
+            "Copy as data URL",
+            attrs! {At::Disabled => (size > CLIPBOARD_MAX_BYTES).as_at_value()},
+            ev(Ev::Click, move |_| Msg::CopyDataUrl(id)),
+        ],
+        if size > CLIPBOARD_MAX_BYTES {
+            span![" (too large for the clipboard) "]
+        } else if size > CLIPBOARD_WARNING_BYTES {
+            span![" (large - the data URL is a third bigger still) "]
+        } else {
+            empty![]
+        },
+    ]
+}
+
+// What's left of the recording after `ExportSettings::trim_end_s`, from the decoded PCM for a restored one.
+fn view_trimmed_duration(model: &Model, recording: &Recording) -> Node<Msg> {
+    let trim_s = model.export_settings.trim_end_s;
+    if trim_s <= 0. {
+        return empty![];
+    }
+    let duration_s = recording.metadata.duration_ms.map(|ms| ms / 1000.).or_else(|| match &model.pcm {
+        Some((pcm_id, pcm)) if *pcm_id == recording.id => Some(pcm.duration_s()),
+        _ => None,
+    });
+    match duration_s {
+        Some(duration_s) => {
+            let trimmed_s = (duration_s - f64::from(trim_s)).max(0.);
+            span![format!(" ({} after the trim) ", format_duration(trimmed_s * 1000.))]
+        }
+        None => span![" (length unknown until the PCM is extracted) "],
+    }
+}
+
+fn view_waveform(model: &Model, duration_s: f64) -> Node<Msg> {
+    div![canvas![
+        el_ref(&model.waveform_canvas),
+        attrs! {At::Width => WAVEFORM_WIDTH, At::Height => WAVEFORM_HEIGHT},
+        style! {St::Cursor => "pointer"},
+        mouse_ev(Ev::Click, move |event| {
+            Msg::SeekTo(f64::from(event.offset_x()) / f64::from(WAVEFORM_WIDTH) * duration_s)
+        }),
+    ]]
+}
+
+fn view_loudness(loudness: &Loudness) -> Node<Msg> {
+    let format = |value: Option<f64>, unit: &str| value.map_or_else(|| "-inf".to_owned(), |value| format!("{:.1} {}", value, unit));
+    span![format!(
+        ", loudness {} (approx.), peak {}",
+        format(loudness.integrated_lufs, "LUFS"),
+        format(loudness.peak_dbfs, "dBFS"),
+    )]
+}
+
+fn view_quality(quality: &Quality) -> Node<Msg> {
+    let format = |value: Option<f64>| value.map_or_else(|| "-inf".to_owned(), |value| format!("{:.1}", value));
+    div![format!(
+        "Quality: peak {} dBFS, RMS {} dBFS, {} clipped sample(s), {:.0} % silence",
+        format(quality.peak_dbfs),
+        format(quality.rms_dbfs),
+        quality.clip_count,
+        quality.silence_ratio * 100.,
+    )]
+}
+
+fn view_muted_regions(regions: &[(f64, f64)]) -> Node<Msg> {
+    if regions.is_empty() {
+        return empty![];
+    }
+    div![
+        "Muted (gaps): ",
+        regions.iter()
+            .map(|(start_ms, end_ms)| format!("{:.1}s - {:.1}s", start_ms / 1000., end_ms / 1000.))
+            .collect::<Vec<_>>()
+            .join(", "),
+    ]
+}
+
+thread_local! {
+    static PCM_CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+// Registers a JS function called as `callback(channels, sampleRate, interleaved)` with the decoded
+// samples every time PCM is extracted, so the embedding app can run its own DSP. `channels` is an
+// `Array` of `Float32Array`s in the order documented on `Pcm`; `interleaved` is one `Float32Array`
+// with the same samples frame by frame, see `pcm::interleave`. Pass `null` to unregister.
+#[wasm_bindgen]
+pub fn set_pcm_callback(callback: Option<js_sys::Function>) {
+    PCM_CALLBACK.with(|cell| *cell.borrow_mut() = callback);
+}
+
+thread_local! {
+    static CHUNK_CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+// Registers a JS function called as `callback(bytes, sequence, elapsedMs)` with every chunk of the
+// take in progress once it's read, e.g. to stream it to a speech API for live captions.
+// `bytes` is a `Uint8Array`, `sequence` the 0-based index of the chunk in the take and `elapsedMs`
+// the time into the take (without pauses) it was received at. Chunks are read concurrently, so they
+// may arrive out of order - `sequence` tells their place. The callback may return a promise;
+// exceptions and rejections are logged and never affect the recording. Pass `null` to unregister.
+#[wasm_bindgen]
+pub fn set_chunk_callback(callback: Option<js_sys::Function>) {
+    CHUNK_CALLBACK.with(|cell| *cell.borrow_mut() = callback);
+}
+
+fn call_chunk_callback(bytes: &[u8], sequence: usize, elapsed_ms: f64) {
+    CHUNK_CALLBACK.with(|cell| {
+        if let Some(callback) = cell.borrow().as_ref() {
+            #[allow(clippy::cast_precision_loss)]
+            let sequence = sequence as f64;
+            let data = js_sys::Uint8Array::from(bytes);
+            match callback.call3(&JsValue::NULL, &data, &sequence.into(), &elapsed_ms.into()) {
+                Ok(result) => {
+                    if let Ok(promise) = result.dyn_into::<js_sys::Promise>() {
+                        wasm_bindgen_futures::spawn_local(async move {
+                            if let Err(error) = JsFuture::from(promise).await {
+                                log_error!("Error in chunk callback:", error);
+                            }
+                        });
+                    }
+                }
+                Err(error) => log_error!("Error in chunk callback:", error),
+            }
+        }
+    });
+}
+
+thread_local! {
+    static READY_CALLBACK: RefCell<Option<js_sys::Function>> = const { RefCell::new(None) };
+}
+
+// Registers a JS function called as `callback(mimeType)` once per take when the stream is acquired
+// and the recorder is created, right before it's started - e.g. for a parent UI to enable its controls.
+// `mimeType` is empty when the browser only picks the format at start. For every take the order is:
+// ready -> (start) -> recording (`progress` event) -> chunks (`set_chunk_callback`) -> finalized
+// (`progress` event and the completion event). Pass `null` to unregister.
+#[wasm_bindgen]
+pub fn set_ready_callback(callback: Option<js_sys::Function>) {
+    READY_CALLBACK.with(|cell| *cell.borrow_mut() = callback);
+}
+
+fn call_ready_callback(mime_type: &str) {
+    READY_CALLBACK.with(|cell| {
+        if let Some(callback) = cell.borrow().as_ref() {
+            if let Err(error) = callback.call1(&JsValue::NULL, &mime_type.into()) {
+                log_error!("Error in ready callback:", error);
+            }
+        }
+    });
+}
+
+fn call_pcm_callback(pcm: &Pcm) {
+    PCM_CALLBACK.with(|cell| {
+        if let Some(callback) = cell.borrow().as_ref() {
+            let channels = pcm.channels.iter()
+                .map(|channel| js_sys::Float32Array::from(channel.as_slice()))
+                .collect::<js_sys::Array>();
+            let interleaved = js_sys::Float32Array::from(pcm::interleave(&pcm.channels).as_slice());
+            if let Err(error) = callback.call3(&JsValue::NULL, &channels, &pcm.sample_rate.into(), &interleaved) {
+                log_error!("Error in PCM callback:", error);
+            }
+        }
+    });
+}
+
+thread_local! {
+    static APP: RefCell<Option<App<Msg, Model, Node<Msg>>>> = const { RefCell::new(None) };
+}
+
+// Lets pages that don't use Seed drive the running app from JS. Any handle - the one returned
+// by `try_start` or `new RecorderHandle()` - controls the app started last; calls made before
+// an app is started are ignored.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct RecorderHandle;
+
+#[wasm_bindgen]
+impl RecorderHandle {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn start_recording(&self) {
+        send_to_app("start_recording", Msg::StartRecording);
+    }
+
+    pub fn stop_recording(&self) {
+        send_to_app("stop_recording", Msg::StopRecording);
+    }
+
+    // Like `stop_recording`, but the stream stays open for the next
+    // `start_recording`, which then starts right away - for apps recording many short takes.
+    // The tracks keep running until `release` is called, and the browser shows the mic as in use.
+    pub fn stop_keep_stream(&self) {
+        send_to_app("stop_keep_stream", Msg::StopKeepStream);
+    }
+
+    // Stops the tracks of a stream kept by `stop_keep_stream` (or the "keep the mic open" setting).
+    // A take in progress keeps its stream until it stops.
+    pub fn release(&self) {
+        send_to_app("release", Msg::ReleaseStream);
+    }
+
+    // Acquires the mic ahead of time (call it from a click or key handler), so that the next
+    // `start_recording` starts instantly. Released again if nothing is recorded within a minute.
+    pub fn prewarm(&self) {
+        send_to_app("prewarm", Msg::Prewarm);
+    }
+
+    pub fn pause(&self) {
+        send_to_app("pause", Msg::PauseRecording);
+    }
+
+    pub fn resume(&self) {
+        send_to_app("resume", Msg::ResumeRecording);
+    }
+
+    // Bytes of the newest finished recording, `undefined` if there's none.
+    pub fn get_last_recording(&self) -> Option<js_sys::Uint8Array> {
+        let cell = Rc::new(RefCell::new(None));
+        send_to_app("get_last_recording", Msg::ReadLastRecording(Rc::clone(&cell)));
+        // `App::update` handles the message synchronously.
+        let bytes = cell.borrow_mut().take();
+        bytes.map(|bytes| js_sys::Uint8Array::from(bytes.as_slice()))
+    }
+}
+
+fn send_to_app(method: &str, msg: Msg) {
+    APP.with(|app| match app.borrow().as_ref() {
+        Some(app) => app.update(msg),
+        None => log_info!(format!("Warning: `RecorderHandle.{}` called before the app was started, ignoring", method)),
+    });
+}
+
+#[wasm_bindgen(start)]
+pub fn start() {
+    if let Err(error) = try_start("app") {
+        log_error!("Error starting the app:", error);
+    }
+}
+
+// Fallible version of `start` for embedders - the returned promise rejects
+// instead of the app panicking when the page can't host the recorder.
+#[wasm_bindgen]
+pub fn try_start(element_id: &str) -> Result<RecorderHandle, JsValue> {
+    check_environment(element_id)?;
+    let app = App::start(element_id, init, update, view);
+    APP.with(|cell| *cell.borrow_mut() = Some(app));
+    Ok(RecorderHandle)
+}
+
+// Like `try_start`, but mounts only a single record/stop toggle button.
+#[wasm_bindgen]
+pub fn start_compact(element_id: &str) -> Result<RecorderHandle, JsValue> {
+    check_environment(element_id)?;
+    let app = App::start(
+        element_id,
+        |url, orders| Model { compact: true, ..init(url, orders) },
+        update,
+        view,
+    );
+    APP.with(|cell| *cell.borrow_mut() = Some(app));
+    Ok(RecorderHandle)
+}
+
+fn check_environment(element_id: &str) -> Result<(), JsValue> {
+    if document().get_element_by_id(element_id).is_none() {
+        return Err(js_sys::Error::new(&format!("element `#{}` not found", element_id)).into());
+    }
+    if !js_sys::Reflect::has(&window(), &"MediaRecorder".into())? {
+        return Err(js_sys::Error::new("MediaRecorder is not supported by this browser").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the `StartStage`s do when they pass: a stream arrives at most once per request.
+    fn arrive_stream(state: &mut StartState, streams: &mut usize, recorders: &mut usize) {
+        if !is_duplicate_start(StartStage::Stream, *state) {
+            *streams += 1;
+            state.stream = true;
+            if !is_duplicate_start(StartStage::Recorder, *state) {
+                *recorders += 1;
+                state.recorder = true;
+            }
+        }
+    }
+
+    #[test]
+    fn two_starts_get_one_recorder_and_one_stream() {
+        let (mut state, mut streams, mut recorders) = (StartState::default(), 0, 0);
+        for _ in 0..2 {
+            if !is_duplicate_start(StartStage::Request, state) {
+                arrive_stream(&mut state, &mut streams, &mut recorders);
+            }
+        }
+        assert_eq!((streams, recorders), (1, 1));
+    }
+
+    #[test]
+    fn two_starts_before_the_stream_get_one_recorder_and_one_stream() {
+        let (mut state, mut streams, mut recorders) = (StartState::default(), 0, 0);
+        assert!(!is_duplicate_start(StartStage::Request, state));
+        assert!(!is_duplicate_start(StartStage::Request, state));
+        arrive_stream(&mut state, &mut streams, &mut recorders);
+        arrive_stream(&mut state, &mut streams, &mut recorders);
+        assert_eq!((streams, recorders), (1, 1));
+    }
+
+    #[test]
+    fn a_start_while_stopping_is_a_duplicate() {
+        let state = StartState { busy: true, ..StartState::default() };
+        assert!(is_duplicate_start(StartStage::Request, state));
+    }
+
+    #[test]
+    fn quality_is_measured_for_takes_of_a_sane_size() {
+        assert!(measures_quality(false, Some(60_000.), 100_000_000));
+        assert!(measures_quality(false, None, 1024));
+    }
+
+    #[test]
+    fn quality_is_not_measured_in_low_power_or_for_long_takes() {
+        assert!(!measures_quality(true, Some(1000.), 1024));
+        assert!(!measures_quality(false, Some(60. * 60. * 1000.), 1024));
+        assert!(!measures_quality(false, None, 100_000_000));
+    }
+
+    #[test]
+    fn stop_keep_stream_keeps_the_tracks_live() {
+        assert_eq!(stream_after_take(true, false, false, false), StreamAfterTake::Keep);
+        // The grace period doesn't apply to a kept stream.
+        assert_eq!(stream_after_take(true, false, false, true), StreamAfterTake::Keep);
+    }
+
+    #[test]
+    fn a_plain_stop_releases_the_stream() {
+        assert_eq!(stream_after_take(false, false, false, false), StreamAfterTake::Stop);
+        assert_eq!(stream_after_take(false, false, false, true), StreamAfterTake::Grace);
+        assert_eq!(stream_after_take(true, false, true, false), StreamAfterTake::Stop);
+        assert_eq!(stream_after_take(false, true, false, false), StreamAfterTake::Keep);
+    }
+
+    #[test]
+    fn immediate_and_batched_chunks_give_the_same_bytes() {
+        let received = [vec![0x1a, 0x45, 0xdf], vec![0xa3], vec![], vec![0x42, 0x86, 0x81]];
+        // Read right away, into a slot each, finishing in any order.
+        let mut immediate = vec![Vec::new(); received.len()];
+        for index in [2, 0, 3, 1].iter().copied() {
+            immediate[index] = received[index].clone();
+        }
+        let mut batched = vec![received.concat()];
+        assert_eq!(concat_chunks(&mut immediate), concat_chunks(&mut batched));
+        assert!(immediate.is_empty());
+    }
+
+    fn tagged(id: usize, tags: &[&str]) -> Recording {
+        Recording {
+            id,
+            bytes: Vec::new(),
+            mime: "audio/ogg".to_owned(),
+            lossless: None,
+            hash: 0,
+            metadata: RecordingMetadata::default(),
+            description: String::new(),
+            tags: tags.iter().map(|tag| (*tag).to_owned()).collect(),
+            // No URL, so dropping it doesn't call into the browser.
+            object_url: None,
+        }
+    }
+
+    fn filtered_ids(recordings: &[Recording], filter: &str) -> Vec<usize> {
+        filter_recordings(recordings, filter).iter().map(|recording| recording.id).collect()
+    }
+
+    #[test]
+    fn an_empty_filter_shows_everything() {
+        let recordings = [tagged(1, &["interview"]), tagged(2, &[])];
+        assert_eq!(filtered_ids(&recordings, ""), [1, 2]);
+        assert_eq!(filtered_ids(&recordings, "   "), [1, 2]);
+    }
+
+    #[test]
+    fn the_filter_ignores_case() {
+        let recordings = [tagged(1, &["interview"]), tagged(2, &["music"])];
+        assert_eq!(filtered_ids(&recordings, "Interview"), [1]);
+        assert_eq!(filtered_ids(&recordings, " MUS "), [2]);
+    }
+
+    #[test]
+    fn any_of_several_tags_matches() {
+        let recordings = [tagged(1, &["draft", "interview"]), tagged(2, &["final", "music"]), tagged(3, &["draft"])];
+        assert_eq!(filtered_ids(&recordings, "draft"), [1, 3]);
+        assert_eq!(filtered_ids(&recordings, "music"), [2]);
+        assert!(filtered_ids(&recordings, "podcast").is_empty());
+    }
+
+    #[test]
+    fn without_voice_activation_ticks_never_change_the_activity() {
+        let mut detector = VoiceActivityDetector::default();
+        for (tick, level) in [0.5, 0.5, 0., 0., 0.].iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let now_ms = tick as f64 * 1000.;
+            assert_eq!(voice_activity_change(&mut detector, false, *level, now_ms), None);
+        }
+        assert!(!detector.is_active());
+        assert_eq!(voice_activity_change(&mut detector, true, 0.5, 5000.), Some(true));
+    }
+
+    // A lossless take has no compressed chunks, and a short one none before the first timeslice;
+    // the state alone decides, so neither loses its PCM or its final chunk.
+    #[test]
+    fn a_browser_stopped_lossless_take_is_finalized() {
+        assert_eq!(stop_action(RecordingState::Inactive), StopAction::Finalize);
+    }
+
+    #[test]
+    fn stopping_a_running_recorder_stops_it() {
+        assert_eq!(stop_action(RecordingState::Recording), StopAction::Stop);
+        assert_eq!(stop_action(RecordingState::Paused), StopAction::Stop);
+    }
 }
@@ -0,0 +1,56 @@
+// Sane ranges for the numeric settings the user can type in. Out-of-range values are
+// clamped rather than rejected; the callers tell the user when that happened.
+
+pub const MIN_BITS_PER_SECOND: u32 = 8_000;
+pub const MAX_BITS_PER_SECOND: u32 = 320_000;
+// Below ~20 ms some engines throw from `start` or flood us with tiny chunks.
+pub const MIN_TIMESLICE_MS: i32 = 20;
+pub const MAX_TIMESLICE_MS: i32 = 10_000;
+pub const MIN_MAX_DURATION_S: f64 = 1.;
+pub const MAX_MAX_DURATION_S: f64 = 24. * 60. * 60.;
+
+pub fn clamp_bits_per_second(bits_per_second: u32) -> u32 {
+    bits_per_second.clamp(MIN_BITS_PER_SECOND, MAX_BITS_PER_SECOND)
+}
+
+pub fn clamp_timeslice_ms(timeslice_ms: i32) -> i32 {
+    timeslice_ms.clamp(MIN_TIMESLICE_MS, MAX_TIMESLICE_MS)
+}
+
+// `NaN` ends up at the minimum, infinities at the respective bound.
+pub fn clamp_max_duration_s(seconds: f64) -> f64 {
+    if seconds.is_nan() {
+        MIN_MAX_DURATION_S
+    } else {
+        seconds.clamp(MIN_MAX_DURATION_S, MAX_MAX_DURATION_S)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitrate_bounds() {
+        assert_eq!(clamp_bits_per_second(MIN_BITS_PER_SECOND), MIN_BITS_PER_SECOND);
+        assert_eq!(clamp_bits_per_second(MAX_BITS_PER_SECOND), MAX_BITS_PER_SECOND);
+        assert_eq!(clamp_bits_per_second(MIN_BITS_PER_SECOND - 1), MIN_BITS_PER_SECOND);
+        assert_eq!(clamp_bits_per_second(MAX_BITS_PER_SECOND + 1), MAX_BITS_PER_SECOND);
+    }
+
+    #[test]
+    fn timeslice_bounds() {
+        assert_eq!(clamp_timeslice_ms(MIN_TIMESLICE_MS), MIN_TIMESLICE_MS);
+        assert_eq!(clamp_timeslice_ms(MAX_TIMESLICE_MS), MAX_TIMESLICE_MS);
+        assert_eq!(clamp_timeslice_ms(MIN_TIMESLICE_MS - 1), MIN_TIMESLICE_MS);
+        assert_eq!(clamp_timeslice_ms(MAX_TIMESLICE_MS + 1), MAX_TIMESLICE_MS);
+        assert_eq!(clamp_timeslice_ms(-1), MIN_TIMESLICE_MS);
+    }
+
+    #[test]
+    fn max_duration_bounds() {
+        assert!((clamp_max_duration_s(f64::NAN) - MIN_MAX_DURATION_S).abs() < f64::EPSILON);
+        assert!((clamp_max_duration_s(f64::INFINITY) - MAX_MAX_DURATION_S).abs() < f64::EPSILON);
+        assert!((clamp_max_duration_s(0.) - MIN_MAX_DURATION_S).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,105 @@
+use seed::prelude::{js_sys, Closure, JsCast, JsValue};
+use std::{cell::RefCell, collections::{BTreeMap, VecDeque}, rc::Rc};
+use web_sys::{MediaSource, SourceBuffer, Url};
+
+// Plays the take in progress while it's being recorded: every chunk is appended to a `SourceBuffer`
+// of a `MediaSource` that a player element plays from `url`, so the recorded part can be scrubbed.
+pub struct LivePlayback {
+    pub url: String,
+    media_source: MediaSource,
+    queue: Rc<RefCell<Queue>>,
+    _on_source_open: Closure<dyn Fn(JsValue)>,
+    _on_update_end: Closure<dyn Fn(JsValue)>,
+}
+
+// A `SourceBuffer` takes one append at a time (until `updateend`), and only once the player
+// has opened the source; chunks wait here until then.
+#[derive(Default)]
+struct Queue {
+    source_buffer: Option<SourceBuffer>,
+    // Chunks are read asynchronously and may finish out of order, see `FileSink`.
+    waiting: BTreeMap<usize, Vec<u8>>,
+    next_index: usize,
+    ready: VecDeque<Vec<u8>>,
+}
+
+impl Queue {
+    fn pump(&mut self) {
+        if let Some(source_buffer) = self.source_buffer.as_ref() {
+            if source_buffer.updating() {
+                return;
+            }
+            if let Some(mut bytes) = self.ready.pop_front() {
+                if let Err(error) = source_buffer.append_buffer_with_u8_array(&mut bytes) {
+                    log_error!("Error appending to live playback:", error);
+                }
+            }
+        }
+    }
+}
+
+// Some browsers have no Media Source Extensions at all; others can't play back every codec they record.
+pub fn is_supported(mime_type: &str) -> bool {
+    let has_media_source = js_sys::Reflect::has(&seed::window(), &"MediaSource".into()).unwrap_or(false);
+    has_media_source && !mime_type.is_empty() && MediaSource::is_type_supported(mime_type)
+}
+
+impl LivePlayback {
+    pub fn new(mime_type: &str) -> Result<Self, JsValue> {
+        let media_source = MediaSource::new()?;
+        let url = Url::create_object_url_with_source(&media_source)?;
+        let queue = Rc::new(RefCell::new(Queue::default()));
+
+        let pumped_queue = Rc::clone(&queue);
+        let on_update_end = Closure::wrap(Box::new(move |_: JsValue| {
+            pumped_queue.borrow_mut().pump();
+        }) as Box<dyn Fn(JsValue)>);
+
+        let (opened_source, opened_queue) = (media_source.clone(), Rc::clone(&queue));
+        let update_end = on_update_end.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        let mime_type = mime_type.to_owned();
+        let on_source_open = Closure::wrap(Box::new(move |_: JsValue| {
+            match opened_source.add_source_buffer(&mime_type) {
+                Ok(source_buffer) => {
+                    source_buffer.set_onupdateend(Some(&update_end));
+                    let mut queue = opened_queue.borrow_mut();
+                    queue.source_buffer = Some(source_buffer);
+                    queue.pump();
+                }
+                Err(error) => log_error!("Error creating live playback buffer:", error),
+            }
+        }) as Box<dyn Fn(JsValue)>);
+        media_source.set_onsourceopen(Some(on_source_open.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            url,
+            media_source,
+            queue,
+            _on_source_open: on_source_open,
+            _on_update_end: on_update_end,
+        })
+    }
+
+    // Queues the chunk `index` of the take; chunks are appended in order.
+    pub fn append(&self, index: usize, bytes: Vec<u8>) {
+        let queue = &mut *self.queue.borrow_mut();
+        queue.waiting.insert(index, bytes);
+        while let Some(bytes) = queue.waiting.remove(&queue.next_index) {
+            queue.ready.push_back(bytes);
+            queue.next_index += 1;
+        }
+        queue.pump();
+    }
+}
+
+impl Drop for LivePlayback {
+    fn drop(&mut self) {
+        self.media_source.set_onsourceopen(None);
+        if let Some(source_buffer) = self.queue.borrow().source_buffer.as_ref() {
+            source_buffer.set_onupdateend(None);
+        }
+        if let Err(error) = Url::revoke_object_url(&self.url) {
+            log_error!("Error revoking live playback URL:", error);
+        }
+    }
+}
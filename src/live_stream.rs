@@ -0,0 +1,389 @@
+use seed::prelude::{js_sys, Closure, JsCast, JsValue};
+use std::{collections::{BTreeMap, VecDeque}, rc::Rc};
+use web_sys::WebSocket;
+
+// Chunks waiting for the socket are bounded, a long outage would otherwise eat up the memory.
+pub const BUFFER_BYTES: usize = 16 * 1024 * 1024;
+const RECONNECT_BASE_MS: u32 = 500;
+const RECONNECT_MAX_MS: u32 = 10_000;
+// Consecutive failed connects before streaming is given up; the take itself goes on.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+// What to do with a new chunk once `BUFFER_BYTES` are waiting.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    // Leaves a gap in what the server gets, which it has to cope with.
+    DropOldest,
+    Stop,
+}
+
+impl Overflow {
+    pub const ALL: [Self; 2] = [Self::DropOldest, Self::Stop];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::DropOldest => "drop-oldest",
+            Self::Stop => "stop",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|overflow| overflow.as_str() == value)
+    }
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Self::DropOldest
+    }
+}
+
+// Chunks of the take on their way to the socket, in order. Kept apart from the socket itself,
+// so it's the same whether they're sent right away or only after a reconnect.
+pub struct ChunkBuffer {
+    // Chunks are read asynchronously and may finish out of order, see `LivePlayback`.
+    waiting: BTreeMap<usize, Vec<u8>>,
+    next_index: usize,
+    ready: VecDeque<Vec<u8>>,
+    queued_bytes: usize,
+    max_bytes: usize,
+    overflow: Overflow,
+    pub dropped: usize,
+    pub dropped_bytes: usize,
+    // With `Overflow::Stop`, nothing is buffered anymore once it's full.
+    pub overflowed: bool,
+}
+
+impl ChunkBuffer {
+    pub fn new(max_bytes: usize, overflow: Overflow) -> Self {
+        Self {
+            waiting: BTreeMap::new(),
+            next_index: 0,
+            ready: VecDeque::new(),
+            queued_bytes: 0,
+            max_bytes,
+            overflow,
+            dropped: 0,
+            dropped_bytes: 0,
+            overflowed: false,
+        }
+    }
+
+    pub fn push(&mut self, index: usize, bytes: Vec<u8>) {
+        if self.overflowed {
+            return;
+        }
+        self.queued_bytes += bytes.len();
+        self.waiting.insert(index, bytes);
+        while let Some(bytes) = self.waiting.remove(&self.next_index) {
+            // An empty one stands in for a chunk that couldn't be read; there's nothing to send.
+            if !bytes.is_empty() {
+                self.ready.push_back(bytes);
+            }
+            self.next_index += 1;
+        }
+        while self.queued_bytes > self.max_bytes {
+            match self.overflow {
+                Overflow::Stop => {
+                    self.overflowed = true;
+                    return;
+                }
+                Overflow::DropOldest => match self.ready.pop_front() {
+                    Some(bytes) => {
+                        self.queued_bytes -= bytes.len();
+                        self.dropped += 1;
+                        self.dropped_bytes += bytes.len();
+                    }
+                    // All of it is waiting for an earlier chunk; that one is dropped once it's there.
+                    None => return,
+                },
+            }
+        }
+    }
+
+    // Sends the chunks in order until `send` fails; the failed one stays first.
+    pub fn flush(&mut self, mut send: impl FnMut(&[u8]) -> Result<(), JsValue>) -> Result<(), JsValue> {
+        while let Some(bytes) = self.ready.pop_front() {
+            if let Err(error) = send(&bytes) {
+                self.ready.push_front(bytes);
+                return Err(error);
+            }
+            self.queued_bytes -= bytes.len();
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued_bytes == 0
+    }
+
+    pub const fn queued_bytes(&self) -> usize {
+        self.queued_bytes
+    }
+}
+
+// Bytes captured but not on the wire yet - still buffered here, or in the socket (`unsent`, its
+// `bufferedAmount`). Dropped chunks never will be, so they don't count.
+pub fn backlog_bytes(captured: usize, dropped: usize, sent: usize, unsent: usize) -> usize {
+    captured.saturating_sub(dropped).saturating_sub(sent) + unsent.min(sent)
+}
+
+// Wait before the reconnect `attempt` (1 for the first), doubling up to `RECONNECT_MAX_MS`.
+pub fn reconnect_delay_ms(attempt: u32) -> u32 {
+    RECONNECT_BASE_MS.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(RECONNECT_MAX_MS)
+}
+
+#[derive(Clone, Copy)]
+pub enum SocketEvent {
+    Opened,
+    // Also after an error, and when a connect fails.
+    Closed,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Connection {
+    Connecting,
+    Open,
+    Reconnecting(u32),
+    // Everything was sent and the socket closed after the take.
+    Done,
+    Failed(String),
+}
+
+// Streams a take chunk by chunk to a WebSocket server, reconnecting when the socket drops.
+pub struct LiveStream {
+    url: String,
+    socket: Option<Socket>,
+    pub buffer: ChunkBuffer,
+    pub connection: Connection,
+    // Tells the events of the current socket from the ones of the replaced ones.
+    pub generation: u32,
+    // Of the chunks pushed, and of those handed to a socket.
+    pub captured_bytes: usize,
+    pub sent_bytes: usize,
+    attempt: u32,
+    // The take has ended; the socket is closed as soon as everything is sent.
+    finishing: bool,
+}
+
+impl LiveStream {
+    pub fn new(url: String, overflow: Overflow) -> Self {
+        Self {
+            url,
+            socket: None,
+            buffer: ChunkBuffer::new(BUFFER_BYTES, overflow),
+            connection: Connection::Connecting,
+            generation: 0,
+            captured_bytes: 0,
+            sent_bytes: 0,
+            attempt: 0,
+            finishing: false,
+        }
+    }
+
+    // `on_event` gets the `generation` of the socket along with its events.
+    // A connect that fails right away (e.g. for an invalid URL) has no `SocketEvent::Closed`.
+    pub fn connect(&mut self, on_event: Rc<dyn Fn(u32, SocketEvent)>) -> Result<(), JsValue> {
+        self.generation += 1;
+        self.socket = None;
+        self.socket = Some(Socket::connect(&self.url, self.generation, on_event)?);
+        Ok(())
+    }
+
+    pub fn push(&mut self, index: usize, bytes: Vec<u8>) {
+        self.captured_bytes += bytes.len();
+        self.buffer.push(index, bytes);
+        if self.buffer.overflowed {
+            self.fail("The buffer filled up while disconnected");
+            return;
+        }
+        self.flush();
+    }
+
+    pub fn opened(&mut self) {
+        self.attempt = 0;
+        self.connection = Connection::Open;
+        self.flush();
+    }
+
+    // The delay of the next attempt, `None` when there's none to make.
+    pub fn closed(&mut self) -> Option<u32> {
+        self.socket = None;
+        match self.connection {
+            Connection::Done | Connection::Failed(_) => return None,
+            _ if self.finishing && self.buffer.is_empty() => {
+                self.connection = Connection::Done;
+                return None;
+            }
+            _ => (),
+        }
+        self.attempt += 1;
+        if self.attempt > MAX_RECONNECT_ATTEMPTS {
+            self.fail(&format!("No connection after {} attempts", MAX_RECONNECT_ATTEMPTS));
+            return None;
+        }
+        self.connection = Connection::Reconnecting(self.attempt);
+        Some(reconnect_delay_ms(self.attempt))
+    }
+
+    pub fn backlog_bytes(&self) -> usize {
+        let unsent = self.socket.as_ref().map_or(0, |socket| socket.socket.buffered_amount() as usize);
+        backlog_bytes(self.captured_bytes, self.buffer.dropped_bytes, self.sent_bytes, unsent)
+    }
+
+    pub fn finish(&mut self) {
+        self.finishing = true;
+        self.close_if_sent();
+    }
+
+    fn flush(&mut self) {
+        if self.connection != Connection::Open {
+            return;
+        }
+        if let Some(socket) = self.socket.as_ref() {
+            let sent_bytes = &mut self.sent_bytes;
+            let sent = self.buffer.flush(|bytes| {
+                socket.send(bytes)?;
+                *sent_bytes += bytes.len();
+                Ok(())
+            });
+            if let Err(error) = sent {
+                // The socket is closing; `closed` takes it from here.
+                log_error!("Error sending to the live stream:", error);
+                return;
+            }
+        }
+        self.close_if_sent();
+    }
+
+    fn close_if_sent(&mut self) {
+        if self.finishing && self.connection == Connection::Open && self.buffer.is_empty() {
+            self.socket = None;
+            self.connection = Connection::Done;
+        }
+    }
+
+    fn fail(&mut self, reason: &str) {
+        log_error!("Live streaming stopped:", reason);
+        self.socket = None;
+        self.connection = Connection::Failed(reason.to_owned());
+    }
+}
+
+struct Socket {
+    socket: WebSocket,
+    _on_open: Closure<dyn Fn(JsValue)>,
+    _on_close: Closure<dyn Fn(JsValue)>,
+}
+
+impl Socket {
+    fn connect(url: &str, generation: u32, on_event: Rc<dyn Fn(u32, SocketEvent)>) -> Result<Self, JsValue> {
+        let socket = WebSocket::new(url)?;
+        let opened = Rc::clone(&on_event);
+        let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+            opened(generation, SocketEvent::Opened);
+        }) as Box<dyn Fn(JsValue)>);
+        // `error` is always followed by `close`, so that's the only one we need.
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            on_event(generation, SocketEvent::Closed);
+        }) as Box<dyn Fn(JsValue)>);
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        Ok(Self { socket, _on_open: on_open, _on_close: on_close })
+    }
+
+    fn send(&self, bytes: &[u8]) -> Result<(), JsValue> {
+        // `send` doesn't throw once the socket is closing, it drops the data.
+        if self.socket.ready_state() != WebSocket::OPEN {
+            return Err(js_sys::Error::new("The socket is not open").into());
+        }
+        self.socket.send_with_u8_array(bytes)
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        self.socket.set_onopen(None);
+        self.socket.set_onclose(None);
+        if let Err(error) = self.socket.close() {
+            log_error!("Error closing the live stream:", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flushed(buffer: &mut ChunkBuffer) -> Vec<Vec<u8>> {
+        let mut sent = Vec::new();
+        buffer.flush(|bytes| {
+            sent.push(bytes.to_vec());
+            Ok(())
+        }).unwrap();
+        sent
+    }
+
+    #[test]
+    fn backlog_is_what_is_not_on_the_wire() {
+        assert_eq!(backlog_bytes(0, 0, 0, 0), 0);
+        // 100 captured, 20 dropped, 50 handed to the socket of which 10 are still in it.
+        assert_eq!(backlog_bytes(100, 20, 50, 10), 40);
+        assert_eq!(backlog_bytes(100, 0, 100, 0), 0);
+        // `bufferedAmount` can't be more than what was sent.
+        assert_eq!(backlog_bytes(100, 0, 50, 80), 100);
+        assert_eq!(backlog_bytes(10, 20, 0, 0), 0);
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_up_to_the_maximum() {
+        let delays = (1..=7).map(reconnect_delay_ms).collect::<Vec<_>>();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 8000, 10_000, 10_000]);
+        assert_eq!(reconnect_delay_ms(0), RECONNECT_BASE_MS);
+        assert_eq!(reconnect_delay_ms(u32::MAX), RECONNECT_MAX_MS);
+    }
+
+    #[test]
+    fn sends_the_chunks_in_order() {
+        let mut buffer = ChunkBuffer::new(100, Overflow::DropOldest);
+        buffer.push(1, vec![2]);
+        assert!(flushed(&mut buffer).is_empty());
+        buffer.push(0, vec![1]);
+        // A chunk that couldn't be read isn't sent, and doesn't hold back the ones after it.
+        buffer.push(2, Vec::new());
+        buffer.push(3, vec![3, 3]);
+        assert_eq!(flushed(&mut buffer), [vec![1], vec![2], vec![3, 3]]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn a_failed_send_stays_first() {
+        let mut buffer = ChunkBuffer::new(100, Overflow::DropOldest);
+        buffer.push(0, vec![1]);
+        buffer.push(1, vec![2]);
+        assert!(buffer.flush(|_| Err(JsValue::NULL)).is_err());
+        assert_eq!(buffer.queued_bytes(), 2);
+        assert_eq!(flushed(&mut buffer), [vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room() {
+        let mut buffer = ChunkBuffer::new(4, Overflow::DropOldest);
+        buffer.push(0, vec![1; 2]);
+        buffer.push(1, vec![2; 2]);
+        buffer.push(2, vec![3; 2]);
+        assert_eq!((buffer.dropped, buffer.dropped_bytes, buffer.overflowed), (1, 2, false));
+        assert_eq!(flushed(&mut buffer), [vec![2; 2], vec![3; 2]]);
+    }
+
+    #[test]
+    fn stop_buffers_nothing_once_full() {
+        let mut buffer = ChunkBuffer::new(4, Overflow::Stop);
+        buffer.push(0, vec![1; 2]);
+        buffer.push(1, vec![2; 3]);
+        assert!(buffer.overflowed);
+        buffer.push(2, vec![3; 1]);
+        assert_eq!(buffer.dropped, 0);
+        assert_eq!(flushed(&mut buffer), [vec![1; 2], vec![2; 3]]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// A tiny logging facade over Seed's `log!`/`error!`, so the crate can be embedded
+// without spamming the console. The level is global (and not only in `Model`)
+// because some of the logging happens in futures that don't see the `Model`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Error, Self::Info, Self::Debug];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|level| level.as_str() == value)
+    }
+
+    const fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Off,
+            1 => Self::Error,
+            2 => Self::Info,
+            _ => Self::Debug,
+        }
+    }
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        Self::Info
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level <= self::level()
+}
+
+// The macros take the same arguments as Seed's `log!`.
+macro_rules! log_error {
+    { $($expr:expr),* $(,)? } => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Error) {
+            seed::error!($($expr),*);
+        }
+    };
+}
+
+macro_rules! log_info {
+    { $($expr:expr),* $(,)? } => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Info) {
+            seed::log!($($expr),*);
+        }
+    };
+}
+
+macro_rules! log_debug {
+    { $($expr:expr),* $(,)? } => {
+        if $crate::logging::enabled($crate::logging::LogLevel::Debug) {
+            seed::log!($($expr),*);
+        }
+    };
+}
@@ -0,0 +1,122 @@
+// An approximation of the integrated loudness of BS.1770: the gating is the real one,
+// but there's no K-weighting, so the result is a gated RMS in LUFS-like units.
+// It reads a few LU off for speech and is meant as a rough guide only.
+
+const BLOCK_S: f32 = 0.4;
+// Blocks overlap by 75 %.
+const STEP_S: f32 = 0.1;
+const ABSOLUTE_GATE_LUFS: f64 = -70.;
+const RELATIVE_GATE_LU: f64 = -10.;
+// ReplayGain 2.0 aims at -18 LUFS.
+pub const REPLAY_GAIN_REFERENCE_LUFS: f64 = -18.;
+
+pub struct Loudness {
+    // `None` for silence or input shorter than a block.
+    pub integrated_lufs: Option<f64>,
+    // `None` for silence.
+    pub peak_dbfs: Option<f64>,
+}
+
+pub fn measure(channels: &[Vec<f32>], sample_rate: f32) -> Loudness {
+    Loudness {
+        integrated_lufs: integrated_loudness(channels, sample_rate),
+        peak_dbfs: peak_dbfs(channels),
+    }
+}
+
+pub fn integrated_loudness(channels: &[Vec<f32>], sample_rate: f32) -> Option<f64> {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (block, step) = ((BLOCK_S * sample_rate) as usize, (STEP_S * sample_rate) as usize);
+    let frames = channels.first().map_or(0, Vec::len);
+    if block == 0 || step == 0 || frames < block {
+        return None;
+    }
+
+    // Mean square of each block, summed over the channels (all weighted 1.0).
+    let powers = (0..=(frames - block) / step)
+        .map(|index| {
+            let start = index * step;
+            channels
+                .iter()
+                .map(|channel| {
+                    let sum = channel[start..start + block].iter().map(|s| f64::from(*s).powi(2)).sum::<f64>();
+                    #[allow(clippy::cast_precision_loss)]
+                    let mean = sum / block as f64;
+                    mean
+                })
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+
+    let above_absolute = powers
+        .iter()
+        .copied()
+        .filter(|power| to_lufs(*power) > ABSOLUTE_GATE_LUFS)
+        .collect::<Vec<_>>();
+    let relative_gate = to_lufs(mean(&above_absolute)?) + RELATIVE_GATE_LU;
+    let above_relative = above_absolute
+        .into_iter()
+        .filter(|power| to_lufs(*power) > relative_gate)
+        .collect::<Vec<_>>();
+    mean(&above_relative).map(to_lufs)
+}
+
+// Gain in dB that brings a track of `integrated_lufs` to `REPLAY_GAIN_REFERENCE_LUFS`.
+// With the approximated loudness above it's ReplayGain-like rather than ReplayGain proper.
+pub fn replay_gain_db(integrated_lufs: f64) -> f64 {
+    REPLAY_GAIN_REFERENCE_LUFS - integrated_lufs
+}
+
+pub fn peak_dbfs(channels: &[Vec<f32>]) -> Option<f64> {
+    let peak = channels.iter().flatten().fold(0_f32, |peak, sample| peak.max(sample.abs()));
+    if peak > 0. {
+        Some(20. * f64::from(peak).log10())
+    } else {
+        None
+    }
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10. * power.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let count = values.len() as f64;
+    Some(values.iter().sum::<f64>() / count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames).map(|frame| if frame % 2 == 0 { amplitude } else { -amplitude }).collect()
+    }
+
+    #[test]
+    fn measures_a_constant_amplitude() {
+        let loudness = measure(&[square(0.5, 48_000)], 48_000.);
+        let expected = -0.691 + 10. * 0.25_f64.log10();
+        assert!((loudness.integrated_lufs.unwrap() - expected).abs() < 1e-6);
+        assert!((loudness.peak_dbfs.unwrap() - 20. * 0.5_f64.log10()).abs() < 1e-6);
+        assert!((replay_gain_db(expected) - (-18. - expected)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sums_the_channels() {
+        let mono = integrated_loudness(&[square(0.5, 48_000)], 48_000.).unwrap();
+        let stereo = integrated_loudness(&[square(0.5, 48_000), square(0.5, 48_000)], 48_000.).unwrap();
+        assert!((stereo - mono - 10. * 2_f64.log10()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn silence_and_short_input_have_no_loudness() {
+        assert!(integrated_loudness(&[vec![0.; 48_000]], 48_000.).is_none());
+        assert!(integrated_loudness(&[square(0.5, 100)], 48_000.).is_none());
+        assert!(peak_dbfs(&[vec![0.; 10]]).is_none());
+    }
+}
@@ -0,0 +1,157 @@
+// Container and codec are picked separately and composed into the MIME type
+// that `MediaRecorder` understands, e.g. `audio/webm;codecs=opus`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Ogg,
+    Webm,
+    Mp4,
+}
+
+impl Container {
+    pub const ALL: [Self; 3] = [Self::Ogg, Self::Webm, Self::Mp4];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ogg => "ogg",
+            Self::Webm => "webm",
+            Self::Mp4 => "mp4",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|container| container.as_str() == value)
+    }
+}
+
+// Ogg + Opus is what we've always recorded with.
+impl Default for Container {
+    fn default() -> Self {
+        Self::Ogg
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Opus,
+    Vorbis,
+    Aac,
+}
+
+impl Codec {
+    pub const ALL: [Self; 3] = [Self::Opus, Self::Vorbis, Self::Aac];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Vorbis => "vorbis",
+            Self::Aac => "aac",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|codec| codec.as_str() == value)
+    }
+
+    // Value of the `codecs` MIME parameter. Browsers only know AAC by its RFC 6381 name.
+    const fn codecs_parameter(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Vorbis => "vorbis",
+            Self::Aac => "mp4a.40.2",
+        }
+    }
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Self::Opus
+    }
+}
+
+// Full MIME types offered for direct selection, probed with `MediaRecorder.isTypeSupported`.
+// Includes the bare container types, for which the browser picks the codec itself.
+pub const CANDIDATES: [&str; 12] = [
+    "audio/ogg;codecs=opus",
+    "audio/ogg;codecs=vorbis",
+    "audio/ogg",
+    "audio/webm;codecs=opus",
+    "audio/webm;codecs=vorbis",
+    "audio/webm",
+    "audio/mp4;codecs=mp4a.40.2",
+    "audio/mp4;codecs=opus",
+    "audio/mp4",
+    "audio/x-matroska;codecs=opus",
+    "audio/mpeg",
+    "audio/wav",
+];
+
+// The container/codec pair `mime_type` composes into `value`, if any.
+pub fn parse_mime_type(value: &str) -> Option<(Container, Codec)> {
+    Container::ALL.iter()
+        .flat_map(|container| Codec::ALL.iter().map(move |codec| (*container, *codec)))
+        .find(|(container, codec)| mime_type(*container, *codec) == value)
+}
+
+pub fn mime_type(container: Container, codec: Codec) -> String {
+    format!("audio/{};codecs={}", container.as_str(), codec.codecs_parameter())
+}
+
+// File extension for a recording of the given MIME type, e.g. `webm` for `audio/webm;codecs=opus`.
+// Mostly the subtype, except where the usual extension is a different one.
+pub fn extension(mime_type: &str) -> &str {
+    let subtype = mime_type
+        .split(';')
+        .next()
+        .and_then(|essence| essence.split('/').nth(1))
+        .map(str::trim)
+        .filter(|subtype| !subtype.is_empty())
+        .unwrap_or("bin");
+    match subtype {
+        "x-matroska" => "mka",
+        "mpeg" => "mp3",
+        _ => subtype,
+    }
+}
+
+// Video takes leave the codecs to the browser - only the container is fixed.
+pub fn video_mime_type(container: Container) -> String {
+    format!("video/{}", container.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_container_and_codec() {
+        assert_eq!(mime_type(Container::Ogg, Codec::Opus), "audio/ogg;codecs=opus");
+        assert_eq!(mime_type(Container::Mp4, Codec::Aac), "audio/mp4;codecs=mp4a.40.2");
+        assert_eq!(video_mime_type(Container::Webm), "video/webm");
+    }
+
+    #[test]
+    fn parses_what_it_composes() {
+        for container in Container::ALL.iter().copied() {
+            for codec in Codec::ALL.iter().copied() {
+                assert!(parse_mime_type(&mime_type(container, codec)) == Some((container, codec)));
+            }
+        }
+        assert!(parse_mime_type("audio/ogg").is_none());
+    }
+
+    #[test]
+    fn extension_is_the_subtype() {
+        assert_eq!(extension("audio/webm;codecs=opus"), "webm");
+        assert_eq!(extension("audio/wav"), "wav");
+        assert_eq!(extension(""), "bin");
+        assert_eq!(extension("audio/"), "bin");
+    }
+
+    #[test]
+    fn every_candidate_has_its_usual_extension() {
+        let expected = ["ogg", "ogg", "ogg", "webm", "webm", "webm", "mp4", "mp4", "mp4", "mka", "mp3", "wav"];
+        for (mime_type, extension_of_it) in CANDIDATES.iter().zip(expected.iter()) {
+            assert_eq!(extension(mime_type), *extension_of_it, "{}", mime_type);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::ops::Range;
+
+// Decoded samples of a recording.
+//
+// `channels` is planar - one `Vec` per channel - in the Web Audio channel order:
+// mono is `[M]`, stereo `[L, R]`, 5.1 `[L, R, C, LFE, SL, SR]`.
+// All channels have the same length.
+pub struct Pcm {
+    pub sample_rate: f32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Pcm {
+    pub fn frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn duration_s(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let frames = self.frames() as f64;
+        frames / f64::from(self.sample_rate)
+    }
+}
+
+// The frames one after the other, the samples of each in channel order - `[L0, R0, L1, R1, ...]`
+// for stereo - as WAV and most DSP libraries take them.
+pub fn interleave(channels: &[Vec<f32>]) -> Vec<f32> {
+    let frames = channels.first().map_or(0, Vec::len);
+    (0..frames).flat_map(|frame| channels.iter().map(move |channel| channel[frame])).collect()
+}
+
+// Averages all channels into one. The average of samples within -1.0..=1.0 can't clip,
+// but decoded input isn't guaranteed to stay in that range, so the result is clamped as well.
+pub fn downmix_to_mono(channels: &[Vec<f32>]) -> Vec<f32> {
+    match channels {
+        [] => Vec::new(),
+        [mono] => mono.clone(),
+        _ => {
+            #[allow(clippy::cast_precision_loss)]
+            let count = channels.len() as f32;
+            (0..channels[0].len())
+                .map(|frame| {
+                    let sum = channels.iter().map(|channel| channel[frame]).sum::<f32>();
+                    (sum / count).clamp(-1., 1.)
+                })
+                .collect()
+        }
+    }
+}
+
+// Converts `input` sampled at `from` Hz to `to` Hz by linear interpolation between neighbouring samples.
+// There's no anti-aliasing filter, so downsampling folds the content above the new Nyquist
+// frequency back into the band - inaudible for most speech, which is what this is for.
+pub fn resample_linear(input: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return input.to_vec();
+    }
+    if from == 0 || to == 0 || input.is_empty() {
+        return Vec::new();
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let output_len = (input.len() as u64 * u64::from(to) / u64::from(from)) as usize;
+    let step = f64::from(from) / f64::from(to);
+    let last = input.len() - 1;
+    (0..output_len)
+        .map(|index| {
+            #[allow(clippy::cast_precision_loss)]
+            let position = index as f64 * step;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let before = (position as usize).min(last);
+            let after = (before + 1).min(last);
+            #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+            let fraction = (position - before as f64) as f32;
+            input[before] + (input[after] - input[before]) * fraction
+        })
+        .collect()
+}
+
+// Shape of the gain ramp of a fade.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FadeCurve {
+    Linear,
+    // `sin` shaped: sounds more even than linear, which seems to dip in the middle.
+    EqualPower,
+}
+
+impl FadeCurve {
+    pub const ALL: [Self; 2] = [Self::Linear, Self::EqualPower];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::EqualPower => "equal-power",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|curve| curve.as_str() == value)
+    }
+
+    // Gain at `position` (0.0 - 1.0) of a fade-in.
+    fn gain(self, position: f32) -> f32 {
+        match self {
+            Self::Linear => position,
+            Self::EqualPower => (position * std::f32::consts::FRAC_PI_2).sin(),
+        }
+    }
+}
+
+impl Default for FadeCurve {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+// Ramps the first `fade_in_ms` up from silence and the last `fade_out_ms` down to silence.
+// Fades longer than `samples` are clamped to it; zero-length fades leave the samples as they are.
+pub fn fade(samples: &mut [f32], sample_rate: f32, fade_in_ms: f64, fade_out_ms: f64, curve: FadeCurve) {
+    let len = samples.len();
+    let fade_len = |ms: f64| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frames = (ms.max(0.) / 1000. * f64::from(sample_rate)).round() as usize;
+        frames.min(len)
+    };
+    let (fade_in, fade_out) = (fade_len(fade_in_ms), fade_len(fade_out_ms));
+    for (index, sample) in samples.iter_mut().take(fade_in).enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let position = index as f32 / fade_in as f32;
+        *sample *= curve.gain(position);
+    }
+    for (index, sample) in samples.iter_mut().rev().take(fade_out).enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let position = index as f32 / fade_out as f32;
+        *sample *= curve.gain(position);
+    }
+}
+
+// Subtracts the mean, i.e. the DC offset some cheap mics add. Done before the fades so those end at 0.
+pub fn remove_dc(samples: &mut [f32]) {
+    if samples.is_empty() {
+        return;
+    }
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    let mean = (samples.iter().map(|sample| f64::from(*sample)).sum::<f64>() / samples.len() as f64) as f32;
+    for sample in samples {
+        *sample -= mean;
+    }
+}
+
+// `samples` with `duration_ms` of silence inserted at each `(elapsed_ms, duration_ms)` of `pauses`,
+// `elapsed_ms` being the position in the recorded time, i.e. without the earlier pauses.
+// Positions past the end are clamped, so the result always has every pause in it.
+pub fn insert_silence(samples: &[f32], sample_rate: f32, pauses: &[(f64, f64)]) -> Vec<f32> {
+    let to_frames = |ms: f64| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frames = (ms.max(0.) / 1000. * f64::from(sample_rate)).round() as usize;
+        frames
+    };
+    let mut gaps = pauses.iter()
+        .map(|(elapsed_ms, duration_ms)| (to_frames(*elapsed_ms).min(samples.len()), to_frames(*duration_ms)))
+        .collect::<Vec<_>>();
+    gaps.sort_by_key(|(position, _)| *position);
+    let mut output = Vec::with_capacity(samples.len() + gaps.iter().map(|(_, length)| length).sum::<usize>());
+    let mut copied = 0;
+    for (position, length) in gaps {
+        output.extend_from_slice(&samples[copied..position]);
+        output.resize(output.len() + length, 0.);
+        copied = position;
+    }
+    output.extend_from_slice(&samples[copied..]);
+    output
+}
+
+// Frames left of `frames` once the last `trim_s` seconds are dropped; none when the trim is longer than that.
+pub fn trimmed_frames(frames: usize, sample_rate: f32, trim_s: f32) -> usize {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let trimmed = (f64::from(trim_s.max(0.)) * f64::from(sample_rate)).round() as usize;
+    frames.saturating_sub(trimmed)
+}
+
+// Drops the last `trim_s` seconds of every channel, e.g. the fumbling for the stop button.
+pub fn trim_end(pcm: &mut Pcm, trim_s: f32) {
+    let frames = trimmed_frames(pcm.frames(), pcm.sample_rate, trim_s);
+    for channel in &mut pcm.channels {
+        channel.truncate(frames);
+    }
+}
+
+// Frame ranges of the chapters between consecutive markers: from the start to the first marker,
+// between each pair of markers, and from the last marker to the end.
+// `markers_ms` are ms from the start, in order; ones outside the recording are clamped.
+// There's always one chapter more than markers, so a chapter may be empty (e.g. for a marker at 0).
+pub fn chapter_ranges(markers_ms: &[f64], duration_s: f64, sample_rate: f32) -> Vec<Range<usize>> {
+    let to_frame = |ms: f64| {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let frame = (ms.max(0.).min(duration_s * 1000.) / 1000. * f64::from(sample_rate)).round() as usize;
+        frame
+    };
+    let mut boundaries = vec![0];
+    boundaries.extend(markers_ms.iter().map(|ms| to_frame(*ms)));
+    boundaries.push(to_frame(duration_s * 1000.));
+    boundaries
+        .windows(2)
+        // `max` keeps the ranges valid for markers out of order.
+        .map(|pair| pair[0]..pair[1].max(pair[0]))
+        .collect()
+}
+
+// The frames `range` of every channel.
+pub fn slice(pcm: &Pcm, range: Range<usize>) -> Pcm {
+    Pcm {
+        sample_rate: pcm.sample_rate,
+        channels: pcm.channels.iter().map(|channel| channel[range.clone()].to_vec()).collect(),
+    }
+}
+
+// The parts one after another, at the rate and channel count of the first one. Parts of another rate
+// are resampled; a part with fewer channels repeats its first one, extra channels are dropped.
+pub fn concat(parts: &[Pcm]) -> Pcm {
+    let (sample_rate, channel_count) = parts.first().map_or((0., 0), |first| (first.sample_rate, first.channels.len()));
+    let mut channels = vec![Vec::new(); channel_count];
+    for part in parts {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (from, to) = (part.sample_rate as u32, sample_rate as u32);
+        for (index, channel) in channels.iter_mut().enumerate() {
+            let source = match part.channels.get(index).or_else(|| part.channels.first()) {
+                Some(source) => source,
+                None => continue,
+            };
+            if from == to {
+                channel.extend_from_slice(source);
+            } else {
+                channel.extend(resample_linear(source, from, to));
+            }
+        }
+    }
+    Pcm { sample_rate, channels }
+}
+
+// Peak amplitude (0.0 - 1.0) of each of `columns` equal slices of the recording, over all channels,
+// for drawing a waveform.
+pub fn waveform(channels: &[Vec<f32>], columns: usize) -> Vec<f32> {
+    let frames = channels.first().map_or(0, Vec::len);
+    if columns == 0 || frames == 0 {
+        return Vec::new();
+    }
+    (0..columns)
+        .map(|column| {
+            // At least one frame per column, even with fewer frames than columns.
+            let start = column * frames / columns;
+            let end = ((column + 1) * frames / columns).max(start + 1).min(frames);
+            channels
+                .iter()
+                .flat_map(|channel| &channel[start..end])
+                .fold(0_f32, |peak, sample| peak.max(sample.abs()))
+                .min(1.)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_passes_mono_through() {
+        assert_eq!(downmix_to_mono(&[vec![0.5, -0.25, 1.]]), [0.5, -0.25, 1.]);
+        assert!(downmix_to_mono(&[]).is_empty());
+    }
+
+    #[test]
+    fn downmix_averages_stereo() {
+        let channels = vec![vec![0.5, 1., -1.], vec![-0.5, 0.5, -0.5]];
+        assert_eq!(downmix_to_mono(&channels), [0., 0.75, -0.75]);
+    }
+
+    #[test]
+    fn resampling_to_the_same_rate_is_identity() {
+        assert_eq!(resample_linear(&[0.1, -0.2, 0.3], 48_000, 48_000), [0.1, -0.2, 0.3]);
+    }
+
+    #[test]
+    fn upsampling_interpolates() {
+        assert_eq!(resample_linear(&[0., 1., 2., 3.], 24_000, 48_000), [0., 0.5, 1., 1.5, 2., 2.5, 3., 3.]);
+        assert_eq!(resample_linear(&[0.; 44_100], 44_100, 48_000).len(), 48_000);
+    }
+
+    #[test]
+    fn downsampling_keeps_every_nth_sample() {
+        assert_eq!(resample_linear(&[0., 1., 2., 3., 4., 5.], 48_000, 24_000), [0., 2., 4.]);
+        assert_eq!(resample_linear(&[0.; 48_000], 48_000, 16_000).len(), 16_000);
+        assert!(resample_linear(&[], 48_000, 16_000).is_empty());
+        assert!(resample_linear(&[1.], 0, 16_000).is_empty());
+    }
+
+    #[test]
+    fn zero_length_fades_leave_the_samples() {
+        let mut samples = [0.5, -0.5, 0.25];
+        fade(&mut samples, 1000., 0., 0., FadeCurve::Linear);
+        assert_eq!(samples, [0.5, -0.5, 0.25]);
+    }
+
+    #[test]
+    fn fades_longer_than_the_buffer_are_clamped() {
+        let mut fade_in = [1.; 4];
+        fade(&mut fade_in, 1000., 10., 0., FadeCurve::Linear);
+        assert_eq!(fade_in, [0., 0.25, 0.5, 0.75]);
+        let mut fade_out = [1.; 4];
+        fade(&mut fade_out, 1000., 0., 10., FadeCurve::Linear);
+        assert_eq!(fade_out, [0.75, 0.5, 0.25, 0.]);
+        let mut empty: [f32; 0] = [];
+        fade(&mut empty, 1000., 10., 10., FadeCurve::EqualPower);
+    }
+
+    #[test]
+    fn fades_apply_the_curve() {
+        let mut samples = [1.; 4];
+        fade(&mut samples, 1000., 2., 0., FadeCurve::EqualPower);
+        assert_eq!(samples[0], 0.);
+        assert!((samples[1] - std::f32::consts::FRAC_PI_4.sin()).abs() < 1e-6);
+        assert_eq!(samples[2..], [1., 1.]);
+    }
+
+    #[test]
+    fn no_markers_is_one_chapter() {
+        assert_eq!(chapter_ranges(&[], 1., 1000.), vec![Range { start: 0, end: 1000 }]);
+    }
+
+    #[test]
+    fn a_marker_at_0_gives_an_empty_first_chapter() {
+        assert_eq!(chapter_ranges(&[0., 400.], 1., 1000.), [0..0, 0..400, 400..1000]);
+    }
+
+    #[test]
+    fn markers_past_the_end_are_clamped() {
+        assert_eq!(chapter_ranges(&[500., 2000.], 1., 1000.), [0..500, 500..1000, 1000..1000]);
+        assert_eq!(chapter_ranges(&[-10.], 1., 1000.), [0..0, 0..1000]);
+    }
+
+    #[test]
+    fn removing_dc_centres_the_mean() {
+        let mut samples = (0..1000).map(|index| if index % 2 == 0 { 0.3 } else { -0.1 }).collect::<Vec<f32>>();
+        remove_dc(&mut samples);
+        let mean = samples.iter().map(|sample| f64::from(*sample)).sum::<f64>() / 1000.;
+        assert!(mean.abs() < 1e-6);
+        assert!((samples[0] - 0.2).abs() < 1e-6);
+        remove_dc(&mut []);
+    }
+
+    #[test]
+    fn inserts_silence_at_the_pauses() {
+        let samples = [1., 2., 3., 4.];
+        assert_eq!(insert_silence(&samples, 1000., &[(1., 2.), (3., 1.)]), [1., 0., 0., 2., 3., 0., 4.]);
+        assert_eq!(insert_silence(&samples, 1000., &[]), samples);
+    }
+
+    #[test]
+    fn pauses_past_the_end_are_appended() {
+        assert_eq!(insert_silence(&[1., 2.], 1000., &[(10., 2.), (0., 1.)]), [0., 1., 2., 0., 0.]);
+    }
+
+    #[test]
+    fn trimming_nothing_keeps_every_frame() {
+        assert_eq!(trimmed_frames(1000, 1000., 0.), 1000);
+        assert_eq!(trimmed_frames(1000, 1000., -1.), 1000);
+    }
+
+    #[test]
+    fn trimming_more_than_the_length_leaves_nothing() {
+        assert_eq!(trimmed_frames(1000, 1000., 5.), 0);
+        let mut pcm = Pcm { sample_rate: 1000., channels: vec![vec![0.; 1000], vec![0.; 1000]] };
+        trim_end(&mut pcm, 5.);
+        assert_eq!(pcm.frames(), 0);
+        assert!(pcm.channels.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn trims_every_channel_alike() {
+        let mut pcm = Pcm { sample_rate: 1000., channels: vec![vec![0.; 1000], vec![0.; 1000]] };
+        trim_end(&mut pcm, 0.25);
+        assert_eq!(pcm.channels.iter().map(Vec::len).collect::<Vec<_>>(), [750, 750]);
+    }
+
+    #[test]
+    fn interleaves_stereo_frame_by_frame() {
+        let channels = vec![vec![0.1, 0.2, 0.3], vec![-0.1, -0.2, -0.3]];
+        assert_eq!(interleave(&channels), [0.1, -0.1, 0.2, -0.2, 0.3, -0.3]);
+    }
+
+    #[test]
+    fn interleaves_mono_and_nothing_as_they_are() {
+        assert_eq!(interleave(&[vec![0.5, -0.5]]), [0.5, -0.5]);
+        assert!(interleave(&[]).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Machine-readable progress for test harnesses driving the recorder in a headless browser:
+// one JSON object per console line, e.g. `{"event":"chunk","bytes":1234,"elapsed":1000}`.
+// Enabled with `?progress=json`; independent of the log level.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub enum Value<'a> {
+    Number(f64),
+    Text(&'a str),
+}
+
+pub fn emit(event: &str, fields: &[(&str, Value)]) {
+    if ENABLED.load(Ordering::Relaxed) {
+        web_sys::console::log_1(&json_line(event, fields).into());
+    }
+}
+
+pub fn json_line(event: &str, fields: &[(&str, Value)]) -> String {
+    let mut line = format!("{{\"event\":{}", quote(event));
+    for (key, value) in fields {
+        line.push(',');
+        line.push_str(&quote(key));
+        line.push(':');
+        match value {
+            // JSON has no NaN or infinity.
+            Value::Number(number) if !number.is_finite() => line.push_str("null"),
+            Value::Number(number) => line.push_str(&number.to_string()),
+            Value::Text(text) => line.push_str(&quote(text)),
+        }
+    }
+    line.push('}');
+    line
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            control if control < ' ' => quoted.push_str(&format!("\\u{:04x}", u32::from(control))),
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_one_object_per_line() {
+        let line = json_line("chunk", &[("bytes", Value::Number(1234.)), ("elapsed", Value::Number(1000.5))]);
+        assert_eq!(line, r#"{"event":"chunk","bytes":1234,"elapsed":1000.5}"#);
+    }
+
+    #[test]
+    fn escapes_text_and_nulls_non_finite_numbers() {
+        let line = json_line("ready", &[
+            ("mimeType", Value::Text("audio/ogg; \"x\"\\\n\u{1}")),
+            ("gain", Value::Number(f64::NAN)),
+        ]);
+        let parsed = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        assert_eq!(parsed["event"], "ready");
+        assert_eq!(parsed["mimeType"], "audio/ogg; \"x\"\\\n\u{1}");
+        assert!(parsed["gain"].is_null());
+    }
+}
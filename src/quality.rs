@@ -0,0 +1,123 @@
+// A quick quality readout of a take, computed from its decoded samples.
+// Levels are in dBFS of sample values, i.e. 0 dBFS is full scale without any weighting.
+
+// Samples at (or beyond) this magnitude count as clipped; encoders rarely hit exactly 1.0.
+const CLIP_LEVEL: f32 = 0.999;
+// Windows quieter than this are silence - below the noise floor of typical mics.
+const SILENCE_DBFS: f64 = -50.;
+const SILENCE_WINDOW_S: f32 = 0.05;
+
+#[derive(Clone)]
+pub struct Quality {
+    // `None` for silence.
+    pub peak_dbfs: Option<f64>,
+    pub rms_dbfs: Option<f64>,
+    pub clip_count: usize,
+    // Share (0..=1) of the duration that's silent.
+    pub silence_ratio: f32,
+}
+
+// Over all the channels; silence is the average of the channels'.
+pub fn measure(channels: &[Vec<f32>], sample_rate: f32) -> Quality {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let window = (SILENCE_WINDOW_S * sample_rate) as usize;
+    let peak = channels.iter().map(|channel| peak(channel)).fold(0., f32::max);
+    let samples = channels.iter().map(Vec::len).sum::<usize>();
+    let squares = channels.iter().flatten().map(|sample| f64::from(*sample).powi(2)).sum::<f64>();
+    #[allow(clippy::cast_precision_loss)]
+    let rms = if samples == 0 { 0. } else { (squares / samples as f64).sqrt() };
+    #[allow(clippy::cast_precision_loss)]
+    let silence_ratio = if channels.is_empty() {
+        0.
+    } else {
+        channels.iter().map(|channel| silence_ratio(channel, window)).sum::<f32>() / channels.len() as f32
+    };
+    Quality {
+        peak_dbfs: to_dbfs(f64::from(peak)),
+        rms_dbfs: to_dbfs(rms),
+        clip_count: channels.iter().map(|channel| clip_count(channel)).sum(),
+        silence_ratio,
+    }
+}
+
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0., |peak, sample| peak.max(sample.abs()))
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.;
+    }
+    let squares = samples.iter().map(|sample| f64::from(*sample).powi(2)).sum::<f64>();
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    let rms = (squares / samples.len() as f64).sqrt() as f32;
+    rms
+}
+
+pub fn clip_count(samples: &[f32]) -> usize {
+    samples.iter().filter(|sample| sample.abs() >= CLIP_LEVEL).count()
+}
+
+// Share of the `window`-sample windows whose RMS is below `SILENCE_DBFS`; a shorter last window counts too.
+pub fn silence_ratio(samples: &[f32], window: usize) -> f32 {
+    if samples.is_empty() || window == 0 {
+        return 0.;
+    }
+    let windows = samples.chunks(window).collect::<Vec<_>>();
+    let silent = windows.iter()
+        .filter(|window| to_dbfs(f64::from(rms(window))).map_or(true, |dbfs| dbfs < SILENCE_DBFS))
+        .count();
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = silent as f32 / windows.len() as f32;
+    ratio
+}
+
+fn to_dbfs(amplitude: f64) -> Option<f64> {
+    if amplitude > 0. {
+        Some(20. * amplitude.log10())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_is_the_largest_magnitude() {
+        assert_eq!(peak(&[0.1, -0.7, 0.5]), 0.7);
+        assert_eq!(peak(&[]), 0.);
+    }
+
+    #[test]
+    fn rms_of_a_square_wave_is_its_amplitude() {
+        assert!((rms(&[0.5, -0.5, 0.5, -0.5]) - 0.5).abs() < 1e-6);
+        assert_eq!(rms(&[]), 0.);
+    }
+
+    #[test]
+    fn counts_clipped_samples() {
+        assert_eq!(clip_count(&[1., -1., 0.999, 0.998, 1.5]), 4);
+        assert_eq!(clip_count(&[0.5]), 0);
+    }
+
+    #[test]
+    fn silence_ratio_counts_quiet_windows() {
+        // Four windows, the last one shorter: two loud, two silent.
+        let samples = [0.5, -0.5, 0., 0., 0.0001, -0.0001, 0.5];
+        assert!((silence_ratio(&samples, 2) - 0.5).abs() < f32::EPSILON);
+        assert_eq!(silence_ratio(&[], 2), 0.);
+        assert_eq!(silence_ratio(&samples, 0), 0.);
+    }
+
+    #[test]
+    fn measures_over_all_channels() {
+        let quality = measure(&[vec![0.5; 100], vec![0.; 100]], 1000.);
+        assert!((quality.peak_dbfs.unwrap() - 20. * 0.5_f64.log10()).abs() < 1e-6);
+        assert!((quality.rms_dbfs.unwrap() - 20. * 0.125_f64.sqrt().log10()).abs() < 1e-6);
+        assert_eq!(quality.clip_count, 0);
+        assert!((quality.silence_ratio - 0.5).abs() < f32::EPSILON);
+        assert!(measure(&[], 1000.).peak_dbfs.is_none());
+    }
+}
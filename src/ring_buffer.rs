@@ -0,0 +1,108 @@
+// The latest `capacity` frames of each channel, e.g. for an instant replay; older frames are overwritten.
+// Planar like `Pcm`, and allocated up front so that pushing never allocates.
+pub struct RingBuffer {
+    channels: Vec<Vec<f32>>,
+    capacity: usize,
+    // Where the next frame goes.
+    next: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    pub fn new(channel_count: usize, capacity: usize) -> Self {
+        Self {
+            channels: vec![vec![0.; capacity]; channel_count],
+            capacity,
+            next: 0,
+            len: 0,
+        }
+    }
+
+    // Appends a block of planar frames. A block with fewer channels repeats its first one, extra channels
+    // are dropped (see `pcm::concat`); of a block longer than the capacity only its end is kept.
+    pub fn push(&mut self, block: &[Vec<f32>]) {
+        let frames = block.iter().map(Vec::len).min().unwrap_or(0);
+        if self.capacity == 0 || frames == 0 {
+            return;
+        }
+        let skipped = frames.saturating_sub(self.capacity);
+        let written = frames - skipped;
+        // Up to the end of the storage, and the rest from its start.
+        let first = written.min(self.capacity - self.next);
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let source = match block.get(index).or_else(|| block.first()) {
+                Some(source) => &source[skipped..frames],
+                None => continue,
+            };
+            channel[self.next..self.next + first].copy_from_slice(&source[..first]);
+            channel[..written - first].copy_from_slice(&source[first..]);
+        }
+        self.next = (self.next + written) % self.capacity;
+        self.len = (self.len + written).min(self.capacity);
+    }
+
+    // The last `frames` frames of each channel (all of them if there are fewer), oldest first.
+    pub fn latest(&self, frames: usize) -> Vec<Vec<f32>> {
+        let frames = frames.min(self.len);
+        if frames == 0 {
+            return vec![Vec::new(); self.channels.len()];
+        }
+        let start = (self.next + self.capacity - frames) % self.capacity;
+        self.channels
+            .iter()
+            .map(|channel| {
+                if start + frames <= self.capacity {
+                    channel[start..start + frames].to_vec()
+                } else {
+                    let mut latest = channel[start..].to_vec();
+                    latest.extend_from_slice(&channel[..frames - (self.capacity - start)]);
+                    latest
+                }
+            })
+            .collect()
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_there_is_when_under_filled() {
+        let mut buffer = RingBuffer::new(1, 4);
+        assert_eq!(buffer.latest(3), [Vec::<f32>::new()]);
+        buffer.push(&[vec![1., 2.]]);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.latest(3), [vec![1., 2.]]);
+        assert_eq!(buffer.latest(1), [vec![2.]]);
+    }
+
+    #[test]
+    fn wraps_around_keeping_the_latest_oldest_first() {
+        let mut buffer = RingBuffer::new(2, 4);
+        buffer.push(&[vec![1., 2., 3.], vec![-1., -2., -3.]]);
+        buffer.push(&[vec![4., 5., 6.], vec![-4., -5., -6.]]);
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.latest(4), [vec![3., 4., 5., 6.], vec![-3., -4., -5., -6.]]);
+        assert_eq!(buffer.latest(10), buffer.latest(4));
+    }
+
+    #[test]
+    fn keeps_the_end_of_a_block_longer_than_the_capacity() {
+        let mut buffer = RingBuffer::new(1, 3);
+        buffer.push(&[vec![1.]]);
+        buffer.push(&[vec![2., 3., 4., 5., 6.]]);
+        assert_eq!(buffer.latest(3), [vec![4., 5., 6.]]);
+    }
+
+    #[test]
+    fn a_mono_block_fills_every_channel() {
+        let mut buffer = RingBuffer::new(2, 4);
+        buffer.push(&[vec![1., 2.]]);
+        assert_eq!(buffer.latest(2), [vec![1., 2.], vec![1., 2.]]);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::quality::Quality;
+use seed::prelude::{js_sys, Closure, JsCast, JsValue};
+use std::{cell::RefCell, rc::Rc};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbObjectStoreParameters, IdbOpenDbRequest, IdbRequest, IdbTransactionMode, IdbVersionChangeEvent};
+
+// The whole session list is kept in IndexedDB so it survives a reload.
+// Audio with its metadata and the notes (the description and the tags) live in separate stores,
+// keyed by the recording id, so that editing a note doesn't rewrite megabytes of audio on every keystroke.
+const DATABASE: &str = "mediarecorder";
+// Version of the database layout - the object stores. Bump it with a new step in `upgrade`.
+// 1: `recordings` and `notes`.
+// 2: no new stores, records carry a `schema` (see `SCHEMA`).
+const VERSION: u32 = 2;
+// Version of the shape of a record in `recordings`, stored in each record as `schema`.
+// Records are migrated when read (see `migrate`) and written in the current shape on the next `put`.
+// 1 (no `schema`): before capture modes, i.e. no `captureMode` and no `lossless`.
+// 2: `captureMode` and `lossless`.
+// 3: `quality`, missing until it's measured (and in records of before).
+const SCHEMA: u32 = 3;
+const RECORDINGS: &str = "recordings";
+const NOTES: &str = "notes";
+
+pub struct StoredRecording {
+    pub id: usize,
+    pub bytes: Vec<u8>,
+    pub mime: String,
+    // WAV next to the encoded `bytes`, see `CaptureMode`.
+    pub lossless: Option<Vec<u8>>,
+    pub capture_mode: String,
+    pub muted_regions: Vec<(f64, f64)>,
+    // `(elapsed_ms, label)`
+    pub markers: Vec<(f64, Option<String>)>,
+    // `(elapsed_ms, duration_ms)`; missing in records from before pauses were kept, read as none.
+    pub pauses: Vec<(f64, f64)>,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub quality: Option<Quality>,
+}
+
+pub struct Loaded {
+    pub recordings: Vec<StoredRecording>,
+    // Past every key in the stores, including the records that were skipped - a new recording
+    // with one of those ids would overwrite them.
+    pub next_id: usize,
+}
+
+pub async fn load_all() -> Result<Loaded, JsValue> {
+    let database = open().await?;
+    let recordings = get_all(&database, RECORDINGS).await?;
+    let notes = get_all(&database, NOTES).await?;
+    let mut keys = get_all_keys(&database, RECORDINGS).await?.iter().collect::<Vec<_>>();
+    keys.extend(get_all_keys(&database, NOTES).await?.iter());
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let next_id = keys.iter()
+        .filter_map(JsValue::as_f64)
+        .map(|key| key as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let recordings = recordings
+        .iter()
+        .filter(|recording| {
+            // Written by a newer version of the app (e.g. in another tab); we can't know its shape.
+            let supported = schema(recording) <= SCHEMA;
+            if !supported {
+                log_error!("Skipping a recording stored with a newer schema:", schema(recording));
+            }
+            supported
+        })
+        .filter_map(|recording| match from_js(&migrate(recording)) {
+            Ok(recording) => Some(recording),
+            // The others are still worth restoring.
+            Err(error) => {
+                log_error!("Skipping a stored recording that can't be read:", error);
+                None
+            }
+        })
+        .map(|mut recording| {
+            let note = notes.iter().find(|note| get(note, "id").as_f64() == Some(id_to_f64(recording.id)));
+            if let Some(note) = note {
+                recording.description = get(&note, "text").as_string().unwrap_or_default();
+                // Notes from before tags have none.
+                recording.tags = get(&note, "tags")
+                    .dyn_into::<js_sys::Array>()
+                    .map(|tags| tags.iter().filter_map(|tag| tag.as_string()).collect())
+                    .unwrap_or_default();
+            }
+            recording
+        })
+        .collect();
+    Ok(Loaded { recordings, next_id })
+}
+
+pub async fn put(recording: StoredRecording) -> Result<(), JsValue> {
+    let database = open().await?;
+    write(&database, RECORDINGS, |store| store.put(&to_js(&recording)?)).await
+}
+
+pub async fn put_note(id: usize, text: String, tags: Vec<String>) -> Result<(), JsValue> {
+    let database = open().await?;
+    let note = js_sys::Object::new();
+    js_sys::Reflect::set(&note, &"id".into(), &id_to_f64(id).into())?;
+    js_sys::Reflect::set(&note, &"text".into(), &text.into())?;
+    let tags = tags.iter().map(|tag| JsValue::from(tag.as_str())).collect::<js_sys::Array>();
+    js_sys::Reflect::set(&note, &"tags".into(), &tags)?;
+    write(&database, NOTES, |store| store.put(&note)).await
+}
+
+pub async fn delete(id: usize) -> Result<(), JsValue> {
+    let database = open().await?;
+    let key = JsValue::from(id_to_f64(id));
+    write(&database, RECORDINGS, |store| store.delete(&key)).await?;
+    write(&database, NOTES, |store| store.delete(&key)).await
+}
+
+pub async fn clear() -> Result<(), JsValue> {
+    let database = open().await?;
+    write(&database, RECORDINGS, web_sys::IdbObjectStore::clear).await?;
+    write(&database, NOTES, web_sys::IdbObjectStore::clear).await
+}
+
+// Bytes the origin can still store according to `StorageManager.estimate()`.
+// It's an estimate indeed - browsers pad and round it so that it can't be used for fingerprinting.
+pub async fn remaining_bytes() -> Result<f64, JsValue> {
+    let estimate = JsFuture::from(seed::window().navigator().storage().estimate()?).await?;
+    let quota = get(&estimate, "quota").as_f64().unwrap_or_default();
+    let usage = get(&estimate, "usage").as_f64().unwrap_or_default();
+    Ok((quota - usage).max(0.))
+}
+
+// How many minutes of audio at `bits_per_second` fit into `remaining_bytes`, zero when
+// over the quota. `None` for a bitrate that isn't known.
+pub fn minutes_remaining(remaining_bytes: f64, bits_per_second: f64) -> Option<f64> {
+    if bits_per_second.is_nan() || bits_per_second <= 0. {
+        return None;
+    }
+    Some(remaining_bytes.max(0.) * 8. / bits_per_second / 60.)
+}
+
+thread_local! {
+    // Opened on first use and kept for the session, see `open`.
+    static CONNECTION: RefCell<Option<IdbDatabase>> = const { RefCell::new(None) };
+}
+
+async fn open() -> Result<IdbDatabase, JsValue> {
+    if let Some(database) = CONNECTION.with(|connection| connection.borrow().clone()) {
+        return Ok(database);
+    }
+    let factory = seed::window()
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from(js_sys::Error::new("IndexedDB is not available")))?;
+    let request = factory.open_with_u32(DATABASE, VERSION)?;
+    let upgrade_error = Rc::new(RefCell::new(None));
+    let (upgraded_request, failed_upgrade) = (request.clone(), Rc::clone(&upgrade_error));
+    // Runs before `success` when the database is new (or older than `VERSION`).
+    let on_upgrade_needed = Closure::once_into_js(move |event: JsValue| {
+        if let Err(error) = upgrade(&upgraded_request, &event) {
+            // Aborting fails the open, and the database stays at its old version.
+            if let Some(transaction) = upgraded_request.transaction() {
+                let _ = transaction.abort();
+            }
+            *failed_upgrade.borrow_mut() = Some(error);
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade_needed.unchecked_ref()));
+    let opened = finished(&request).await;
+    if let Some(error) = upgrade_error.borrow_mut().take() {
+        return Err(error);
+    }
+    let database = opened?.unchecked_into::<IdbDatabase>();
+    // A newer version of the app in another tab can only upgrade once every connection is closed;
+    // the next `open` here then gets the upgraded database (or fails, being the older version).
+    let closed_database = database.clone();
+    let on_version_change = Closure::once_into_js(move |_: JsValue| {
+        closed_database.close();
+        CONNECTION.with(|connection| connection.borrow_mut().take());
+    });
+    database.set_onversionchange(Some(on_version_change.unchecked_ref()));
+    CONNECTION.with(|connection| *connection.borrow_mut() = Some(database.clone()));
+    Ok(database)
+}
+
+// Applies the layout steps the database hasn't been through yet.
+fn upgrade(request: &IdbOpenDbRequest, event: &JsValue) -> Result<(), JsValue> {
+    let old_version = event.unchecked_ref::<IdbVersionChangeEvent>().old_version();
+    let database = request.result()?.unchecked_into::<IdbDatabase>();
+    if old_version < 1. {
+        let mut parameters = IdbObjectStoreParameters::new();
+        parameters.key_path(Some(&"id".into()));
+        for name in &[RECORDINGS, NOTES] {
+            database.create_object_store_with_optional_parameters(name, &parameters)?;
+        }
+    }
+    // 1 -> 2 is handled by `migrate` record by record.
+    Ok(())
+}
+
+async fn get_all(database: &IdbDatabase, store: &str) -> Result<js_sys::Array, JsValue> {
+    let request = database.transaction_with_str(store)?.object_store(store)?.get_all()?;
+    Ok(finished(&request).await?.unchecked_into())
+}
+
+async fn get_all_keys(database: &IdbDatabase, store: &str) -> Result<js_sys::Array, JsValue> {
+    let request = database.transaction_with_str(store)?.object_store(store)?.get_all_keys()?;
+    Ok(finished(&request).await?.unchecked_into())
+}
+
+async fn write(
+    database: &IdbDatabase,
+    store: &str,
+    operation: impl FnOnce(&web_sys::IdbObjectStore) -> Result<IdbRequest, JsValue>,
+) -> Result<(), JsValue> {
+    let transaction = database.transaction_with_str_and_mode(store, IdbTransactionMode::Readwrite)?;
+    let request = operation(&transaction.object_store(store)?)?;
+    finished(&request).await?;
+    Ok(())
+}
+
+// Resolves with the request's result once it succeeds.
+async fn finished(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let (succeeded, failed) = (request.clone(), request.clone());
+        let on_success = Closure::once_into_js(move |_: JsValue| {
+            let _ = resolve.call1(&JsValue::NULL, &succeeded.result().unwrap_or(JsValue::UNDEFINED));
+        });
+        let on_error = Closure::once_into_js(move |_: JsValue| {
+            let error = failed.error().ok().flatten().map_or(JsValue::UNDEFINED, JsValue::from);
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+fn to_js(recording: &StoredRecording) -> Result<JsValue, JsValue> {
+    let object = js_sys::Object::new();
+    let set = |key: &str, value: &JsValue| js_sys::Reflect::set(&object, &key.into(), value).map(drop);
+    set("schema", &SCHEMA.into())?;
+    set("id", &id_to_f64(recording.id).into())?;
+    set("bytes", &js_sys::Uint8Array::from(recording.bytes.as_slice()))?;
+    set("mime", &recording.mime.as_str().into())?;
+    if let Some(lossless) = recording.lossless.as_ref() {
+        set("lossless", &js_sys::Uint8Array::from(lossless.as_slice()))?;
+    }
+    set("captureMode", &recording.capture_mode.as_str().into())?;
+    let muted_regions = recording.muted_regions.iter()
+        .map(|(start_ms, end_ms)| js_sys::Array::of2(&(*start_ms).into(), &(*end_ms).into()))
+        .collect::<js_sys::Array>();
+    set("mutedRegions", &muted_regions)?;
+    let markers = recording.markers.iter()
+        .map(|(elapsed_ms, label)| js_sys::Array::of2(&(*elapsed_ms).into(), &label.as_deref().into()))
+        .collect::<js_sys::Array>();
+    set("markers", &markers)?;
+    let pauses = recording.pauses.iter()
+        .map(|(elapsed_ms, duration_ms)| js_sys::Array::of2(&(*elapsed_ms).into(), &(*duration_ms).into()))
+        .collect::<js_sys::Array>();
+    set("pauses", &pauses)?;
+    if let Some(quality) = recording.quality.as_ref() {
+        let summary = js_sys::Object::new();
+        let set = |key: &str, value: &JsValue| js_sys::Reflect::set(&summary, &key.into(), value).map(drop);
+        set("peakDbfs", &quality.peak_dbfs.into())?;
+        set("rmsDbfs", &quality.rms_dbfs.into())?;
+        set("clipCount", &id_to_f64(quality.clip_count).into())?;
+        set("silenceRatio", &quality.silence_ratio.into())?;
+        js_sys::Reflect::set(&object, &"quality".into(), &summary)?;
+    }
+    Ok(object.into())
+}
+
+fn from_js(value: &JsValue) -> Result<StoredRecording, JsValue> {
+    let invalid = || JsValue::from(js_sys::Error::new("invalid stored recording"));
+    let pairs = |key: &str| {
+        get(value, key)
+            .dyn_into::<js_sys::Array>()
+            .map(|array| array.iter().map(|pair| pair.unchecked_into::<js_sys::Array>()).collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let id = get(value, "id").as_f64().ok_or_else(invalid)? as usize;
+    Ok(StoredRecording {
+        id,
+        bytes: get(value, "bytes").dyn_into::<js_sys::Uint8Array>().map_err(|_| invalid())?.to_vec(),
+        mime: get(value, "mime").as_string().ok_or_else(invalid)?,
+        lossless: get(value, "lossless").dyn_into::<js_sys::Uint8Array>().ok().map(|bytes| bytes.to_vec()),
+        capture_mode: get(value, "captureMode").as_string().ok_or_else(invalid)?,
+        muted_regions: pairs("mutedRegions")
+            .iter()
+            .filter_map(|pair| Some((pair.get(0).as_f64()?, pair.get(1).as_f64()?)))
+            .collect(),
+        markers: pairs("markers")
+            .iter()
+            .filter_map(|pair| Some((pair.get(0).as_f64()?, pair.get(1).as_string())))
+            .collect(),
+        pauses: pairs("pauses")
+            .iter()
+            .filter_map(|pair| Some((pair.get(0).as_f64()?, pair.get(1).as_f64()?)))
+            .collect(),
+        description: String::new(),
+        tags: Vec::new(),
+        quality: quality_from_js(&get(value, "quality")),
+    })
+}
+
+fn quality_from_js(value: &JsValue) -> Option<Quality> {
+    if !value.is_object() {
+        return None;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let clip_count = get(value, "clipCount").as_f64()? as usize;
+    #[allow(clippy::cast_possible_truncation)]
+    let silence_ratio = get(value, "silenceRatio").as_f64()? as f32;
+    Some(Quality {
+        peak_dbfs: get(value, "peakDbfs").as_f64(),
+        rms_dbfs: get(value, "rmsDbfs").as_f64(),
+        clip_count,
+        silence_ratio,
+    })
+}
+
+fn schema(record: &JsValue) -> u32 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let schema = get(record, "schema").as_f64().map_or(1, |schema| schema as u32);
+    schema
+}
+
+// Brings a record of an older `SCHEMA` to the current shape.
+fn migrate(record: JsValue) -> JsValue {
+    for (key, value) in migration(schema(&record)) {
+        let _ = js_sys::Reflect::set(&record, &key.into(), &value.into());
+    }
+    record
+}
+
+// The fields a record of `schema` lacks, with the values they have for it, one version at a time.
+fn migration(schema: u32) -> Vec<(&'static str, &'static str)> {
+    let mut fields = Vec::new();
+    if schema < 2 {
+        // Recordings stored before capture modes existed were compressed-only.
+        fields.push(("captureMode", "compressed"));
+    }
+    fields
+}
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(object, &key.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn id_to_f64(id: usize) -> f64 {
+    id as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_1_records_were_compressed_only() {
+        assert_eq!(migration(1), [("captureMode", "compressed")]);
+        // `quality` is optional, there's nothing to fill in.
+        assert!(migration(2).is_empty());
+        assert!(migration(SCHEMA).is_empty());
+    }
+
+    #[test]
+    fn minutes_at_the_bitrate() {
+        // 128 kbit/s is 960 kB a minute.
+        assert_eq!(minutes_remaining(9_600_000., 128_000.), Some(10.));
+    }
+
+    #[test]
+    fn no_minutes_without_space() {
+        assert_eq!(minutes_remaining(0., 128_000.), Some(0.));
+        assert_eq!(minutes_remaining(-1_000., 128_000.), Some(0.));
+    }
+
+    #[test]
+    fn no_estimate_without_a_bitrate() {
+        assert_eq!(minutes_remaining(1_000., 0.), None);
+        assert_eq!(minutes_remaining(1_000., -1.), None);
+        assert_eq!(minutes_remaining(1_000., f64::NAN), None);
+    }
+}
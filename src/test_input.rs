@@ -0,0 +1,43 @@
+use seed::prelude::JsValue;
+use web_sys::{AudioContext, GainNode, MediaStream, MediaStreamAudioDestinationNode, OscillatorNode};
+
+// A synthetic mic for end-to-end tests in CI, where there's no real one: a steady sine through a
+// `MediaStreamAudioDestinationNode`, so every take records the same, known signal. Only built with
+// the `test-input` feature and only used with `?input=test-tone`; see `request_audio_stream`.
+const FREQUENCY: f32 = 440.;
+// Half scale, clear of clipping after any processing of the browser.
+const GAIN: f32 = 0.5;
+
+pub struct TestInput {
+    context: AudioContext,
+    oscillator: OscillatorNode,
+    _gain: GainNode,
+    destination: MediaStreamAudioDestinationNode,
+}
+
+impl TestInput {
+    pub fn new() -> Result<Self, JsValue> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        oscillator.frequency().set_value(FREQUENCY);
+        let gain = context.create_gain()?;
+        gain.gain().set_value(GAIN);
+        let destination = context.create_media_stream_destination()?;
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&destination)?;
+        oscillator.start()?;
+        Ok(Self { context, oscillator, _gain: gain, destination })
+    }
+
+    // Stopping its tracks (as releasing the mic does) ends it for good, so each acquisition takes a new input.
+    pub fn stream(&self) -> MediaStream {
+        self.destination.stream()
+    }
+}
+
+impl Drop for TestInput {
+    fn drop(&mut self) {
+        let _ = self.oscillator.disconnect();
+        let _ = self.context.close();
+    }
+}
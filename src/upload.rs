@@ -0,0 +1,213 @@
+use std::ops::Range;
+
+// How a recording leaves the browser: in `MultipartUpload` parts POSTed to our endpoint,
+// or in one PUT to a presigned URL (e.g. S3's) that the host app got from its backend.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UploadMethod {
+    Post,
+    Put,
+}
+
+impl UploadMethod {
+    pub const ALL: [Self; 2] = [Self::Post, Self::Put];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Post => "POST",
+            Self::Put => "PUT",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|method| method.as_str() == value)
+    }
+}
+
+impl Default for UploadMethod {
+    fn default() -> Self {
+        Self::Post
+    }
+}
+
+// A PUT of the whole recording to a presigned URL, built apart from the `fetch` that sends it.
+pub struct PutRequest {
+    pub url: String,
+    // Without parameters like `codecs` - a presigned URL is usually signed for the bare type.
+    pub content_type: String,
+}
+
+pub fn put_request(url: &str, mime: &str) -> Result<PutRequest, String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return Err("no presigned URL set".to_owned());
+    }
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(format!("the presigned URL must be http(s): {}", url));
+    }
+    let content_type = mime.split(';').next().unwrap_or_default().trim();
+    Ok(PutRequest {
+        url: url.to_owned(),
+        content_type: if content_type.is_empty() { "application/octet-stream" } else { content_type }.to_owned(),
+    })
+}
+
+// Storage services answer a successful PUT with 200 (S3) or 204, without a body.
+pub const fn put_succeeded(status: u16) -> bool {
+    matches!(status, 200 | 204)
+}
+
+// Bookkeeping of a chunked upload, kept apart from the network calls:
+// `update` asks for the next part, sends it and reports back the result.
+// Parts are sent one after another; a failed part is retried up to `max_attempts` times.
+pub struct MultipartUpload {
+    pub recording_id: usize,
+    parts: Vec<Part>,
+    max_attempts: u32,
+}
+
+struct Part {
+    range: Range<usize>,
+    state: PartState,
+    attempts: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PartState {
+    Pending,
+    InFlight,
+    Done,
+    Failed,
+}
+
+// Splits `len` bytes into consecutive ranges of `part_size` bytes; the last one may be shorter.
+pub fn split_into_parts(len: usize, part_size: usize) -> Vec<Range<usize>> {
+    assert!(part_size > 0, "part size must be positive");
+    (0..len).step_by(part_size)
+        .map(|start| start..(start + part_size).min(len))
+        .collect()
+}
+
+impl MultipartUpload {
+    pub fn new(recording_id: usize, len: usize, part_size: usize, max_attempts: u32) -> Self {
+        let parts = split_into_parts(len, part_size).into_iter()
+            .map(|range| Part { range, state: PartState::Pending, attempts: 0 })
+            .collect();
+        Self { recording_id, parts, max_attempts }
+    }
+
+    pub fn part_count(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn done_count(&self) -> usize {
+        self.parts.iter().filter(|part| part.state == PartState::Done).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.parts.iter().all(|part| part.state == PartState::Done)
+    }
+
+    // True when a part ran out of attempts; the upload only continues after `retry_failed`.
+    pub fn has_failed(&self) -> bool {
+        self.parts.iter().any(|part| part.state == PartState::Failed)
+    }
+
+    // Marks the next part as in flight and returns its index and byte range.
+    // `None` while another part is in flight, after a failure, or when everything is done.
+    pub fn next_part(&mut self) -> Option<(usize, Range<usize>)> {
+        if self.has_failed() || self.parts.iter().any(|part| part.state == PartState::InFlight) {
+            return None;
+        }
+        let (index, part) = self.parts.iter_mut()
+            .enumerate()
+            .find(|(_, part)| part.state == PartState::Pending)?;
+        part.state = PartState::InFlight;
+        part.attempts += 1;
+        Some((index, part.range.clone()))
+    }
+
+    pub fn part_succeeded(&mut self, index: usize) {
+        self.parts[index].state = PartState::Done;
+    }
+
+    pub fn part_failed(&mut self, index: usize) {
+        let part = &mut self.parts[index];
+        part.state = if part.attempts < self.max_attempts {
+            PartState::Pending
+        } else {
+            PartState::Failed
+        };
+    }
+
+    // Gives the parts that ran out of attempts another round.
+    pub fn retry_failed(&mut self) {
+        for part in self.parts.iter_mut().filter(|part| part.state == PartState::Failed) {
+            part.state = PartState::Pending;
+            part.attempts = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_the_put_request() {
+        let request = put_request("  https://bucket.example.com/take.ogg?X-Amz-Signature=abc ", "audio/ogg;codecs=opus").unwrap();
+        assert_eq!(request.url, "https://bucket.example.com/take.ogg?X-Amz-Signature=abc");
+        assert_eq!(request.content_type, "audio/ogg");
+        assert_eq!(put_request("http://localhost/upload", "").unwrap().content_type, "application/octet-stream");
+    }
+
+    #[test]
+    fn rejects_missing_and_non_http_urls() {
+        assert!(put_request(" ", "audio/ogg").is_err());
+        assert!(put_request("ftp://example.com/take.ogg", "audio/ogg").is_err());
+    }
+
+    #[test]
+    fn put_succeeds_with_200_or_204() {
+        assert!(put_succeeded(200));
+        assert!(put_succeeded(204));
+        assert!(!put_succeeded(201));
+        assert!(!put_succeeded(403));
+    }
+
+    #[test]
+    fn splits_into_consecutive_parts() {
+        assert_eq!(split_into_parts(10, 4), [0..4, 4..8, 8..10]);
+        assert_eq!(split_into_parts(8, 4), [0..4, 4..8]);
+        assert_eq!(split_into_parts(3, 4), vec![Range { start: 0, end: 3 }]);
+        assert!(split_into_parts(0, 4).is_empty());
+    }
+
+    #[test]
+    fn sends_the_parts_in_order_one_at_a_time() {
+        let mut upload = MultipartUpload::new(7, 10, 4, 3);
+        assert_eq!(upload.part_count(), 3);
+        assert_eq!(upload.next_part(), Some((0, 0..4)));
+        assert_eq!(upload.next_part(), None);
+        upload.part_succeeded(0);
+        assert_eq!(upload.next_part(), Some((1, 4..8)));
+        upload.part_succeeded(1);
+        assert_eq!(upload.next_part(), Some((2, 8..10)));
+        upload.part_succeeded(2);
+        assert_eq!(upload.next_part(), None);
+        assert!(upload.is_complete());
+        assert_eq!(upload.done_count(), 3);
+    }
+
+    #[test]
+    fn retries_a_part_under_the_same_index() {
+        let mut upload = MultipartUpload::new(7, 10, 4, 2);
+        upload.next_part();
+        upload.part_failed(0);
+        assert_eq!(upload.next_part(), Some((0, 0..4)));
+        upload.part_failed(0);
+        assert!(upload.has_failed());
+        assert_eq!(upload.next_part(), None);
+        upload.retry_failed();
+        assert_eq!(upload.next_part(), Some((0, 0..4)));
+    }
+}
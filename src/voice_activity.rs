@@ -0,0 +1,102 @@
+// Hysteresis for the voice activation: we start recording once the level goes above
+// `start_threshold`, but only stop after it has stayed below the (lower) `stop_threshold`
+// for `silence_timeout_ms`. With a single threshold the recorder would flap on and off
+// with every breath between two words.
+pub struct VoiceActivityDetector {
+    pub start_threshold: f32,
+    pub stop_threshold: f32,
+    pub silence_timeout_ms: f64,
+    active: bool,
+    silent_since_ms: Option<f64>,
+}
+
+impl Default for VoiceActivityDetector {
+    fn default() -> Self {
+        Self {
+            start_threshold: 0.1,
+            stop_threshold: 0.05,
+            silence_timeout_ms: 1500.,
+            active: false,
+            silent_since_ms: None,
+        }
+    }
+}
+
+impl VoiceActivityDetector {
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Forget the current activity, e.g. when the voice activation has been switched off.
+    pub fn reset(&mut self) {
+        self.active = false;
+        self.silent_since_ms = None;
+    }
+
+    // Feeds a new level sample (0.0 - 1.0) taken at `now_ms`.
+    // Returns `Some(active)` only when the activity has changed.
+    pub fn update(&mut self, level: f32, now_ms: f64) -> Option<bool> {
+        if !self.active {
+            if level >= self.start_threshold {
+                self.active = true;
+                self.silent_since_ms = None;
+                return Some(true);
+            }
+            return None;
+        }
+
+        if level >= self.stop_threshold {
+            self.silent_since_ms = None;
+            return None;
+        }
+        let silent_since_ms = *self.silent_since_ms.get_or_insert(now_ms);
+        if now_ms - silent_since_ms >= self.silence_timeout_ms {
+            self.reset();
+            return Some(false);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_above_the_start_threshold() {
+        let mut detector = VoiceActivityDetector::default();
+        assert_eq!(detector.update(0.09, 0.), None);
+        assert_eq!(detector.update(0.1, 10.), Some(true));
+        assert!(detector.is_active());
+        assert_eq!(detector.update(0.2, 20.), None);
+    }
+
+    #[test]
+    fn a_dip_between_the_thresholds_does_not_stop() {
+        let mut detector = VoiceActivityDetector::default();
+        detector.update(0.2, 0.);
+        assert_eq!(detector.update(0.07, 100.), None);
+        assert_eq!(detector.update(0.07, 10_000.), None);
+        assert!(detector.is_active());
+    }
+
+    #[test]
+    fn stops_after_the_silence_timeout() {
+        let mut detector = VoiceActivityDetector::default();
+        detector.update(0.2, 0.);
+        assert_eq!(detector.update(0.01, 100.), None);
+        assert_eq!(detector.update(0.01, 1599.), None);
+        assert_eq!(detector.update(0.01, 1600.), Some(false));
+        assert!(!detector.is_active());
+    }
+
+    #[test]
+    fn speech_restarts_the_silence_timeout() {
+        let mut detector = VoiceActivityDetector::default();
+        detector.update(0.2, 0.);
+        detector.update(0.01, 100.);
+        detector.update(0.06, 1000.);
+        assert_eq!(detector.update(0.01, 1600.), None);
+        assert_eq!(detector.update(0.01, 3100.), Some(false));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::pcm::{self, Pcm};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl BitDepth {
+    pub const ALL: [Self; 3] = [Self::Int16, Self::Int24, Self::Float32];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Int16 => "16-bit",
+            Self::Int24 => "24-bit",
+            Self::Float32 => "32-bit float",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|depth| depth.as_str() == value)
+    }
+
+    pub const fn bits(self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+
+    // The `wFormatTag` of the `fmt ` chunk: 1 is integer PCM, 3 IEEE float.
+    const fn format_tag(self) -> u16 {
+        match self {
+            Self::Int16 | Self::Int24 => 1,
+            Self::Float32 => 3,
+        }
+    }
+
+    // Appends `sample` in this depth, little-endian.
+    pub fn write_sample(self, bytes: &mut Vec<u8>, sample: f32) {
+        match self {
+            Self::Int16 => bytes.extend_from_slice(&quantize_i16(sample).to_le_bytes()),
+            Self::Int24 => bytes.extend_from_slice(&quantize_i24(sample)),
+            Self::Float32 => bytes.extend_from_slice(&quantize_f32(sample).to_le_bytes()),
+        }
+    }
+}
+
+impl Default for BitDepth {
+    fn default() -> Self {
+        Self::Int16
+    }
+}
+
+// Encodes PCM as a 16-bit little-endian WAV file (RIFF header + interleaved samples).
+pub fn encode(pcm: &Pcm) -> Vec<u8> {
+    encode_with(pcm, BitDepth::Int16, None)
+}
+
+// Like `encode` in `bit_depth`, with `comment` in a `LIST/INFO` chunk (as `ICMT`) after the samples.
+pub fn encode_with(pcm: &Pcm, bit_depth: BitDepth, comment: Option<&str>) -> Vec<u8> {
+    let bits_per_sample = bit_depth.bits();
+    let bytes_per_sample = usize::from(bits_per_sample / 8);
+
+    #[allow(clippy::cast_possible_truncation)]
+    let channel_count = pcm.channels.len() as u16;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let sample_rate = pcm.sample_rate as u32;
+    let block_align = channel_count * bits_per_sample / 8;
+    let data_len = pcm.frames() * usize::from(channel_count) * bytes_per_sample;
+    #[allow(clippy::cast_possible_truncation)]
+    let data_len_u32 = data_len as u32;
+    #[allow(clippy::cast_possible_truncation)]
+    let frames_u32 = pcm.frames() as u32;
+
+    // Formats other than integer PCM have a `cbSize` (of 0 here) ending the `fmt ` chunk,
+    // and a `fact` chunk with the number of frames.
+    let is_float = bit_depth.format_tag() != 1;
+    let fmt_len: u32 = if is_float { 18 } else { 16 };
+    let fact_len: u32 = if is_float { 12 } else { 0 };
+
+    let list = comment.map(info_list).unwrap_or_default();
+    #[allow(clippy::cast_possible_truncation)]
+    let list_len_u32 = list.len() as u32;
+
+    // Chunks start at even offsets; the pad byte isn't part of the `data` length, but of the RIFF one.
+    let pad = data_len % 2;
+    #[allow(clippy::cast_possible_truncation)]
+    let pad_u32 = pad as u32;
+
+    let header_len = 4 + (8 + fmt_len) + fact_len + 8;
+    let mut bytes = Vec::with_capacity(8 + header_len as usize + data_len + pad + list.len());
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(header_len + data_len_u32 + pad_u32 + list_len_u32).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&fmt_len.to_le_bytes());
+    bytes.extend_from_slice(&bit_depth.format_tag().to_le_bytes());
+    bytes.extend_from_slice(&channel_count.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&bits_per_sample.to_le_bytes());
+    if is_float {
+        bytes.extend_from_slice(&0_u16.to_le_bytes());
+        bytes.extend_from_slice(b"fact");
+        bytes.extend_from_slice(&4_u32.to_le_bytes());
+        bytes.extend_from_slice(&frames_u32.to_le_bytes());
+    }
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len_u32.to_le_bytes());
+    for sample in pcm::interleave(&pcm.channels) {
+        bit_depth.write_sample(&mut bytes, sample);
+    }
+    bytes.resize(bytes.len() + pad, 0);
+    bytes.extend_from_slice(&list);
+    bytes
+}
+
+// A whole `LIST` chunk with a single `ICMT` subchunk. Text in INFO subchunks is nul-terminated
+// and every chunk is padded to an even length.
+fn info_list(comment: &str) -> Vec<u8> {
+    let mut text = comment.as_bytes().to_vec();
+    text.push(0);
+    #[allow(clippy::cast_possible_truncation)]
+    let text_len = text.len() as u32;
+    if text.len() % 2 == 1 {
+        text.push(0);
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let list_len = (4 + 8 + text.len()) as u32;
+
+    let mut list = Vec::with_capacity(8 + 12 + text.len());
+    list.extend_from_slice(b"LIST");
+    list.extend_from_slice(&list_len.to_le_bytes());
+    list.extend_from_slice(b"INFO");
+    list.extend_from_slice(b"ICMT");
+    list.extend_from_slice(&text_len.to_le_bytes());
+    list.extend_from_slice(&text);
+    list
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn quantize_i16(sample: f32) -> i16 {
+    (sample.clamp(-1., 1.) * f32::from(i16::MAX)) as i16
+}
+
+// The lowest three bytes of the little-endian `i32`, which keeps the sign of a 24-bit value.
+pub fn quantize_i24(sample: f32) -> [u8; 3] {
+    let [low, middle, high, _] = to_i24(sample).to_le_bytes();
+    [low, middle, high]
+}
+
+// In the range of a signed 24-bit integer.
+pub fn to_i24(sample: f32) -> i32 {
+    const MAX: f64 = 8_388_607.;
+    #[allow(clippy::cast_possible_truncation)]
+    let value = (f64::from(sample.clamp(-1., 1.)) * MAX) as i32;
+    value
+}
+
+// Float samples could go past full scale, but most players would clip them anyway - so they're
+// clamped like the integer ones, and a NaN becomes silence.
+pub fn quantize_f32(sample: f32) -> f32 {
+    if sample.is_nan() {
+        0.
+    } else {
+        sample.clamp(-1., 1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    #[test]
+    fn clips_at_full_scale() {
+        assert_eq!(quantize_i16(1.), i16::MAX);
+        assert_eq!(quantize_i16(1.5), i16::MAX);
+        assert_eq!(quantize_i16(-1.), -i16::MAX);
+        assert_eq!(quantize_i16(-1.5), -i16::MAX);
+        assert_eq!(to_i24(1.5), 8_388_607);
+        assert_eq!(to_i24(-1.5), -8_388_607);
+        assert_eq!(quantize_f32(1.5), 1.);
+        assert_eq!(quantize_f32(-1.5), -1.);
+        assert_eq!(quantize_f32(f32::NAN), 0.);
+        assert_eq!(quantize_f32(0.25), 0.25);
+    }
+
+    #[test]
+    fn packs_24_bit_little_endian() {
+        assert_eq!(quantize_i24(1.), [0xff, 0xff, 0x7f]);
+        assert_eq!(quantize_i24(-1.), [0x01, 0x00, 0x80]);
+        assert_eq!(quantize_i24(0.), [0, 0, 0]);
+        let mut bytes = Vec::new();
+        BitDepth::Int24.write_sample(&mut bytes, 0.5);
+        // 0.5 * 8388607 = 4194303.5, truncated to 0x3fffff.
+        assert_eq!(bytes, [0xff, 0xff, 0x3f]);
+    }
+
+    #[test]
+    fn writes_format_tag_and_bits() {
+        let pcm = Pcm { sample_rate: 48000., channels: vec![vec![0.; 4], vec![0.; 4]] };
+        let int24 = encode_with(&pcm, BitDepth::Int24, None);
+        assert_eq!(&int24[20..24], &[1, 0, 2, 0]);
+        assert_eq!(&int24[32..36], &[6, 0, 24, 0]);
+        let float = encode_with(&pcm, BitDepth::Float32, None);
+        assert_eq!(&float[20..22], &[3, 0]);
+        assert_eq!(&float[34..36], &[32, 0]);
+        assert_eq!(&float[38..42], b"fact");
+        assert_eq!(u32_at(&float, 4) as usize, float.len() - 8);
+    }
+
+    #[test]
+    fn pads_odd_data_before_the_list() {
+        // 24-bit mono with 3 frames: 9 bytes of samples.
+        let pcm = Pcm { sample_rate: 8000., channels: vec![vec![0.1, 0.2, 0.3]] };
+        let wav = encode_with(&pcm, BitDepth::Int24, Some("hi"));
+        assert_eq!(u32_at(&wav, 40), 9);
+        assert_eq!(wav[44 + 9], 0);
+        assert_eq!(&wav[44 + 10..44 + 14], b"LIST");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+
+        let even = encode_with(&Pcm { sample_rate: 8000., channels: vec![vec![0.; 2]] }, BitDepth::Int16, None);
+        assert_eq!(even.len(), 44 + 4);
+    }
+}
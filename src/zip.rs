@@ -0,0 +1,143 @@
+// Minimal ZIP writer for bundling recordings. Entries are stored uncompressed -
+// the audio is compressed already - and there's no ZIP64, so the archive has to stay
+// below 4 GiB and 65535 entries.
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+// 2.0, the minimum for plain stored entries.
+const VERSION: u16 = 20;
+// Bit 11: names are UTF-8.
+const FLAGS: u16 = 0x0800;
+const METHOD_STORED: u16 = 0;
+// MS-DOS time 00:00:00 and date 1980-01-01, the earliest representable.
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = 0x21;
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn store(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+    for (name, bytes) in files {
+        let offset = archive.len() as u32;
+        let crc = crc32(bytes);
+
+        put_u32(&mut archive, LOCAL_HEADER_SIGNATURE);
+        put_common_fields(&mut archive, name, bytes, crc);
+        put_u16(&mut archive, 0); // extra field length
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(bytes);
+
+        put_u32(&mut central_directory, CENTRAL_HEADER_SIGNATURE);
+        put_u16(&mut central_directory, VERSION); // version made by
+        put_common_fields(&mut central_directory, name, bytes, crc);
+        put_u16(&mut central_directory, 0); // extra field length
+        put_u16(&mut central_directory, 0); // comment length
+        put_u16(&mut central_directory, 0); // disk number
+        put_u16(&mut central_directory, 0); // internal attributes
+        put_u32(&mut central_directory, 0); // external attributes
+        put_u32(&mut central_directory, offset);
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+
+    let central_directory_offset = archive.len() as u32;
+    archive.extend_from_slice(&central_directory);
+    put_u32(&mut archive, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+    put_u16(&mut archive, 0); // this disk
+    put_u16(&mut archive, 0); // disk with the central directory
+    put_u16(&mut archive, files.len() as u16); // entries on this disk
+    put_u16(&mut archive, files.len() as u16); // entries in total
+    put_u32(&mut archive, central_directory.len() as u32);
+    put_u32(&mut archive, central_directory_offset);
+    put_u16(&mut archive, 0); // comment length
+    archive
+}
+
+// The fields local and central headers share, from "version needed" up to the name length.
+#[allow(clippy::cast_possible_truncation)]
+fn put_common_fields(buffer: &mut Vec<u8>, name: &str, bytes: &[u8], crc: u32) {
+    put_u16(buffer, VERSION);
+    put_u16(buffer, FLAGS);
+    put_u16(buffer, METHOD_STORED);
+    put_u16(buffer, DOS_TIME);
+    put_u16(buffer, DOS_DATE);
+    put_u32(buffer, crc);
+    put_u32(buffer, bytes.len() as u32); // compressed size
+    put_u32(buffer, bytes.len() as u32); // uncompressed size
+    put_u16(buffer, name.len() as u16);
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+// CRC-32 (IEEE), bit by bit - fast enough for a handful of recordings.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0_u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn lays_out_headers_directory_and_end_record() {
+        let files = vec![("a.ogg".to_owned(), b"123456789".to_vec()), ("bc.wav".to_owned(), vec![7; 3])];
+        let archive = store(&files);
+
+        // Local headers: 30 bytes, the name, then the data.
+        assert_eq!(u32_at(&archive, 0), LOCAL_HEADER_SIGNATURE);
+        assert_eq!(u32_at(&archive, 14), 0xcbf4_3926);
+        assert_eq!(u32_at(&archive, 18), 9);
+        assert_eq!(u32_at(&archive, 22), 9);
+        assert_eq!(u16_at(&archive, 26), 5);
+        assert_eq!(&archive[30..35], b"a.ogg");
+        assert_eq!(&archive[35..44], b"123456789");
+        let second = 44;
+        assert_eq!(u32_at(&archive, second), LOCAL_HEADER_SIGNATURE);
+        let central_directory = second + 30 + 6 + 3;
+
+        // Central directory: 46 bytes and the name per entry, pointing back at the local headers.
+        assert_eq!(u32_at(&archive, central_directory), CENTRAL_HEADER_SIGNATURE);
+        assert_eq!(u32_at(&archive, central_directory + 16), 0xcbf4_3926);
+        assert_eq!(u32_at(&archive, central_directory + 42), 0);
+        let second_entry = central_directory + 46 + 5;
+        assert_eq!(u32_at(&archive, second_entry), CENTRAL_HEADER_SIGNATURE);
+        assert_eq!(u32_at(&archive, second_entry + 42) as usize, second);
+        let end = second_entry + 46 + 6;
+
+        // End of central directory record, the last 22 bytes.
+        assert_eq!(archive.len(), end + 22);
+        assert_eq!(u32_at(&archive, end), END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        assert_eq!(u16_at(&archive, end + 8), 2);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        assert_eq!(u32_at(&archive, end + 12) as usize, end - central_directory);
+        assert_eq!(u32_at(&archive, end + 16) as usize, central_directory);
+    }
+}